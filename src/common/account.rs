@@ -1,7 +1,7 @@
 //! Generalises the solana-sdk::account::Account type to allow for any type that can 
 //! provide the raw byte data of a Solana account. This is subject to change.
 
-use solana_sdk::account::Account;
+use solana_sdk::{account::Account, pubkey::Pubkey};
 
 /// A trait for any type that can provide the raw byte data of a Solana account.
 ///
@@ -16,6 +16,9 @@ pub trait AccountData {
     /// This is more efficient than `bytes()` when the caller needs ownership of the
     /// data, as it avoids a clone.
     fn into_bytes(self) -> Vec<u8> where Self: Sized; // consumes
+
+    /// The program owning the account, e.g. to tell Token and Token-2022 mints apart.
+    fn owner(&self) -> &Pubkey;
 }
 
 /// Provide a default implementation for the most common account type.
@@ -30,4 +33,8 @@ impl AccountData for Account {
     fn into_bytes(self) -> Vec<u8> {
         self.data // consumes
     }
+
+    fn owner(&self) -> &Pubkey {
+        &self.owner
+    }
 }
@@ -0,0 +1,169 @@
+//! A cache of the latest blockhash and slot, kept fresh by a background task.
+//!
+//! Every transaction needs a recent blockhash and every freshness decision the current slot. Fetching them on the
+//! hot path costs a round trip, so `BlockhashCache` polls them in the background and serves reads lock-free from
+//! `ArcSwap`s, as `ManagedAccount` does for account data.
+
+use crate::common::{
+    rpc::{LatestBlockhash, RpcProvider},
+    types::AnyResult,
+};
+use anyhow::anyhow;
+use arc_swap::{ArcSwapOption, Guard};
+use solana_sdk::{clock::Slot, hash::Hash};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// A cached blockhash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedBlockhash {
+    pub blockhash: Hash,
+    pub last_valid_block_height: u64,
+    /// The Unix nanoseconds timestamp of the response it came from.
+    pub response_time: u64,
+}
+
+/// A cached slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedSlot {
+    pub slot: Slot,
+    /// The Unix nanoseconds timestamp of the response it came from.
+    pub response_time: u64,
+}
+
+/// The latest blockhash and slot, empty until first refreshed.
+///
+/// Neither ever goes backwards: a response older than the cached value, e.g. from a lagging node behind a load
+/// balancer, is dropped.
+#[derive(Debug, Default)]
+pub struct BlockhashCache {
+    blockhash: ArcSwapOption<CachedBlockhash>,
+    slot: ArcSwapOption<CachedSlot>,
+    /// The error of the last failed background blockhash refresh, cleared by the next successful one.
+    blockhash_error: ArcSwapOption<String>,
+    /// As `blockhash_error`, for the slot.
+    slot_error: ArcSwapOption<String>,
+}
+
+impl BlockhashCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Provides fast, read-only access to the cached blockhash, see `ManagedAccount::get`.
+    pub fn blockhash(&self) -> Guard<Option<Arc<CachedBlockhash>>> {
+        self.blockhash.load()
+    }
+
+    /// Provides fast, read-only access to the cached slot.
+    pub fn slot(&self) -> Guard<Option<Arc<CachedSlot>>> {
+        self.slot.load()
+    }
+
+    /// Returns the cached blockhash, or an error if there isn't one yet.
+    pub fn latest_blockhash(&self) -> AnyResult<Hash> {
+        self.blockhash
+            .load()
+            .as_ref()
+            .map(|cached| cached.blockhash)
+            .ok_or_else(|| anyhow!("No blockhash cached yet"))
+    }
+
+    /// Returns the cached slot, or an error if there isn't one yet.
+    pub fn current_slot(&self) -> AnyResult<Slot> {
+        self.slot
+            .load()
+            .as_ref()
+            .map(|cached| cached.slot)
+            .ok_or_else(|| anyhow!("No slot cached yet"))
+    }
+
+    /// Returns the error of the last background blockhash refresh, if it failed.
+    pub fn last_blockhash_error(&self) -> Option<Arc<String>> {
+        self.blockhash_error.load_full()
+    }
+
+    /// Returns the error of the last background slot refresh, if it failed.
+    pub fn last_slot_error(&self) -> Option<Arc<String>> {
+        self.slot_error.load_full()
+    }
+
+    /// Fetches the latest blockhash, caching it unless it's older than the cached one.
+    pub async fn refresh_blockhash<C: RpcProvider>(&self, rpc_provider: &C) -> AnyResult<()> {
+        let response = rpc_provider.get_latest_blockhash().await?;
+        let LatestBlockhash { blockhash, last_valid_block_height } = response.result;
+        let cached = CachedBlockhash {
+            blockhash,
+            last_valid_block_height,
+            response_time: response.response_time,
+        };
+        self.blockhash.rcu(|current| match current {
+            Some(current) if current.last_valid_block_height > cached.last_valid_block_height => Some(current.clone()),
+            _ => Some(Arc::new(cached)),
+        });
+        Ok(())
+    }
+
+    /// Fetches the current slot, caching it unless it's older than the cached one.
+    pub async fn refresh_slot<C: RpcProvider>(&self, rpc_provider: &C) -> AnyResult<()> {
+        let response = rpc_provider.get_slot().await?;
+        let cached = CachedSlot {
+            slot: response.result,
+            response_time: response.response_time,
+        };
+        self.slot.rcu(|current| match current {
+            Some(current) if current.slot > cached.slot => Some(current.clone()),
+            _ => Some(Arc::new(cached)),
+        });
+        Ok(())
+    }
+
+    /// Spawns a tokio task refreshing the blockhash every `blockhash_interval` and the slot every `slot_interval`.
+    ///
+    /// Failed refreshes keep the cached values and are reported by `last_blockhash_error` and `last_slot_error`.
+    /// The task runs until the returned handle is dropped or stopped.
+    pub fn spawn<C: RpcProvider + 'static>(
+        self: &Arc<Self>,
+        rpc_provider: Arc<C>,
+        blockhash_interval: Duration,
+        slot_interval: Duration,
+    ) -> BlockhashCacheHandle {
+        let cache = self.clone();
+        let task = tokio::spawn(async move {
+            let mut blockhash_ticker = tokio::time::interval(blockhash_interval);
+            let mut slot_ticker = tokio::time::interval(slot_interval);
+            blockhash_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            slot_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                let (result, last_error) = tokio::select! {
+                    _ = blockhash_ticker.tick() => {
+                        (cache.refresh_blockhash(rpc_provider.as_ref()).await, &cache.blockhash_error)
+                    }
+                    _ = slot_ticker.tick() => (cache.refresh_slot(rpc_provider.as_ref()).await, &cache.slot_error),
+                };
+                last_error.store(result.err().map(|e| Arc::new(e.to_string())));
+            }
+        });
+        BlockhashCacheHandle { task }
+    }
+}
+
+/// The background refresh task of a `BlockhashCache`, stopped when dropped.
+#[derive(Debug)]
+pub struct BlockhashCacheHandle {
+    task: JoinHandle<()>,
+}
+
+impl BlockhashCacheHandle {
+    /// Stops the background refresh task.
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for BlockhashCacheHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,508 @@
+//! Wrappers adding resilience to any `RpcProvider`.
+//!
+//! Each wrapper implements `RpcProvider` itself, so they compose, e.g. a `Failover` over endpoints that each
+//! time out and retry:
+//!
+//! ```no_run
+//! # use solana_client::nonblocking::rpc_client::RpcClient;
+//! # use solana_dex_tools::common::combinators::{Failover, RetryPolicy, WithRetry, WithTimeout};
+//! # use std::time::Duration;
+//! # fn main() -> anyhow::Result<()> {
+//! let endpoint = |url: &str| {
+//!     let client = RpcClient::new(url.to_string());
+//!     WithRetry::new(WithTimeout::new(client, Duration::from_millis(500)), RetryPolicy::default())
+//! };
+//! let rpc_provider = Failover::new(vec![endpoint("https://primary"), endpoint("https://backup")])?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! - `WithRetry` retries retriable errors (see `is_retriable`) with exponential backoff.
+//! - `WithTimeout` bounds each call, failing with `RpcTimeout`.
+//! - `Failover` sends each call to the healthiest of several providers, moving on to the next on failure.
+//! - `Hedged` sends each call to two providers, the second after a delay, and takes the first success.
+
+use crate::common::{
+    rate_limit::SharedError,
+    rpc::{LatestBlockhash, RpcProvider, RpcResponse},
+    types::AnyResult,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::future::{select, BoxFuture, Either};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::RpcError;
+use solana_sdk::{clock::Slot, pubkey::Pubkey};
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Returns true if `error` is likely transient, i.e. the same call may succeed if retried or sent elsewhere.
+///
+/// Timeouts, network errors and server-side RPC errors are retriable. Missing accounts, unsupported methods,
+/// malformed requests or responses, and any error we can't classify aren't. Errors shared by coalesced calls are
+/// judged by the original, see `SharedError`, and the errors of `Failover` and `Hedged` by the last one they saw.
+///
+/// Providers with their own error types should pass `RetryPolicy` an `is_retriable` that knows them.
+pub fn is_retriable(error: &anyhow::Error) -> bool {
+    let error = SharedError::original(error);
+    if error.is::<RpcTimeout>() {
+        return true;
+    }
+    if let Some(client_error) = error.downcast_ref::<ClientError>() {
+        return match client_error.kind() {
+            ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) | ClientErrorKind::Middleware(_) => true,
+            ClientErrorKind::RpcError(RpcError::RpcRequestError(_)) => true,
+            // JSON-RPC reserves -32000 to -32099 for server errors, e.g. Solana's "node is unhealthy".
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => (-32099..=-32000).contains(code),
+            _ => false,
+        };
+    }
+    false
+}
+
+// --- Retry --- //
+
+/// How `WithRetry` retries.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts per call, including the first.
+    pub max_attempts: u32,
+    /// The wait before the first retry, doubled for each later one.
+    pub initial_backoff: Duration,
+    /// The cap on the wait between retries.
+    pub max_backoff: Duration,
+    /// Decides which errors are worth retrying.
+    pub is_retriable: fn(&anyhow::Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            is_retriable,
+        }
+    }
+}
+
+/// Retries the calls of the inner provider that fail with a retriable error, see `RetryPolicy`.
+pub struct WithRetry<P: RpcProvider> {
+    inner: P,
+    policy: RetryPolicy,
+}
+
+impl<P: RpcProvider> WithRetry<P> {
+    pub fn new(inner: P, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    async fn retry<'a, T, F>(&'a self, call: F) -> AnyResult<T>
+    where
+        F: Fn(&'a P) -> BoxFuture<'a, AnyResult<T>>,
+    {
+        let mut backoff = self.policy.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match call(&self.inner).await {
+                Err(e) if attempt < self.policy.max_attempts && (self.policy.is_retriable)(&e) => {
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2).min(self.policy.max_backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<P: RpcProvider> RpcProvider for WithRetry<P> {
+    type AccountType = P::AccountType;
+
+    async fn get_account(&self, pubkey: &Pubkey) -> AnyResult<RpcResponse<Self::AccountType>> {
+        self.retry(|inner| inner.get_account(pubkey)).await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>> {
+        self.retry(|inner| inner.get_multiple_accounts(pubkeys)).await
+    }
+
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        self.inner.max_accounts_per_rpc_call()
+    }
+
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        self.retry(|inner| inner.get_latest_blockhash()).await
+    }
+
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        self.retry(|inner| inner.get_slot()).await
+    }
+}
+
+// --- Timeout --- //
+
+/// The error of a call that timed out, see `WithTimeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpcTimeout {
+    pub method: &'static str,
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for RpcTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RPC call {} timed out after {:?}", self.method, self.timeout)
+    }
+}
+
+impl std::error::Error for RpcTimeout {}
+
+/// Fails the calls of the inner provider that take longer than a timeout, with an `RpcTimeout`.
+///
+/// Wrap it in `WithRetry` to retry timed out calls, not the other way round, which would bound all attempts
+/// together.
+pub struct WithTimeout<P: RpcProvider> {
+    inner: P,
+    timeout: Duration,
+}
+
+impl<P: RpcProvider> WithTimeout<P> {
+    pub fn new(inner: P, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    async fn bounded<T>(&self, method: &'static str, call: impl Future<Output = AnyResult<T>>) -> AnyResult<T> {
+        tokio::time::timeout(self.timeout, call).await.map_err(|_| {
+            anyhow::Error::new(RpcTimeout {
+                method,
+                timeout: self.timeout,
+            })
+        })?
+    }
+}
+
+#[async_trait]
+impl<P: RpcProvider> RpcProvider for WithTimeout<P> {
+    type AccountType = P::AccountType;
+
+    async fn get_account(&self, pubkey: &Pubkey) -> AnyResult<RpcResponse<Self::AccountType>> {
+        self.bounded("get_account", self.inner.get_account(pubkey)).await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>> {
+        self.bounded("get_multiple_accounts", self.inner.get_multiple_accounts(pubkeys)).await
+    }
+
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        self.inner.max_accounts_per_rpc_call()
+    }
+
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        self.bounded("get_latest_blockhash", self.inner.get_latest_blockhash()).await
+    }
+
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        self.bounded("get_slot", self.inner.get_slot()).await
+    }
+}
+
+// --- Failover --- //
+
+/// How long `Failover` waits by default before probing a demoted provider, see `Failover::recovery_cooldown`.
+pub const DEFAULT_RECOVERY_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// The health of one of a `Failover`'s providers.
+#[derive(Debug, Default)]
+struct EndpointHealth {
+    /// The number of calls that have failed since the last success.
+    consecutive_failures: AtomicU32,
+    /// An exponentially weighted moving average of successful calls' latency, in nanoseconds, 0 until one succeeds.
+    latency_nanos: AtomicU64,
+    /// When the provider last failed or was last probed, in nanoseconds since the failover was constructed.
+    last_failure_nanos: AtomicU64,
+}
+
+/// A snapshot of the health of one of a `Failover`'s providers, see `Failover::health`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndpointHealthReport {
+    /// The provider's index, as given to `Failover::new`.
+    pub index: usize,
+    pub consecutive_failures: u32,
+    /// The moving average latency of successful calls, `None` until one succeeds.
+    pub latency: Option<Duration>,
+}
+
+/// Sends each call to the healthiest of several providers, moving on to the next if it fails with a retriable
+/// error (see `is_retriable`).
+///
+/// Providers are ranked by their consecutive failures, then by their moving average latency, so a failing
+/// endpoint drops to the back. Once it has gone a cooldown without failing, the next call probes it first: a
+/// success restores it, a failure demotes it for another cooldown. Non-retriable errors, e.g. a missing
+/// account, are returned straight away, as another endpoint would give the same answer.
+pub struct Failover<P: RpcProvider> {
+    providers: Vec<P>,
+    health: Vec<EndpointHealth>,
+    recovery_cooldown: Duration,
+    /// The origin of `EndpointHealth::last_failure_nanos`.
+    created: Instant,
+}
+
+impl<P: RpcProvider> Failover<P> {
+    /// Constructs a failover over `providers`, in order of preference until their health tells them apart.
+    ///
+    /// Fails if `providers` is empty.
+    pub fn new(providers: Vec<P>) -> AnyResult<Self> {
+        if providers.is_empty() {
+            return Err(anyhow!("Failover needs at least one provider"));
+        }
+        let health = providers.iter().map(|_| EndpointHealth::default()).collect();
+        Ok(Self {
+            providers,
+            health,
+            recovery_cooldown: DEFAULT_RECOVERY_COOLDOWN,
+            created: Instant::now(),
+        })
+    }
+
+    /// How long a demoted provider goes without failing before a call probes it. Defaults to
+    /// `DEFAULT_RECOVERY_COOLDOWN`.
+    pub fn recovery_cooldown(mut self, recovery_cooldown: Duration) -> Self {
+        self.recovery_cooldown = recovery_cooldown;
+        self
+    }
+
+    pub fn providers(&self) -> &[P] {
+        &self.providers
+    }
+
+    /// Returns the health of each provider, in the order given to `new`.
+    pub fn health(&self) -> Vec<EndpointHealthReport> {
+        self.health
+            .iter()
+            .enumerate()
+            .map(|(index, health)| {
+                let latency_nanos = health.latency_nanos.load(Ordering::Relaxed);
+                EndpointHealthReport {
+                    index,
+                    consecutive_failures: health.consecutive_failures.load(Ordering::Relaxed),
+                    latency: (latency_nanos > 0).then(|| Duration::from_nanos(latency_nanos)),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the provider indexes, healthiest first.
+    ///
+    /// A demoted provider whose cooldown is up ranks first, to probe it, and its cooldown restarts, so concurrent
+    /// calls don't all probe it.
+    fn ranked(&self) -> Vec<usize> {
+        let now = self.elapsed_nanos();
+        let cooldown = self.recovery_cooldown.as_nanos() as u64;
+        let keys: Vec<(u32, u64)> = self
+            .health
+            .iter()
+            .map(|health| {
+                let failures = health.consecutive_failures.load(Ordering::Relaxed);
+                let last_failure = health.last_failure_nanos.load(Ordering::Relaxed);
+                let probe = failures > 0
+                    && now.saturating_sub(last_failure) >= cooldown
+                    && health
+                        .last_failure_nanos
+                        .compare_exchange(last_failure, now, Ordering::Relaxed, Ordering::Relaxed)
+                        .is_ok();
+                if probe {
+                    (0, 0)
+                } else {
+                    (failures, health.latency_nanos.load(Ordering::Relaxed))
+                }
+            })
+            .collect();
+        let mut indexes: Vec<usize> = (0..self.providers.len()).collect();
+        // A stable sort, so ties keep the order of preference.
+        indexes.sort_by_key(|&i| keys[i]);
+        indexes
+    }
+
+    fn elapsed_nanos(&self) -> u64 {
+        self.created.elapsed().as_nanos() as u64
+    }
+
+    async fn failover<'a, T, F>(&'a self, call: F) -> AnyResult<T>
+    where
+        F: Fn(&'a P) -> BoxFuture<'a, AnyResult<T>>,
+    {
+        let mut failures = Vec::new();
+        let mut last_error = None;
+        for i in self.ranked() {
+            let health = &self.health[i];
+            let start = Instant::now();
+            match call(&self.providers[i]).await {
+                Ok(response) => {
+                    health.consecutive_failures.store(0, Ordering::Relaxed);
+                    let latency = start.elapsed().as_nanos() as u64;
+                    // An EWMA with a weight of 1/8 on the latest sample, seeded by the first.
+                    let _ = health.latency_nanos.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+                        Some(if average == 0 { latency } else { average - average / 8 + latency / 8 })
+                    });
+                    return Ok(response);
+                }
+                Err(e) if is_retriable(&e) => {
+                    health.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+                    health.last_failure_nanos.store(self.elapsed_nanos(), Ordering::Relaxed);
+                    failures.push(format!("provider {}: {}", i, e));
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let message = format!("All RPC providers failed: {}", failures.join("; "));
+        // Keep the last error as the source, so `is_retriable` can still tell what it was.
+        Err(match last_error {
+            Some(e) => e.context(message),
+            None => anyhow!(message),
+        })
+    }
+}
+
+#[async_trait]
+impl<P: RpcProvider> RpcProvider for Failover<P> {
+    type AccountType = P::AccountType;
+
+    async fn get_account(&self, pubkey: &Pubkey) -> AnyResult<RpcResponse<Self::AccountType>> {
+        self.failover(|provider| provider.get_account(pubkey)).await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>> {
+        self.failover(|provider| provider.get_multiple_accounts(pubkeys)).await
+    }
+
+    /// The smallest limit of the providers, as any of them may serve a call.
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        self.providers
+            .iter()
+            .map(|provider| provider.max_accounts_per_rpc_call())
+            .min()
+            .unwrap_or_default()
+    }
+
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        self.failover(|provider| provider.get_latest_blockhash()).await
+    }
+
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        self.failover(|provider| provider.get_slot()).await
+    }
+}
+
+// --- Hedged --- //
+
+/// Sends each call to a primary provider and, if it hasn't answered within a delay, to a secondary one too,
+/// taking the first success. This cuts tail latency at the cost of up to twice the requests.
+///
+/// If one call fails the other is awaited, and the call only fails if both do. A zero delay sends both at once.
+pub struct Hedged<P: RpcProvider, S: RpcProvider<AccountType = P::AccountType>> {
+    primary: P,
+    secondary: S,
+    delay: Duration,
+}
+
+impl<P: RpcProvider, S: RpcProvider<AccountType = P::AccountType>> Hedged<P, S> {
+    pub fn new(primary: P, secondary: S, delay: Duration) -> Self {
+        Self {
+            primary,
+            secondary,
+            delay,
+        }
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+
+    async fn hedge<T>(
+        &self,
+        primary: impl Future<Output = AnyResult<T>>,
+        secondary: impl Future<Output = AnyResult<T>>,
+    ) -> AnyResult<T> {
+        let delay = self.delay;
+        let secondary = async move {
+            tokio::time::sleep(delay).await;
+            secondary.await
+        };
+        let (primary, secondary) = (Box::pin(primary), Box::pin(secondary));
+        match select(primary, secondary).await {
+            Either::Left((Ok(response), _)) | Either::Right((Ok(response), _)) => Ok(response),
+            // The error of the call that failed last is kept as the source, see `is_retriable`.
+            Either::Left((Err(first), secondary)) => secondary.await.map_err(|e| {
+                let message = format!("Both hedged RPC calls failed: primary: {}; secondary: {}", first, e);
+                e.context(message)
+            }),
+            Either::Right((Err(first), primary)) => primary.await.map_err(|e| {
+                let message = format!("Both hedged RPC calls failed: primary: {}; secondary: {}", e, first);
+                e.context(message)
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl<P, S> RpcProvider for Hedged<P, S>
+where
+    P: RpcProvider,
+    S: RpcProvider<AccountType = P::AccountType>,
+{
+    type AccountType = P::AccountType;
+
+    async fn get_account(&self, pubkey: &Pubkey) -> AnyResult<RpcResponse<Self::AccountType>> {
+        self.hedge(self.primary.get_account(pubkey), self.secondary.get_account(pubkey)).await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>> {
+        self.hedge(
+            self.primary.get_multiple_accounts(pubkeys),
+            self.secondary.get_multiple_accounts(pubkeys),
+        )
+        .await
+    }
+
+    /// The smaller limit of the two providers, as both serve every call.
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        self.primary
+            .max_accounts_per_rpc_call()
+            .min(self.secondary.max_accounts_per_rpc_call())
+    }
+
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        self.hedge(self.primary.get_latest_blockhash(), self.secondary.get_latest_blockhash()).await
+    }
+
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        self.hedge(self.primary.get_slot(), self.secondary.get_slot()).await
+    }
+}
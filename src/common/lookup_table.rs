@@ -0,0 +1,134 @@
+//! Address lookup table (ALT) management for the pools we trade.
+//!
+//! A v0 transaction can reference accounts through lookup tables at 1 byte each rather than 32, which is what
+//! makes multi-pool swaps fit in a packet. We derive the accounts worth storing from the pools themselves
+//! (see `Pool::static_accounts`), build the instructions to create and extend a table with them, and track the
+//! on-chain table as a `ManagedAccount<LookupTable>` so it's always ready for message compilation.
+
+use crate::common::{
+    deserialize::Deserializable,
+    pool::Pool,
+    state::{AccountState, ManagedAccount},
+    types::AnyResult,
+};
+use anyhow::anyhow;
+use solana_sdk::{
+    address_lookup_table::{
+        instruction::{create_lookup_table, extend_lookup_table},
+        state::{AddressLookupTable, LookupTableMeta, LOOKUP_TABLE_MAX_ADDRESSES},
+        AddressLookupTableAccount,
+    },
+    clock::Slot,
+    instruction::Instruction,
+    pubkey::Pubkey,
+};
+use std::collections::HashSet;
+
+/// The most addresses we put in a single extend instruction, so each one fits in a transaction on its own.
+pub const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+/// An owned, deserialized address lookup table account.
+///
+/// `solana_sdk`'s `AddressLookupTable` borrows from the account data, which doesn't fit `ManagedAccount`, so we
+/// copy the addresses out.
+#[derive(Debug, Clone)]
+pub struct LookupTable {
+    pub meta: LookupTableMeta,
+    pub addresses: Vec<Pubkey>,
+}
+
+impl Deserializable for LookupTable {
+    fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+        let table = AddressLookupTable::deserialize(bytes)
+            .map_err(|e| anyhow!("Failed to deserialize AddressLookupTable: {}", e))?;
+        Ok(Self {
+            meta: table.meta,
+            addresses: table.addresses.into_owned(),
+        })
+    }
+}
+
+impl LookupTable {
+    /// Returns true if the table has been deactivated, after which it can't be extended and will be closable.
+    pub fn is_deactivated(&self) -> bool {
+        self.meta.deactivation_slot != Slot::MAX
+    }
+
+    /// Returns the addresses of `wanted` the table doesn't hold yet, in order and without duplicates.
+    pub fn missing_addresses(&self, wanted: &[Pubkey]) -> Vec<Pubkey> {
+        let mut seen: HashSet<&Pubkey> = self.addresses.iter().collect();
+        wanted.iter().filter(|address| seen.insert(*address)).copied().collect()
+    }
+}
+
+impl ManagedAccount<LookupTable> {
+    /// Returns the table in the form `TransactionBuilder::lookup_table` takes.
+    pub fn lookup_table_account(&self) -> AddressLookupTableAccount {
+        AddressLookupTableAccount {
+            key: *self.pubkey(),
+            addresses: self.get().addresses.clone(),
+        }
+    }
+
+    /// Builds the instructions extending the table with the addresses of `wanted` it doesn't hold yet, see
+    /// `extend_lookup_table_instructions`. Empty if the table already holds them all.
+    pub fn extend_instructions(
+        &self,
+        authority: Pubkey,
+        payer: Pubkey,
+        wanted: &[Pubkey],
+    ) -> AnyResult<Vec<Instruction>> {
+        let table = self.get();
+        if table.is_deactivated() {
+            return Err(anyhow!("Lookup table {} is deactivated and can't be extended", self.pubkey()));
+        }
+        let missing = table.missing_addresses(wanted);
+        if table.addresses.len() + missing.len() > LOOKUP_TABLE_MAX_ADDRESSES {
+            return Err(anyhow!(
+                "Lookup table {} holds {} addresses, adding {} would exceed the limit of {}",
+                self.pubkey(),
+                table.addresses.len(),
+                missing.len(),
+                LOOKUP_TABLE_MAX_ADDRESSES
+            ));
+        }
+        Ok(extend_lookup_table_instructions(*self.pubkey(), authority, payer, &missing))
+    }
+}
+
+/// Gathers the static accounts of `pools`, in order and without duplicates.
+///
+/// Shared accounts, e.g. token programs and common mints, are only included once, which is most of the gain of
+/// one table for many pools.
+pub fn static_accounts<'a, P: Pool + ?Sized + 'a>(pools: impl IntoIterator<Item = &'a P>) -> Vec<Pubkey> {
+    let mut seen = HashSet::new();
+    pools
+        .into_iter()
+        .flat_map(|pool| pool.static_accounts())
+        .filter(|address| seen.insert(*address))
+        .collect()
+}
+
+/// Builds the instruction creating a new lookup table owned by `authority`, returning it with the table's address.
+///
+/// `recent_slot` seeds the address and must be a recent slot, e.g. from `getSlot` at finalized commitment.
+pub fn create_lookup_table_instruction(authority: Pubkey, payer: Pubkey, recent_slot: Slot) -> (Instruction, Pubkey) {
+    create_lookup_table(authority, payer, recent_slot)
+}
+
+/// Builds the instructions extending the lookup table at `lookup_table` with `addresses`, at most
+/// `MAX_ADDRESSES_PER_EXTEND` per instruction.
+///
+/// Note: Addresses are only usable in lookups from the slot after they're added, so don't compile messages
+/// against them in the same slot.
+pub fn extend_lookup_table_instructions(
+    lookup_table: Pubkey,
+    authority: Pubkey,
+    payer: Pubkey,
+    addresses: &[Pubkey],
+) -> Vec<Instruction> {
+    addresses
+        .chunks(MAX_ADDRESSES_PER_EXTEND)
+        .map(|chunk| extend_lookup_table(lookup_table, authority, Some(payer), chunk.to_vec()))
+        .collect()
+}
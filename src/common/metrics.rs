@@ -0,0 +1,190 @@
+//! Latency and throughput metrics for RPC calls and account updates, behind the `metrics` feature.
+//!
+//! Metrics are emitted through the `metrics` crate, so install any of its recorders (e.g.
+//! `metrics-exporter-prometheus`) to collect them. Until one is installed nothing is recorded.
+//!
+//! RPC calls are measured by wrapping a provider in `Instrumented`, account updates by `ManagedAccount`,
+//! `ZeroCopyAccount` and `LazyAccount` themselves. The metrics are:
+//!
+//! | Name | Type | Labels |
+//! |------|------|--------|
+//! | `rpc_call_duration_seconds` | histogram | `method` |
+//! | `rpc_calls_total` | counter | `method`, `outcome` (`ok`/`error`) |
+//! | `rpc_errors_total` | counter | `method`, `kind`, see `error_kind` |
+//! | `rpc_accounts_fetched_total` | counter | `method` |
+//! | `rpc_accounts_missing_total` | counter | `method` |
+//! | `rpc_bytes_received_total` | counter | `method` |
+//! | `account_updates_total` | counter | `kind` (the account type, e.g. `TickArray`), `outcome` (`changed`/`unchanged`/`failed`) |
+//! | `account_deserialize_duration_seconds` | histogram | `kind` |
+//!
+//! Account metrics are labelled by type rather than pubkey, to keep the number of series bounded however many
+//! pools are tracked.
+
+use crate::common::{
+    account::AccountData,
+    combinators::RpcTimeout,
+    rate_limit::SharedError,
+    rpc::{LatestBlockhash, RpcProvider, RpcResponse},
+    state::account_kind,
+    types::AnyResult,
+};
+use async_trait::async_trait;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_sdk::{clock::Slot, pubkey::Pubkey};
+use std::time::{Duration, Instant};
+
+pub const RPC_CALL_DURATION: &str = "rpc_call_duration_seconds";
+pub const RPC_CALLS: &str = "rpc_calls_total";
+pub const RPC_ERRORS: &str = "rpc_errors_total";
+pub const RPC_ACCOUNTS_FETCHED: &str = "rpc_accounts_fetched_total";
+pub const RPC_ACCOUNTS_MISSING: &str = "rpc_accounts_missing_total";
+pub const RPC_BYTES_RECEIVED: &str = "rpc_bytes_received_total";
+pub const ACCOUNT_UPDATES: &str = "account_updates_total";
+pub const ACCOUNT_DESERIALIZE_DURATION: &str = "account_deserialize_duration_seconds";
+
+/// Whether an account update changed the data, see `AccountState::update`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UpdateOutcome {
+    Changed,
+    Unchanged,
+    Failed,
+}
+
+/// Records an update of an account of type `T`, and how long deserializing it took if it was deserialized.
+pub(crate) fn record_account_update<T>(outcome: UpdateOutcome, deserialize_duration: Option<Duration>) {
+    let outcome = match outcome {
+        UpdateOutcome::Changed => "changed",
+        UpdateOutcome::Unchanged => "unchanged",
+        UpdateOutcome::Failed => "failed",
+    };
+    ::metrics::counter!(ACCOUNT_UPDATES, "kind" => account_kind::<T>(), "outcome" => outcome).increment(1);
+    if let Some(duration) = deserialize_duration {
+        record_deserialize_duration::<T>(duration);
+    }
+}
+
+/// Records how long deserializing an account of type `T` took, for accounts that deserialize outside of updates.
+pub(crate) fn record_deserialize_duration<T>(duration: Duration) {
+    ::metrics::histogram!(ACCOUNT_DESERIALIZE_DURATION, "kind" => account_kind::<T>()).record(duration.as_secs_f64());
+}
+
+/// Classifies an RPC error for the `kind` label of `rpc_errors_total`: `timeout`, `io`, `http`, `rpc`, `parse`,
+/// `not_found`, `unsupported` or `other`.
+pub fn error_kind(error: &anyhow::Error) -> &'static str {
+    let error = SharedError::original(error);
+    if error.is::<RpcTimeout>() {
+        return "timeout";
+    }
+    if let Some(client_error) = error.downcast_ref::<ClientError>() {
+        return match client_error.kind() {
+            ClientErrorKind::Io(_) => "io",
+            ClientErrorKind::Reqwest(_) | ClientErrorKind::Middleware(_) => "http",
+            ClientErrorKind::RpcError(_) => "rpc",
+            ClientErrorKind::SerdeJson(_) => "parse",
+            _ => "other",
+        };
+    }
+    let message = error.to_string();
+    if message.contains("AccountNotFound") {
+        "not_found"
+    } else if message.contains("not supported") {
+        "unsupported"
+    } else {
+        "other"
+    }
+}
+
+// --- Instrumented Provider --- //
+
+/// Records the metrics of every call of the inner provider, see the module docs.
+///
+/// Wrap it around the outermost provider to measure what callers wait for, or around a single endpoint (e.g.
+/// inside a `Failover`) to measure that endpoint.
+pub struct Instrumented<P: RpcProvider> {
+    inner: P,
+}
+
+impl<P: RpcProvider> Instrumented<P> {
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+}
+
+/// Records the latency and outcome of a call to `method` that started at `start`.
+fn record_call<T>(method: &'static str, start: Instant, result: &AnyResult<T>) {
+    ::metrics::histogram!(RPC_CALL_DURATION, "method" => method).record(start.elapsed().as_secs_f64());
+    match result {
+        Ok(_) => ::metrics::counter!(RPC_CALLS, "method" => method, "outcome" => "ok").increment(1),
+        Err(e) => {
+            ::metrics::counter!(RPC_CALLS, "method" => method, "outcome" => "error").increment(1);
+            ::metrics::counter!(RPC_ERRORS, "method" => method, "kind" => error_kind(e)).increment(1);
+        }
+    }
+}
+
+/// Records the accounts and bytes a successful call to `method` returned.
+fn record_accounts<'a, A: AccountData + 'a>(method: &'static str, accounts: impl Iterator<Item = Option<&'a A>>) {
+    let (mut fetched, mut missing, mut bytes) = (0, 0, 0);
+    for account in accounts {
+        match account {
+            Some(account) => {
+                fetched += 1;
+                bytes += account.bytes().len() as u64;
+            }
+            None => missing += 1,
+        }
+    }
+    ::metrics::counter!(RPC_ACCOUNTS_FETCHED, "method" => method).increment(fetched);
+    ::metrics::counter!(RPC_ACCOUNTS_MISSING, "method" => method).increment(missing);
+    ::metrics::counter!(RPC_BYTES_RECEIVED, "method" => method).increment(bytes);
+}
+
+#[async_trait]
+impl<P: RpcProvider> RpcProvider for Instrumented<P> {
+    type AccountType = P::AccountType;
+
+    async fn get_account(&self, pubkey: &Pubkey) -> AnyResult<RpcResponse<Self::AccountType>> {
+        let start = Instant::now();
+        let result = self.inner.get_account(pubkey).await;
+        record_call("get_account", start, &result);
+        if let Ok(response) = &result {
+            record_accounts("get_account", std::iter::once(Some(&response.result)));
+        }
+        result
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>> {
+        let start = Instant::now();
+        let result = self.inner.get_multiple_accounts(pubkeys).await;
+        record_call("get_multiple_accounts", start, &result);
+        if let Ok(response) = &result {
+            record_accounts("get_multiple_accounts", response.result.iter().map(Option::as_ref));
+        }
+        result
+    }
+
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        self.inner.max_accounts_per_rpc_call()
+    }
+
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        let start = Instant::now();
+        let result = self.inner.get_latest_blockhash().await;
+        record_call("get_latest_blockhash", start, &result);
+        result
+    }
+
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        let start = Instant::now();
+        let result = self.inner.get_slot().await;
+        record_call("get_slot", start, &result);
+        result
+    }
+}
@@ -5,8 +5,19 @@
 //! states for multi-threaded, read-only consumption of DEX data by external 
 //! consumers. 
 pub mod account;
+pub mod blockhash;
+pub mod combinators;
 pub mod deserialize;
+pub mod lookup_table;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod pool;
+pub mod rate_limit;
+pub mod refresh;
 pub mod rpc;
+pub mod scheduler;
+pub mod snapshot;
 pub mod state;
-pub mod types;
\ No newline at end of file
+pub mod transaction;
+pub mod types;
+pub mod zero_copy;
\ No newline at end of file
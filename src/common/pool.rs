@@ -2,13 +2,18 @@
 
 use crate::common::{
     account::AccountData,
+    refresh::{RefreshPolicy, RefreshReport},
     rpc::RpcProvider,
-    state::AccountState,
+    snapshot::PoolSnapshot,
+    state::{AccountLifecycle, AccountState, UpdateStamp},
     types::AnyResult,
 };
+use anyhow::anyhow;
 use async_trait::async_trait;
+use futures::future::select_all;
 use solana_sdk::pubkey::Pubkey;
-use std::{any::Any, sync::Arc};
+use std::{any::Any, sync::Arc, time::Duration};
+use tokio::sync::watch;
 
 #[async_trait]
 pub trait Pool: Send + Sync {
@@ -27,9 +32,58 @@ pub trait Pool: Send + Sync {
     /// all accounts in a pool without needing to know their concrete types.
     fn accounts(&self) -> Vec<Arc<dyn AccountState>>;
 
+    /// Returns the pubkeys of the accounts a transaction trading the pool is likely to reference, e.g. for an
+    /// address lookup table, see `common::lookup_table`.
+    /// 
+    /// Defaults to the pubkeys of `accounts()`. DEXs should override it to add the accounts they don't track, 
+    /// like vaults and programs, and drop the ones trades rarely touch.
+    fn static_accounts(&self) -> Vec<Pubkey> {
+        self.accounts().iter().map(|account| *account.pubkey()).collect()
+    }
+
+    /// Returns an immutable view of every account of the pool, as of the same refresh.
+    /// 
+    /// Defaults to snapshotting `accounts()` one by one, which is only consistent if no refresh is running. DEXs 
+    /// should override it to return a snapshot published atomically at the end of each refresh.
+    fn snapshot(&self) -> Arc<PoolSnapshot> {
+        Arc::new(PoolSnapshot {
+            pubkey: *self.pubkey(),
+            sequence: 0,
+            slot: None,
+            accounts: self.accounts().iter().map(|account| account.snapshot()).collect(),
+        })
+    }
+
+    /// Returns the lifecycle of the pool's accounts, see `PoolHealth`.
+    fn health(&self) -> PoolHealth {
+        PoolHealth::new(self.pubkey(), &self.accounts())
+    }
+
+    /// Fails unless every account of the pool is live and was last updated within `max_age`, see 
+    /// `AccountState::ensure_fresh`. Trading code should call it before quoting on the pool's data.
+    fn ensure_fresh(&self, max_age: Duration) -> AnyResult<()> {
+        self.accounts().iter().try_for_each(|account| account.ensure_fresh(max_age))
+    }
+
+    /// Returns true if any account of the pool isn't live or is older than `max_age`, see `ensure_fresh`.
+    fn is_stale(&self, max_age: Duration) -> bool {
+        self.ensure_fresh(max_age).is_err()
+    }
+
+    /// Subscribes to the updates of every account of the pool, see `PoolUpdates`.
+    fn updates(&self) -> PoolUpdates {
+        PoolUpdates::new(&self.accounts())
+    }
+
     /// Downcasts the trait object to its concrete type (e.g., `OrcaWhirlpool`).
     fn as_any(&self) -> &dyn Any;
 
+    /// Triggers a refresh of all accounts in the pool using the provided RPC client, under the default 
+    /// `RefreshPolicy`. See `refresh_with_policy`.
+    async fn refresh(&self, rpc_client: &dyn RpcProvider<AccountType = Self::AccountType>) -> AnyResult<RefreshReport> {
+        self.refresh_with_policy(rpc_client, RefreshPolicy::default()).await
+    }
+
     /// Triggers a refresh of all accounts in the pool using the provided RPC client.
     ///
     /// Any implementation of this method should be highly optimized, fetching 
@@ -39,5 +93,84 @@ pub trait Pool: Send + Sync {
     /// The RPC client is generic over the AccountType, allowing for this method to 
     /// be used with any RPC client that implements the RpcProvider trait over the 
     /// specified AccountType.
-    async fn refresh(&self, rpc_client: &dyn RpcProvider<AccountType = Self::AccountType>) -> AnyResult<()>;
+    /// 
+    /// Returns a `RefreshReport` of what happened to each account, see `RefreshReport::apply`. `policy` decides 
+    /// whether an account that fails to deserialize aborts the refresh or is skipped.
+    async fn refresh_with_policy(
+        &self,
+        rpc_client: &dyn RpcProvider<AccountType = Self::AccountType>,
+        policy: RefreshPolicy,
+    ) -> AnyResult<RefreshReport>;
+}
+
+/// The accounts of a pool that aren't live, see `Pool::health`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolHealth {
+    /// True if the pool's own account, e.g. the whirlpool, is closed, i.e. the pool no longer exists.
+    pub pool_closed: bool,
+    /// Accounts that no longer exist on-chain, e.g. a deleted tick array.
+    pub closed: Vec<Pubkey>,
+    /// Accounts whose last update failed to deserialize.
+    pub errored: Vec<Pubkey>,
+}
+
+impl PoolHealth {
+    /// Collects the lifecycles of `accounts`, those of the pool at `pool_pubkey`.
+    pub fn new(pool_pubkey: &Pubkey, accounts: &[Arc<dyn AccountState>]) -> Self {
+        let mut health = Self::default();
+        for account in accounts {
+            match account.lifecycle() {
+                AccountLifecycle::Live => {}
+                AccountLifecycle::Closed => {
+                    health.pool_closed |= account.pubkey() == pool_pubkey;
+                    health.closed.push(*account.pubkey());
+                }
+                AccountLifecycle::Errored => health.errored.push(*account.pubkey()),
+            }
+        }
+        health
+    }
+
+    /// Returns true if every account is live, i.e. the pool's data can be trusted.
+    pub fn is_healthy(&self) -> bool {
+        self.closed.is_empty() && self.errored.is_empty()
+    }
+}
+
+/// A subscription to the updates of a set of accounts, e.g. those of a pool, see `Pool::updates`.
+///
+/// Note: The set is fixed at creation, so accounts a pool starts tracking later, e.g. tick arrays entering a 
+/// windowed pool's range, aren't covered. Resubscribe after a refresh that changed them.
+pub struct PoolUpdates {
+    receivers: Vec<watch::Receiver<UpdateStamp>>,
+}
+
+impl PoolUpdates {
+    /// Subscribes to `accounts`, starting from their latest updates, so `next` waits for the next one.
+    pub fn new(accounts: &[Arc<dyn AccountState>]) -> Self {
+        Self {
+            receivers: accounts.iter().map(|account| account.watch()).collect(),
+        }
+    }
+
+    /// Waits for the next update of any of the accounts, returning its stamp.
+    ///
+    /// Each call returns at most one update per account, the latest, and those already pending return immediately.
+    /// Fails once every account has been dropped.
+    pub async fn next(&mut self) -> AnyResult<UpdateStamp> {
+        loop {
+            if self.receivers.is_empty() {
+                return Err(anyhow!("All subscribed accounts have been dropped"));
+            }
+            let (result, index, _) =
+                select_all(self.receivers.iter_mut().map(|receiver| Box::pin(receiver.changed()))).await;
+            match result {
+                Ok(()) => return Ok(*self.receivers[index].borrow_and_update()),
+                // The account was dropped, stop listening to it.
+                Err(_) => {
+                    self.receivers.swap_remove(index);
+                }
+            }
+        }
+    }
 }
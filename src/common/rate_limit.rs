@@ -0,0 +1,374 @@
+//! An `RpcProvider` wrapper that keeps within an RPC's rate limit.
+//!
+//! `RateLimited` spends credits from a token bucket before each upstream call, waiting for the bucket to refill
+//! rather than tripping the provider's 429s. Credits are charged per method, see `MethodCredits`, to match
+//! providers that bill methods differently.
+//!
+//! It also coalesces account fetches: concurrent `get_account` and `get_multiple_accounts` calls arriving within
+//! `RateLimitConfig::coalesce_window` of each other are merged into one `get_multiple_accounts` batch over their
+//! deduplicated pubkeys, whose result is fanned out to each caller. Many pools refreshing at once then cost a
+//! handful of calls, and shared accounts (e.g. token mints) are fetched once.
+
+use crate::common::{
+    rpc::{LatestBlockhash, RpcProvider, RpcResponse},
+    types::AnyResult,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use solana_sdk::{clock::Slot, pubkey::Pubkey};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// The credits each method costs, per upstream call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MethodCredits {
+    pub get_account: u32,
+    pub get_multiple_accounts: u32,
+    pub get_latest_blockhash: u32,
+    pub get_slot: u32,
+}
+
+impl Default for MethodCredits {
+    /// One credit per call, i.e. a plain requests per second limit.
+    fn default() -> Self {
+        Self {
+            get_account: 1,
+            get_multiple_accounts: 1,
+            get_latest_blockhash: 1,
+            get_slot: 1,
+        }
+    }
+}
+
+/// The settings of a `RateLimited` provider.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    /// The credits the bucket refills per second.
+    pub credits_per_second: f64,
+    /// The bucket's capacity, i.e. the largest burst of credits spent at once.
+    pub burst: f64,
+    pub credits: MethodCredits,
+    /// How long the first of a batch of account fetches waits for others to join it. `None` disables
+    /// coalescing, `Some(Duration::ZERO)` only coalesces calls made before the runtime next polls the first.
+    pub coalesce_window: Option<Duration>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            credits_per_second: 10.0,
+            burst: 10.0,
+            credits: MethodCredits::default(),
+            coalesce_window: Some(Duration::from_millis(2)),
+        }
+    }
+}
+
+// --- Token Bucket --- //
+
+/// A token bucket holding up to `capacity` credits, refilled continuously at `rate` credits per second.
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    rate: f64,
+    /// The credits available as of the instant.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// Constructs a full bucket.
+    ///
+    /// Fails unless `capacity` and `rate` are positive.
+    pub fn new(capacity: f64, rate: f64) -> AnyResult<Self> {
+        // Written to also reject NaNs.
+        if !(capacity > 0.0 && rate > 0.0) {
+            return Err(anyhow!("Token bucket capacity ({}) and rate ({}) must be positive", capacity, rate));
+        }
+        Ok(Self {
+            capacity,
+            rate,
+            state: Mutex::new((capacity, Instant::now())),
+        })
+    }
+
+    /// Takes `credits` from the bucket if it holds enough, otherwise returns how long until it will.
+    ///
+    /// Costs above the capacity are capped at it, so they wait for a full bucket rather than forever.
+    pub fn try_acquire(&self, credits: f64) -> Result<(), Duration> {
+        let credits = credits.min(self.capacity);
+        let mut state = self.state();
+        let now = Instant::now();
+        let (available, last_refill) = *state;
+        let available = (available + now.duration_since(last_refill).as_secs_f64() * self.rate).min(self.capacity);
+        if available >= credits {
+            *state = (available - credits, now);
+            Ok(())
+        } else {
+            *state = (available, now);
+            Err(Duration::from_secs_f64((credits - available) / self.rate))
+        }
+    }
+
+    /// Takes `credits` from the bucket, waiting for it to refill if needed.
+    pub async fn acquire(&self, credits: f64) {
+        while let Err(wait) = self.try_acquire(credits) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, (f64, Instant)> {
+        // The lock is never held across a panic.
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// --- Coalescing --- //
+
+/// An error shared by the callers of a coalesced batch, as `anyhow::Error` isn't `Clone`.
+///
+/// Displays as, and has the sources of, the original error. Classify it through `SharedError::original`, as
+/// `is_retriable` does, since downcasting the wrapper doesn't reach the original.
+#[derive(Debug, Clone)]
+pub struct SharedError(Arc<anyhow::Error>);
+
+impl SharedError {
+    /// Returns the original error if `error` is a `SharedError`, `error` itself otherwise.
+    pub fn original(error: &anyhow::Error) -> &anyhow::Error {
+        match error.downcast_ref::<SharedError>() {
+            Some(shared) => &shared.0,
+            None => error,
+        }
+    }
+}
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// The result of a batch, shared by its callers.
+type BatchResult<A> = Option<Result<Arc<RpcResponse<Vec<Option<A>>>>, Arc<anyhow::Error>>>;
+
+/// The batch of account fetches currently collecting pubkeys.
+struct PendingBatch<A> {
+    pubkeys: Vec<Pubkey>,
+    /// Each pubkey's position in `pubkeys`.
+    positions: HashMap<Pubkey, usize>,
+    result: watch::Sender<BatchResult<A>>,
+}
+
+/// The state a batch's task shares with the wrapper.
+struct Shared<P: RpcProvider> {
+    inner: P,
+    config: RateLimitConfig,
+    bucket: TokenBucket,
+    pending: Mutex<Option<PendingBatch<P::AccountType>>>,
+}
+
+impl<P: RpcProvider> Shared<P> {
+    fn pending(&self) -> MutexGuard<'_, Option<PendingBatch<P::AccountType>>> {
+        // The lock is never held across a panic.
+        self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// --- The Wrapper --- //
+
+/// Rate limits, and coalesces the account fetches of, the inner provider, see the module docs.
+///
+/// Coalesced batches run in a task of their own, so a caller dropping its request doesn't fail the others that
+/// joined the batch. Coalescing therefore needs a tokio runtime.
+///
+/// Note: Coalesced `get_account` calls are served by `get_multiple_accounts`, and so fail with `AccountNotFound`
+/// for a missing account, like the `RpcClient` implementation.
+pub struct RateLimited<P: RpcProvider + 'static>
+where
+    P::AccountType: Clone + 'static,
+{
+    shared: Arc<Shared<P>>,
+}
+
+impl<P: RpcProvider + 'static> RateLimited<P>
+where
+    P::AccountType: Clone + 'static,
+{
+    /// Fails unless `credits_per_second` and `burst` are positive.
+    pub fn new(inner: P, config: RateLimitConfig) -> AnyResult<Self> {
+        Ok(Self {
+            shared: Arc::new(Shared {
+                bucket: TokenBucket::new(config.burst, config.credits_per_second)?,
+                inner,
+                config,
+                pending: Mutex::new(None),
+            }),
+        })
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.shared.inner
+    }
+
+    pub fn bucket(&self) -> &TokenBucket {
+        &self.shared.bucket
+    }
+
+    /// Fetches `pubkeys` as part of a coalesced batch, see the module docs.
+    async fn fetch_coalesced(
+        &self,
+        pubkeys: &[Pubkey],
+        window: Duration,
+    ) -> AnyResult<RpcResponse<Vec<Option<P::AccountType>>>> {
+        // Join the pending batch, or start one.
+        let (mut receiver, positions) = {
+            let mut pending = self.shared.pending();
+            let batch = pending.get_or_insert_with(|| {
+                tokio::spawn(run_batch(self.shared.clone(), window));
+                PendingBatch {
+                    pubkeys: Vec::new(),
+                    positions: HashMap::new(),
+                    result: watch::channel(None).0,
+                }
+            });
+            let positions: Vec<usize> = pubkeys
+                .iter()
+                .map(|pubkey| {
+                    *batch.positions.entry(*pubkey).or_insert_with(|| {
+                        batch.pubkeys.push(*pubkey);
+                        batch.pubkeys.len() - 1
+                    })
+                })
+                .collect();
+            (batch.result.subscribe(), positions)
+        };
+
+        let result = receiver
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| anyhow!("The coalesced batch was dropped before completing"))?
+            .clone()
+            .expect("Waited for a result");
+        let response = result.map_err(|e| anyhow::Error::new(SharedError(e)))?;
+        Ok(RpcResponse {
+            // `run_batch` checked there's an account per pubkey.
+            result: positions.iter().map(|&i| response.result[i].clone()).collect(),
+            response_time: response.response_time,
+            slot: response.slot,
+        })
+    }
+}
+
+/// Waits for the pending batch to fill, then fetches it in chunks of `max_accounts_per_rpc_call` and publishes the
+/// result.
+///
+/// If the task is dropped, e.g. as the runtime shuts down, the result sender is dropped with it, so the callers
+/// fail rather than wait forever.
+async fn run_batch<P: RpcProvider>(shared: Arc<Shared<P>>, window: Duration) {
+    if window.is_zero() {
+        tokio::task::yield_now().await;
+    } else {
+        tokio::time::sleep(window).await;
+    }
+    let Some(batch) = shared.pending().take() else {
+        return;
+    };
+
+    let mut accounts = Vec::with_capacity(batch.pubkeys.len());
+    let mut response_time = 0;
+    let mut slot = None;
+    let mut result = Ok(());
+    for chunk in batch.pubkeys.chunks(shared.inner.max_accounts_per_rpc_call().max(1)) {
+        shared.bucket.acquire(shared.config.credits.get_multiple_accounts as f64).await;
+        match shared.inner.get_multiple_accounts(chunk).await {
+            Ok(response) if response.result.len() != chunk.len() => {
+                result = Err(anyhow!(
+                    "get_multiple_accounts returned {} accounts for {} pubkeys",
+                    response.result.len(),
+                    chunk.len()
+                ));
+                break;
+            }
+            Ok(response) => {
+                accounts.extend(response.result);
+                response_time = response_time.max(response.response_time);
+                slot = slot.max(response.slot);
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    let result = match result {
+        Ok(()) => Ok(Arc::new(RpcResponse {
+            result: accounts,
+            response_time,
+            slot,
+        })),
+        Err(e) => Err(Arc::new(e)),
+    };
+    batch.result.send_replace(Some(result));
+}
+
+#[async_trait]
+impl<P: RpcProvider + 'static> RpcProvider for RateLimited<P>
+where
+    P::AccountType: Clone + 'static,
+{
+    type AccountType = P::AccountType;
+
+    async fn get_account(&self, pubkey: &Pubkey) -> AnyResult<RpcResponse<Self::AccountType>> {
+        match self.shared.config.coalesce_window {
+            Some(window) => {
+                let response = self.fetch_coalesced(std::slice::from_ref(pubkey), window).await?;
+                let account = response.result.into_iter().next().flatten();
+                Ok(RpcResponse {
+                    result: account.ok_or_else(|| anyhow!("AccountNotFound: pubkey={}", pubkey))?,
+                    response_time: response.response_time,
+                    slot: response.slot,
+                })
+            }
+            None => {
+                self.shared.bucket.acquire(self.shared.config.credits.get_account as f64).await;
+                self.shared.inner.get_account(pubkey).await
+            }
+        }
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>> {
+        match self.shared.config.coalesce_window {
+            Some(window) => self.fetch_coalesced(pubkeys, window).await,
+            None => {
+                self.shared.bucket.acquire(self.shared.config.credits.get_multiple_accounts as f64).await;
+                self.shared.inner.get_multiple_accounts(pubkeys).await
+            }
+        }
+    }
+
+    /// The inner provider's limit, though coalesced batches over it are split into several calls.
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        self.shared.inner.max_accounts_per_rpc_call()
+    }
+
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        self.shared.bucket.acquire(self.shared.config.credits.get_latest_blockhash as f64).await;
+        self.shared.inner.get_latest_blockhash().await
+    }
+
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        self.shared.bucket.acquire(self.shared.config.credits.get_slot as f64).await;
+        self.shared.inner.get_slot().await
+    }
+}
@@ -0,0 +1,107 @@
+//! Defines what a pool refresh reports, and how it treats accounts that fail to update.
+
+use crate::common::{state::AccountState, types::AnyResult};
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+use std::time::Duration;
+
+/// What a refresh does when an account's new data fails to deserialize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RefreshPolicy {
+    /// Stop at the first failure and return its error.
+    ///
+    /// Accounts updated before the failure keep their new data, but the pool's snapshot isn't published, so
+    /// `Pool::snapshot` still returns the previous, consistent state until the next refresh completes.
+    #[default]
+    Abort,
+    /// Keep the failed account's previous data, record the failure in the report and carry on.
+    Skip,
+}
+
+/// An account that failed to update during a refresh.
+#[derive(Debug, Clone)]
+pub struct FailedUpdate {
+    pub pubkey: Pubkey,
+    pub reason: String,
+}
+
+/// The outcome of a refresh, account by account.
+#[derive(Debug, Clone, Default)]
+pub struct RefreshReport {
+    /// Accounts whose data changed, including any the pool started or stopped tracking.
+    pub changed: Vec<Pubkey>,
+    /// Accounts whose data was fetched but identical.
+    pub unchanged: Vec<Pubkey>,
+    /// Tracked accounts the RPC returned no data for, which are marked closed (see `AccountState::mark_closed`). 
+    /// They keep their previous data, unless the pool drops them, e.g. tick arrays in `TickArrayMode::Windowed`, 
+    /// which are then also recorded as changed, as the pool's state no longer holds them.
+    pub missing: Vec<Pubkey>,
+    /// Accounts whose data failed to deserialize, only populated under `RefreshPolicy::Skip`.
+    pub failed: Vec<FailedUpdate>,
+    /// The total time spent waiting on RPC calls.
+    pub rpc_latency: Duration,
+    /// The number of RPC calls made.
+    pub rpc_calls: usize,
+    /// The latest slot the RPC responses were served at, if the provider reports slots.
+    pub slot: Option<u64>,
+}
+
+impl RefreshReport {
+    /// Returns true if any account changed, i.e. the refresh did something.
+    pub fn has_changes(&self) -> bool {
+        !self.changed.is_empty()
+    }
+
+    /// Returns true if every tracked account was fetched and updated.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty() && self.failed.is_empty()
+    }
+
+    /// Records an RPC call that took `latency` and was served at `slot`.
+    pub fn record_rpc_call(&mut self, latency: Duration, slot: Option<u64>) {
+        self.rpc_latency += latency;
+        self.rpc_calls += 1;
+        self.slot = self.slot.max(slot);
+    }
+
+    /// Applies freshly fetched data to `account`, recording the outcome.
+    ///
+    /// `None` data marks the account closed and is recorded as missing. A failed update returns its error under `RefreshPolicy::Abort`, and is
+    /// recorded as failed under `RefreshPolicy::Skip`.
+    pub fn apply(
+        &mut self,
+        policy: RefreshPolicy,
+        account: &dyn AccountState,
+        bytes: Option<Vec<u8>>,
+        update_time: u64,
+    ) -> AnyResult<()> {
+        let pubkey = *account.pubkey();
+        match bytes {
+            Some(bytes) => {
+                let result = account.update(bytes, update_time);
+                self.record(policy, pubkey, result)
+            }
+            None => {
+                account.mark_closed();
+                self.missing.push(pubkey);
+                Ok(())
+            }
+        }
+    }
+
+    /// Records the result of updating (or creating) the account at `pubkey`, see `apply`.
+    pub fn record(&mut self, policy: RefreshPolicy, pubkey: Pubkey, result: AnyResult<bool>) -> AnyResult<()> {
+        match result {
+            Ok(true) => self.changed.push(pubkey),
+            Ok(false) => self.unchanged.push(pubkey),
+            Err(e) => match policy {
+                RefreshPolicy::Abort => return Err(anyhow!("Failed to update account {}: {}", pubkey, e)),
+                RefreshPolicy::Skip => self.failed.push(FailedUpdate {
+                    pubkey,
+                    reason: e.to_string(),
+                }),
+            },
+        }
+        Ok(())
+    }
+}
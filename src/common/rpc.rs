@@ -4,12 +4,17 @@ use crate::common::{
     account::AccountData,
     types::AnyResult,
 };
+use anyhow::anyhow;
 use async_trait::async_trait;
 use solana_sdk::{
     account::Account, 
+    clock::Slot,
+    hash::Hash,
     pubkey::Pubkey
 };
 use solana_client::nonblocking::rpc_client::RpcClient;
+use std::future::Future;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 
@@ -20,6 +25,8 @@ use std::time::{SystemTime, UNIX_EPOCH};
 pub struct RpcResponse<T> {
     pub result: T,
     pub response_time: u64, // Unix timestamp in nanoseconds
+    /// The slot the node answered at, if the provider reports it (the RPC response `context`).
+    pub slot: Option<u64>,
 }
 
 /// An abstract interface for a client that can provide Solana account data.
@@ -45,51 +52,77 @@ pub trait RpcProvider: Send + Sync {
     ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>>;
 
     fn max_accounts_per_rpc_call(&self) -> usize;
+
+    /// Fetches the latest blockhash, for signing transactions.
+    ///
+    /// Optional, providers that only serve account data can leave the default, which errors.
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        Err(anyhow!("get_latest_blockhash is not supported by this RPC provider"))
+    }
+
+    /// Fetches the current slot.
+    ///
+    /// Optional, providers that only serve account data can leave the default, which errors.
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        Err(anyhow!("get_slot is not supported by this RPC provider"))
+    }
+}
+
+/// A blockhash along with the last block height at which transactions using it are valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatestBlockhash {
+    pub blockhash: Hash,
+    pub last_valid_block_height: u64,
 }
 
 #[async_trait]
 impl RpcProvider for RpcClient {
     type AccountType = Account;
 
-    /// Just invokes the underlying `RpcClient::get_account` method, but 
-    /// also handles the response time tracking.
+    /// Invokes the underlying `RpcClient::get_account_with_commitment` method at the client's commitment, 
+    /// and also handles the response time and slot tracking. Errors if the account doesn't exist.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "debug",
+        skip_all,
+        err,
+        fields(pubkey = %pubkey, slot = tracing::field::Empty),
+    ))]
     async fn get_account(
         &self,
         pubkey: &Pubkey,
     ) -> AnyResult<RpcResponse<Self::AccountType>> {
-        let start_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
-        let result = self.get_account(pubkey).await?;
-        let end_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
-        // The number of nanoseconds in a u64 is safe for the next ~500 years.
-        // We take the average of the start and end times to get the response time.
-        // Idea is pings each direction roughly equal, and server time negligible, 
-        // so this is a good approximation of the actual response time.
-        let response_time = (start_time + (end_time - start_time) / 2) as u64;
-
+        let response = timed(self.get_account_with_commitment(pubkey, self.commitment())).await?;
+        let context = response.result;
+        trace_record!("slot" = context.context.slot);
         Ok(RpcResponse {
-            result,
-            response_time,
+            result: context.value.ok_or_else(|| anyhow!("AccountNotFound: pubkey={}", pubkey))?,
+            response_time: response.response_time,
+            slot: Some(context.context.slot),
         })
     }
 
-    /// Just invokes the underlying `RpcClient::get_multiple_accounts` method, but 
-    /// also handles the response time tracking.
+    /// Invokes the underlying `RpcClient::get_multiple_accounts_with_commitment` method at the client's 
+    /// commitment, and also handles the response time and slot tracking.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "debug",
+        skip_all,
+        err,
+        fields(chunk_size = pubkeys.len(), found = tracing::field::Empty, slot = tracing::field::Empty),
+    ))]
     async fn get_multiple_accounts(
         &self,
         pubkeys: &[Pubkey],
     ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>> {
-        let start_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
-        let result = self.get_multiple_accounts(pubkeys).await?;
-        let end_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
-        // The number of nanoseconds in a u64 is safe for the next ~500 years.
-        // We take the average of the start and end times to get the response time.
-        // Idea is pings each direction roughly equal, and server time negligible, 
-        // so this is a good approximation of the actual response time.
-        let response_time = (start_time + (end_time - start_time) / 2) as u64;
-
+        let response = timed(self.get_multiple_accounts_with_commitment(pubkeys, self.commitment())).await?;
+        let context = response.result;
+        trace_record!(
+            "found" = context.value.iter().filter(|account| account.is_some()).count(),
+            "slot" = context.context.slot,
+        );
         Ok(RpcResponse {
-            result,
-            response_time,
+            result: context.value,
+            response_time: response.response_time,
+            slot: Some(context.context.slot),
         })
     }
 
@@ -99,5 +132,86 @@ impl RpcProvider for RpcClient {
     fn max_accounts_per_rpc_call(&self) -> usize {
         100
     }
+
+    /// Invokes `RpcClient::get_latest_blockhash_with_commitment` at the client's commitment.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, err))]
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        let response = timed(self.get_latest_blockhash_with_commitment(self.commitment())).await?;
+        let (blockhash, last_valid_block_height) = response.result;
+        Ok(RpcResponse {
+            result: LatestBlockhash { blockhash, last_valid_block_height },
+            response_time: response.response_time,
+            slot: None,
+        })
+    }
+
+    /// Just invokes the underlying `RpcClient::get_slot` method.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "debug",
+        skip_all,
+        err,
+        fields(slot = tracing::field::Empty),
+    ))]
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        let response = timed(RpcClient::get_slot(self)).await?;
+        trace_record!("slot" = response.result);
+        Ok(RpcResponse {
+            slot: Some(response.result),
+            ..response
+        })
+    }
+}
+
+/// Forwards to the shared provider, so an `Arc<dyn RpcProvider>` can be used wherever a provider is expected, 
+/// e.g. to mix provider types in a `Failover`.
+#[async_trait]
+impl<P: RpcProvider + ?Sized> RpcProvider for Arc<P> {
+    type AccountType = P::AccountType;
+
+    async fn get_account(
+        &self,
+        pubkey: &Pubkey,
+    ) -> AnyResult<RpcResponse<Self::AccountType>> {
+        (**self).get_account(pubkey).await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> AnyResult<RpcResponse<Vec<Option<Self::AccountType>>>> {
+        (**self).get_multiple_accounts(pubkeys).await
+    }
+
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        (**self).max_accounts_per_rpc_call()
+    }
+
+    async fn get_latest_blockhash(&self) -> AnyResult<RpcResponse<LatestBlockhash>> {
+        (**self).get_latest_blockhash().await
+    }
+
+    async fn get_slot(&self) -> AnyResult<RpcResponse<Slot>> {
+        (**self).get_slot().await
+    }
 }
 
+/// Awaits an RPC request, wrapping its result in an `RpcResponse` with the response time (but no slot).
+async fn timed<T, E>(request: impl Future<Output = Result<T, E>>) -> AnyResult<RpcResponse<T>>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let start_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let result = request.await?;
+    let end_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    // The number of nanoseconds in a u64 is safe for the next ~500 years.
+    // We take the average of the start and end times to get the response time.
+    // Idea is pings each direction roughly equal, and server time negligible, 
+    // so this is a good approximation of the actual response time.
+    let response_time = (start_time + (end_time - start_time) / 2) as u64;
+
+    Ok(RpcResponse {
+        result,
+        response_time,
+        slot: None,
+    })
+}
@@ -0,0 +1,300 @@
+//! A background scheduler that keeps pools refreshed.
+//!
+//! Rather than looping over `Pool::refresh` by hand, register each pool with a `RefreshScheduler` along with how
+//! often to refresh it. Every pool gets its own tokio task, which sleeps for its interval (plus some jitter, so
+//! pools registered together don't hit the RPC in lockstep), then refreshes the pool once a concurrency permit is
+//! free. Failed refreshes back off exponentially until one succeeds.
+//!
+//! Concurrency is capped across all pools by `SchedulerConfig::max_concurrent_refreshes`, of which
+//! `SchedulerConfig::hot_reserved` permits are reserved to `Priority::Hot` pools, so a backlog of normal pools
+//! can't delay the ones being traded.
+
+use crate::common::{
+    account::AccountData,
+    pool::Pool,
+    refresh::{RefreshPolicy, RefreshReport},
+    rpc::RpcProvider,
+    types::AnyResult,
+};
+use anyhow::anyhow;
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinHandle;
+
+/// How urgently a pool needs its refreshes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    #[default]
+    Normal,
+    /// May use the permits reserved by `SchedulerConfig::hot_reserved`.
+    Hot,
+}
+
+/// How often, and how urgently, to refresh a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolSchedule {
+    pub interval: Duration,
+    pub priority: Priority,
+}
+
+impl PoolSchedule {
+    /// A `Priority::Normal` schedule.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            interval,
+            priority: Priority::Normal,
+        }
+    }
+
+    /// A `Priority::Hot` schedule.
+    pub fn hot(interval: Duration) -> Self {
+        Self {
+            interval,
+            priority: Priority::Hot,
+        }
+    }
+}
+
+/// The settings shared by every pool of a `RefreshScheduler`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SchedulerConfig {
+    /// The maximum number of refreshes in flight at once, across all pools.
+    pub max_concurrent_refreshes: usize,
+    /// How many of `max_concurrent_refreshes` only `Priority::Hot` pools may use.
+    pub hot_reserved: usize,
+    /// The fraction of a pool's interval by which each wait is randomly lengthened or shortened, e.g. 0.1 for ±10%.
+    pub jitter: f64,
+    /// The cap on the exponential backoff after failed refreshes.
+    pub max_backoff: Duration,
+    /// How refreshes treat accounts that fail to update.
+    pub policy: RefreshPolicy,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_refreshes: 8,
+            hot_reserved: 2,
+            jitter: 0.1,
+            max_backoff: Duration::from_secs(30),
+            policy: RefreshPolicy::default(),
+        }
+    }
+}
+
+/// The refresh history of a scheduled pool. Timestamps are Unix nanoseconds.
+#[derive(Debug, Clone, Default)]
+pub struct PoolRefreshStatus {
+    /// When the last successful refresh completed.
+    pub last_success: Option<u64>,
+    /// When the last refresh, successful or not, completed.
+    pub last_attempt: Option<u64>,
+    /// The number of refreshes that have failed since the last success.
+    pub consecutive_failures: u32,
+    /// The error of the last failed refresh, cleared by the next successful one.
+    pub last_error: Option<String>,
+    /// The report of the last successful refresh.
+    pub last_report: Option<RefreshReport>,
+}
+
+/// A registered pool's task and status.
+struct ScheduledPool {
+    task: JoinHandle<()>,
+    status: Arc<ArcSwap<PoolRefreshStatus>>,
+}
+
+/// The concurrency permits, see the module docs.
+struct Permits {
+    /// Every refresh takes one of these, `max_concurrent_refreshes` in total.
+    all: Semaphore,
+    /// Normal refreshes also take one of these, `max_concurrent_refreshes - hot_reserved` in total.
+    normal: Semaphore,
+}
+
+/// Refreshes registered pools in the background, see the module docs.
+///
+/// Pools are refreshed until they're unregistered or the scheduler is shut down or dropped. Registering needs a
+/// tokio runtime.
+pub struct RefreshScheduler<A: AccountData + Send + Sync + 'static> {
+    rpc_provider: Arc<dyn RpcProvider<AccountType = A>>,
+    config: SchedulerConfig,
+    permits: Arc<Permits>,
+    /// Flipped to true to stop every task at its next await point.
+    shutdown: watch::Sender<bool>,
+    pools: DashMap<Pubkey, ScheduledPool>,
+}
+
+impl<A: AccountData + Send + Sync + 'static> RefreshScheduler<A> {
+    /// Constructs a scheduler refreshing pools through `rpc_provider`.
+    ///
+    /// Fails if `max_concurrent_refreshes` is zero or doesn't exceed `hot_reserved`, as normal pools would never
+    /// refresh, or if `jitter` isn't between 0 and 1.
+    pub fn new(rpc_provider: Arc<dyn RpcProvider<AccountType = A>>, config: SchedulerConfig) -> AnyResult<Self> {
+        // Also rejects NaN, which `Duration::mul_f64` would panic on.
+        if !(0.0..=1.0).contains(&config.jitter) {
+            return Err(anyhow!("jitter ({}) must be between 0 and 1", config.jitter));
+        }
+        if config.max_concurrent_refreshes <= config.hot_reserved {
+            return Err(anyhow!(
+                "max_concurrent_refreshes ({}) must exceed hot_reserved ({})",
+                config.max_concurrent_refreshes,
+                config.hot_reserved
+            ));
+        }
+        let (shutdown, _) = watch::channel(false);
+        Ok(Self {
+            rpc_provider,
+            config,
+            permits: Arc::new(Permits {
+                all: Semaphore::new(config.max_concurrent_refreshes),
+                normal: Semaphore::new(config.max_concurrent_refreshes - config.hot_reserved),
+            }),
+            shutdown,
+            pools: DashMap::new(),
+        })
+    }
+
+    /// Starts refreshing `pool` on `schedule`, replacing its previous schedule if it's already registered.
+    ///
+    /// The first refresh happens after one interval, as pools are constructed fully loaded.
+    pub fn register(&self, pool: Arc<dyn Pool<AccountType = A>>, schedule: PoolSchedule) {
+        let pubkey = *pool.pubkey();
+        let status = Arc::new(ArcSwap::from_pointee(PoolRefreshStatus::default()));
+        let task = tokio::spawn(run_pool(
+            pool,
+            schedule,
+            self.rpc_provider.clone(),
+            self.config,
+            self.permits.clone(),
+            status.clone(),
+            self.shutdown.subscribe(),
+        ));
+        let previous = self.pools.insert(pubkey, ScheduledPool { task, status });
+        if let Some(previous) = previous {
+            previous.task.abort();
+        }
+    }
+
+    /// Stops refreshing the pool at `pubkey`, returning whether it was registered.
+    pub fn unregister(&self, pubkey: &Pubkey) -> bool {
+        match self.pools.remove(pubkey) {
+            Some((_, scheduled)) => {
+                scheduled.task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the pubkeys of the registered pools.
+    pub fn registered(&self) -> Vec<Pubkey> {
+        self.pools.iter().map(|entry| *entry.key()).collect()
+    }
+
+    /// Returns the refresh history of the pool at `pubkey`, if it's registered.
+    pub fn status(&self, pubkey: &Pubkey) -> Option<Arc<PoolRefreshStatus>> {
+        self.pools.get(pubkey).map(|scheduled| scheduled.status.load_full())
+    }
+
+    /// Returns when the pool at `pubkey` was last refreshed successfully, in Unix nanoseconds, if it's registered
+    /// and has been.
+    pub fn last_success(&self, pubkey: &Pubkey) -> Option<u64> {
+        self.status(pubkey).and_then(|status| status.last_success)
+    }
+
+    /// Stops every task, waiting for them to finish.
+    ///
+    /// Refreshes in flight are cancelled. That's safe: a pool only publishes its snapshot at the end of a
+    /// refresh, so readers keep seeing the previous, consistent state.
+    pub async fn shutdown(self) {
+        self.shutdown.send_replace(true);
+        let pubkeys = self.registered();
+        let tasks = pubkeys.iter().filter_map(|pubkey| self.pools.remove(pubkey));
+        for (_, ScheduledPool { task, .. }) in tasks {
+            // The tasks never panic, and aborted ones are the only other failure.
+            let _ = task.await;
+        }
+    }
+}
+
+impl<A: AccountData + Send + Sync + 'static> Drop for RefreshScheduler<A> {
+    fn drop(&mut self) {
+        for entry in self.pools.iter() {
+            entry.value().task.abort();
+        }
+    }
+}
+
+/// The task of a single pool: wait, refresh, record, repeat, until shut down.
+async fn run_pool<A: AccountData + Send + Sync + 'static>(
+    pool: Arc<dyn Pool<AccountType = A>>,
+    schedule: PoolSchedule,
+    rpc_provider: Arc<dyn RpcProvider<AccountType = A>>,
+    config: SchedulerConfig,
+    permits: Arc<Permits>,
+    status: Arc<ArcSwap<PoolRefreshStatus>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut consecutive_failures = 0u32;
+    loop {
+        let wait = next_wait(schedule.interval, consecutive_failures, &config);
+        let refresh = async {
+            tokio::time::sleep(wait).await;
+            // The semaphores are never closed, so acquiring can't fail.
+            let _normal_permit = match schedule.priority {
+                Priority::Normal => Some(permits.normal.acquire().await.ok()?),
+                Priority::Hot => None,
+            };
+            let _permit = permits.all.acquire().await.ok()?;
+            Some(pool.refresh_with_policy(rpc_provider.as_ref(), config.policy).await)
+        };
+        let result = tokio::select! {
+            result = refresh => result,
+            _ = shutdown.wait_for(|stopped| *stopped) => return,
+        };
+        let Some(result) = result else {
+            return;
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        let previous = status.load_full();
+        let next = match result {
+            Ok(report) => {
+                consecutive_failures = 0;
+                PoolRefreshStatus {
+                    last_success: Some(now),
+                    last_attempt: Some(now),
+                    consecutive_failures,
+                    last_error: None,
+                    last_report: Some(report),
+                }
+            }
+            Err(e) => {
+                consecutive_failures = consecutive_failures.saturating_add(1);
+                PoolRefreshStatus {
+                    last_attempt: Some(now),
+                    consecutive_failures,
+                    last_error: Some(e.to_string()),
+                    ..(*previous).clone()
+                }
+            }
+        };
+        status.store(Arc::new(next));
+    }
+}
+
+/// Returns how long to wait before the next refresh: the interval, doubled for each consecutive failure up to
+/// `max_backoff`, then jittered.
+fn next_wait(interval: Duration, consecutive_failures: u32, config: &SchedulerConfig) -> Duration {
+    let base = if consecutive_failures == 0 {
+        interval
+    } else {
+        let factor = 2u32.saturating_pow(consecutive_failures.min(16));
+        interval.saturating_mul(factor).min(config.max_backoff.max(interval))
+    };
+    base.mul_f64(1.0 + config.jitter * (2.0 * fastrand::f64() - 1.0))
+}
@@ -0,0 +1,77 @@
+//! Immutable, point-in-time views of accounts and pools.
+//!
+//! Each `ManagedAccount` is swapped independently, so reading several accounts of a pool one by one mid-refresh
+//! can mix states from different refreshes, e.g. a new `Whirlpool` with old `TickArray`s. A `PoolSnapshot` holds
+//! every account of a pool as of the same refresh, and pools publish it with a single pointer swap once the
+//! refresh is complete, see `Pool::snapshot`.
+
+use solana_sdk::pubkey::Pubkey;
+use std::any::Any;
+use std::sync::Arc;
+
+/// The state of a single account at a point in time.
+#[derive(Clone)]
+pub struct AccountSnapshot {
+    pub pubkey: Pubkey,
+    /// The raw byte data.
+    pub bytes: Arc<Vec<u8>>,
+    /// The deserialized data, type-erased, see `downcast`.
+    pub data: Arc<dyn Any + Send + Sync>,
+    /// The account's `update_slot` counter when the snapshot was taken.
+    pub update_slot: u64,
+    /// The Unix nanoseconds timestamp of the account's last update when the snapshot was taken.
+    pub update_time: u64,
+}
+
+impl AccountSnapshot {
+    /// Returns the deserialized data as a `T`, `None` if it's another type.
+    pub fn downcast<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.data.clone().downcast::<T>().ok()
+    }
+}
+
+impl std::fmt::Debug for AccountSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountSnapshot")
+            .field("pubkey", &self.pubkey)
+            .field("bytes_len", &self.bytes.len())
+            .field("update_slot", &self.update_slot)
+            .field("update_time", &self.update_time)
+            .finish()
+    }
+}
+
+/// The state of every account of a pool, as of the same refresh.
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot {
+    /// The pubkey of the pool.
+    pub pubkey: Pubkey,
+    /// The number of refreshes the pool had completed when the snapshot was published, 0 for the initial state.
+    pub sequence: u64,
+    /// The latest slot the data was fetched at, if the RPC provider reports slots.
+    pub slot: Option<u64>,
+    /// The pool's accounts, in the order of `Pool::accounts`.
+    pub accounts: Vec<AccountSnapshot>,
+}
+
+impl PoolSnapshot {
+    /// Returns the snapshot of the account at `pubkey`, if it's part of the pool.
+    pub fn account(&self, pubkey: &Pubkey) -> Option<&AccountSnapshot> {
+        self.accounts.iter().find(|account| &account.pubkey == pubkey)
+    }
+
+    /// Returns the deserialized data of the account at `pubkey`, `None` if it's not part of the pool or not a `T`.
+    pub fn get<T: Any + Send + Sync>(&self, pubkey: &Pubkey) -> Option<Arc<T>> {
+        self.account(pubkey).and_then(AccountSnapshot::downcast)
+    }
+
+    /// Returns the deserialized data of every account of type `T`, in order.
+    pub fn all<T: Any + Send + Sync>(&self) -> Vec<Arc<T>> {
+        self.accounts.iter().filter_map(AccountSnapshot::downcast).collect()
+    }
+
+    /// Returns the latest update time across the accounts, i.e. when the newest data was received.
+    pub fn update_time(&self) -> u64 {
+        self.accounts.iter().map(|account| account.update_time).max().unwrap_or_default()
+    }
+}
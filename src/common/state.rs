@@ -4,13 +4,19 @@ use crate::common::{
     account::AccountData,
     deserialize::Deserializable,
     rpc::RpcProvider,
+    snapshot::AccountSnapshot,
     types::AnyResult,
 };
+#[cfg(feature = "metrics")]
+use crate::common::metrics::{self, UpdateOutcome};
+use anyhow::anyhow;
 use arc_swap::{ArcSwap, Guard};
 use solana_sdk::pubkey::Pubkey;
 use std::any::Any;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
 
 // --- The Account Trait --- //
 
@@ -19,11 +25,15 @@ use std::sync::Arc;
 /// 
 /// Note this trait is object-safe, so we can utilise dyn. 
 pub trait AccountState: Send + Sync {
-    /// Updates the account's state using a new set of raw bytes.
+    /// Updates the account's state using a new set of raw bytes, returning whether they changed.
     ///
     /// This is expensive, a singular linear clone cost is incurred in the size of 
     /// the byte array. 
-    fn update(&self, new_bytes: Vec<u8>, update_time: u64) -> AnyResult<()>;
+    /// 
+    /// Bytes identical to the current ones are only compared (a memcmp), not deserialized, and neither bump 
+    /// the update slot nor notify subscribers, so the update slot only moves on real changes. The update time 
+    /// is still recorded, as the data was confirmed current at that time.
+    fn update(&self, new_bytes: Vec<u8>, update_time: u64) -> AnyResult<bool>;
 
     /// Returns the account's unique identifier, its public key.
     fn pubkey(&self) -> &Pubkey;
@@ -36,6 +46,147 @@ pub trait AccountState: Send + Sync {
 
     /// Allows for runtime downcasting to the concrete type, e.g. `&ManagedAccount<Whirlpool>`.
     fn as_any(&self) -> &dyn Any;
+
+    /// Subscribes to the account's updates without knowing its type, see `ManagedAccount::subscribe` for the
+    /// typed equivalent.
+    ///
+    /// The receiver starts at the latest update, marked as seen, so `changed().await` waits for the next one. 
+    /// Lifecycle changes, e.g. the account closing, are notified too, with the update slot unchanged.
+    fn watch(&self) -> watch::Receiver<UpdateStamp>;
+
+    /// Captures the account's current bytes, deserialized data and update metadata.
+    fn snapshot(&self) -> AccountSnapshot;
+
+    /// Returns where the account is in its lifecycle, see `AccountLifecycle`.
+    fn lifecycle(&self) -> AccountLifecycle;
+
+    /// Marks the account as closed, e.g. because the RPC returned no data for it, returning whether it wasn't 
+    /// already, in which case subscribers are notified. The last data is kept, the next successful `update` 
+    /// makes the account live again.
+    fn mark_closed(&self) -> bool;
+
+    /// Returns the account's update slot, a counter starting at 1 that increments each time the data changes.
+    /// 
+    /// Note: This is a local version number, not a Solana slot.
+    fn update_slot(&self) -> u64;
+
+    /// Returns the Unix nanoseconds timestamp of the last successful update, whether or not the data changed.
+    fn last_update_time(&self) -> u64;
+
+    /// Returns the time elapsed since the last successful update, zero if it's in the future (clock skew).
+    fn age(&self) -> Duration {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        Duration::from_nanos(now.saturating_sub(self.last_update_time()))
+    }
+
+    /// Fails unless the account is live and was last updated within `max_age`, i.e. its data can be trusted 
+    /// for a decision like quoting.
+    fn ensure_fresh(&self, max_age: Duration) -> AnyResult<()> {
+        let lifecycle = self.lifecycle();
+        if !lifecycle.is_live() {
+            return Err(anyhow!("Account {} is stale: it is {:?}", self.pubkey(), lifecycle));
+        }
+        let age = self.age();
+        if age > max_age {
+            return Err(anyhow!(
+                "Account {} is stale: last updated {:?} ago, the maximum age is {:?}",
+                self.pubkey(),
+                age,
+                max_age
+            ));
+        }
+        Ok(())
+    }
+}
+
+// --- Lifecycle --- //
+
+/// Where an account is in its lifecycle, as far as its updates tell.
+/// 
+/// Readers should treat the data of anything but a `Live` account as stale: a `Closed` or `Errored` account keeps 
+/// serving the last data it deserialized.
+/// 
+/// Accounts are constructed from on-chain data, so they start `Live`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum AccountLifecycle {
+    /// The last update succeeded.
+    Live = 0,
+    /// The account no longer exists on-chain, see `AccountState::mark_closed`.
+    Closed = 1,
+    /// The last update's data failed to deserialize.
+    Errored = 2,
+}
+
+impl AccountLifecycle {
+    /// Returns true if the account's data is current, i.e. it's `Live`.
+    pub fn is_live(&self) -> bool {
+        *self == AccountLifecycle::Live
+    }
+}
+
+/// An `AccountLifecycle` that can be shared across threads.
+#[derive(Debug)]
+pub(crate) struct AtomicLifecycle(AtomicU8);
+
+impl AtomicLifecycle {
+    pub(crate) fn new(lifecycle: AccountLifecycle) -> Self {
+        Self(AtomicU8::new(lifecycle as u8))
+    }
+
+    pub(crate) fn load(&self) -> AccountLifecycle {
+        match self.0.load(Ordering::Relaxed) {
+            0 => AccountLifecycle::Live,
+            1 => AccountLifecycle::Closed,
+            _ => AccountLifecycle::Errored,
+        }
+    }
+
+    pub(crate) fn store(&self, lifecycle: AccountLifecycle) {
+        self.0.store(lifecycle as u8, Ordering::Relaxed);
+    }
+
+    /// Stores `lifecycle`, returning whether it differs from the previous one.
+    pub(crate) fn transition(&self, lifecycle: AccountLifecycle) -> bool {
+        self.0.swap(lifecycle as u8, Ordering::Relaxed) != lifecycle as u8
+    }
+}
+
+/// Returns the unqualified name of an account type, e.g. `Whirlpool`, for tracing spans and metric labels.
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+pub(crate) fn account_kind<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+// --- Update Notifications --- //
+
+/// An update of a `ManagedAccount<T>`, as delivered to subscribers.
+#[derive(Debug)]
+pub struct AccountUpdate<T> {
+    /// The account's `update_slot` counter after the update.
+    pub update_slot: u64,
+    /// The Unix nanoseconds timestamp of the update.
+    pub update_time: u64,
+    /// The state before the update, `None` for the initial state.
+    pub previous: Option<Arc<T>>,
+    /// The state after the update.
+    pub current: Arc<T>,
+    /// The account's lifecycle after the update. Lifecycle changes are delivered as updates of their own, 
+    /// whose `previous` and `current` are the same, unchanged state.
+    pub lifecycle: AccountLifecycle,
+}
+
+/// An untyped notice of an update to an account, see `AccountState::watch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateStamp {
+    pub pubkey: Pubkey,
+    /// The account's `update_slot` counter after the update.
+    pub update_slot: u64,
+    /// The Unix nanoseconds timestamp of the update.
+    pub update_time: u64,
+    /// The account's lifecycle after the update.
+    pub lifecycle: AccountLifecycle,
 }
 
 /// Generic struct that manages the state for a specific type of on-chain account.
@@ -67,6 +218,14 @@ where
     update_slot: AtomicU64,
     /// The Unix nanoseconds timestamp of the last successful `update` call
     last_update_time: AtomicU64,
+    /// Publishes each update to typed subscribers, see `subscribe`.
+    /// 
+    /// Only the writer touches the channel, so reads through `get` stay lock-free.
+    updates: watch::Sender<Arc<AccountUpdate<T>>>,
+    /// Publishes each update to untyped subscribers, see `AccountState::watch`.
+    stamps: watch::Sender<UpdateStamp>,
+    /// Where the account is in its lifecycle, see `AccountState::lifecycle`.
+    lifecycle: AtomicLifecycle,
 }
 
 // --- ManagedAccount Struct Implementations --- //
@@ -84,14 +243,30 @@ impl<T: Deserializable + Clone + Send + Sync + 'static> ManagedAccount<T> {
         initial_time: u64,
     ) -> AnyResult<Self> {
         // Invoke the from_bytes method from the Deserializable trait.
-        let initial_deserialized = T::from_bytes(&initial_bytes)?;
+        let initial_deserialized = Arc::new(T::from_bytes(&initial_bytes)?);
+        let (updates, _) = watch::channel(Arc::new(AccountUpdate {
+            update_slot: 1,
+            update_time: initial_time,
+            previous: None,
+            current: initial_deserialized.clone(),
+            lifecycle: AccountLifecycle::Live,
+        }));
+        let (stamps, _) = watch::channel(UpdateStamp {
+            pubkey,
+            update_slot: 1,
+            update_time: initial_time,
+            lifecycle: AccountLifecycle::Live,
+        });
         Ok(Self {
             pubkey,
             // wrap the byte array and deserialized data in concurrency primitives.
             bytes: Arc::new(ArcSwap::new(Arc::new(initial_bytes))),
-            deserialized: Arc::new(ArcSwap::new(Arc::new(initial_deserialized))),
+            deserialized: Arc::new(ArcSwap::new(initial_deserialized)),
             update_slot: AtomicU64::new(1), // Initialized state is the first version
             last_update_time: AtomicU64::new(initial_time),
+            updates,
+            stamps,
+            lifecycle: AtomicLifecycle::new(AccountLifecycle::Live),
         })
     }
 
@@ -125,22 +300,145 @@ impl<T: Deserializable + Clone + Send + Sync + 'static> ManagedAccount<T> {
         // Load returns a guarded arc ptr to the deserialized data
         self.deserialized.load()
     }
+
+    /// As `get`, but fails unless the account is live and was last updated within `max_age`, see 
+    /// `AccountState::ensure_fresh`.
+    pub fn get_if_fresh(&self, max_age: Duration) -> AnyResult<Guard<Arc<T>>> {
+        self.ensure_fresh(max_age)?;
+        Ok(self.get())
+    }
+
+    /// As `get`, but fails unless the account's update slot is at least `update_slot`, e.g. to make sure a read 
+    /// observes an update already seen through `subscribe`.
+    pub fn get_if_slot_at_least(&self, update_slot: u64) -> AnyResult<Guard<Arc<T>>> {
+        // Load the update slot first: the Acquire pairs with the Release in `update`, so the data we load after 
+        // is at least as new as the slot we check.
+        let current = self.update_slot.load(Ordering::Acquire);
+        if current < update_slot {
+            return Err(anyhow!(
+                "Account {} is at update slot {}, expected at least {}",
+                self.pubkey,
+                current,
+                update_slot
+            ));
+        }
+        Ok(self.get())
+    }
+
+    /// Subscribes to the account's updates.
+    ///
+    /// The receiver starts at the latest update, marked as seen, so `changed().await` waits for the next one. 
+    /// Updates are coalesced: a slow subscriber only sees the latest, whose `previous` is the state right before 
+    /// that update, not the last state the subscriber saw. Diff against your own copy of the last seen state 
+    /// if intermediate updates matter.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<AccountUpdate<T>>> {
+        self.updates.subscribe()
+    }
+
+    /// Waits for the account's next update.
+    /// 
+    /// Each call subscribes afresh, so updates landing between calls are missed. Keep a receiver from 
+    /// `subscribe` to wait for updates in a loop.
+    pub async fn next_update(&self) -> AnyResult<Arc<AccountUpdate<T>>> {
+        let mut receiver = self.updates.subscribe();
+        // The sender lives as long as `self`, which we borrow, so this can't fail in practice.
+        receiver.changed().await?;
+        let update = receiver.borrow_and_update().clone();
+        Ok(update)
+    }
+
+    /// Moves the account to `lifecycle`, notifying subscribers if it changed, with the current state.
+    fn transition(&self, lifecycle: AccountLifecycle) -> bool {
+        if !self.lifecycle.transition(lifecycle) {
+            return false;
+        }
+        let current = self.deserialized.load_full();
+        let update_slot = self.update_slot.load(Ordering::Acquire);
+        let update_time = self.last_update_time.load(Ordering::Relaxed);
+        self.updates.send_replace(Arc::new(AccountUpdate {
+            update_slot,
+            update_time,
+            previous: Some(current.clone()),
+            current,
+            lifecycle,
+        }));
+        self.stamps.send_replace(UpdateStamp {
+            pubkey: self.pubkey,
+            update_slot,
+            update_time,
+            lifecycle,
+        });
+        true
+    }
 }
 
 // --- AccountState Trait Implementation --- //
 
 impl<T: Deserializable + Clone + Send + Sync + 'static> AccountState for ManagedAccount<T> {
-    fn update(&self, new_bytes: Vec<u8>, update_time: u64) -> AnyResult<()> {
-        // Attempt the expensive deserialization, aborting with ? if it fails. 
-        let new_deserialized = T::from_bytes(&new_bytes)?;
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "trace",
+        skip_all,
+        err,
+        fields(
+            account = %self.pubkey,
+            kind = account_kind::<T>(),
+            bytes = new_bytes.len(),
+            changed = tracing::field::Empty,
+        ),
+    ))]
+    fn update(&self, new_bytes: Vec<u8>, update_time: u64) -> AnyResult<bool> {
+        // Polling mostly returns unchanged data, which a memcmp catches far cheaper than a deserialization.
+        if **self.bytes.load() == new_bytes {
+            trace_record!("changed" = false);
+            self.last_update_time.store(update_time, Ordering::Relaxed);
+            self.transition(AccountLifecycle::Live);
+            #[cfg(feature = "metrics")]
+            metrics::record_account_update::<T>(UpdateOutcome::Unchanged, None);
+            return Ok(false);
+        }
+
+        // Attempt the expensive deserialization, aborting if it fails. 
+        #[cfg(feature = "metrics")]
+        let deserialize_start = std::time::Instant::now();
+        let new_deserialized = match T::from_bytes(&new_bytes) {
+            Ok(new_deserialized) => new_deserialized,
+            Err(e) => {
+                self.transition(AccountLifecycle::Errored);
+                #[cfg(feature = "metrics")]
+                metrics::record_account_update::<T>(UpdateOutcome::Failed, None);
+                return Err(e);
+            }
+        };
+        #[cfg(feature = "metrics")]
+        metrics::record_account_update::<T>(UpdateOutcome::Changed, Some(deserialize_start.elapsed()));
 
         // If successful, atomically update raw bytes, deserialized data, and metadata.
+        let new_deserialized = Arc::new(new_deserialized);
         self.bytes.store(Arc::new(new_bytes));
-        self.deserialized.store(Arc::new(new_deserialized));
-        // We use the fetch_add and store methods for u64 to ensure atomicity is preserved across threads.
-        self.update_slot.fetch_add(1, Ordering::Relaxed);
+        let previous = self.deserialized.swap(new_deserialized.clone());
+        // We use the fetch_add and store methods for u64 to ensure atomicity is preserved across threads. Release, 
+        // so readers that Acquire the new slot also see the data stored above, see `get_if_slot_at_least`.
+        let update_slot = self.update_slot.fetch_add(1, Ordering::Release) + 1;
         self.last_update_time.store(update_time, Ordering::Relaxed);
-        Ok(())
+        self.lifecycle.store(AccountLifecycle::Live);
+
+        // Notify subscribers last, so they observe the new state through `get` too. `send_replace` doesn't 
+        // need a receiver to exist.
+        self.updates.send_replace(Arc::new(AccountUpdate {
+            update_slot,
+            update_time,
+            previous: Some(previous),
+            current: new_deserialized,
+            lifecycle: AccountLifecycle::Live,
+        }));
+        self.stamps.send_replace(UpdateStamp {
+            pubkey: self.pubkey,
+            update_slot,
+            update_time,
+            lifecycle: AccountLifecycle::Live,
+        });
+        trace_record!("changed" = true);
+        Ok(true)
     }
 
     fn pubkey(&self) -> &Pubkey {
@@ -155,4 +453,34 @@ impl<T: Deserializable + Clone + Send + Sync + 'static> AccountState for Managed
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn watch(&self) -> watch::Receiver<UpdateStamp> {
+        self.stamps.subscribe()
+    }
+
+    fn snapshot(&self) -> AccountSnapshot {
+        AccountSnapshot {
+            pubkey: self.pubkey,
+            bytes: self.bytes.load_full(),
+            data: self.deserialized.load_full(),
+            update_slot: self.update_slot.load(Ordering::Relaxed),
+            update_time: self.last_update_time.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lifecycle(&self) -> AccountLifecycle {
+        self.lifecycle.load()
+    }
+
+    fn mark_closed(&self) -> bool {
+        self.transition(AccountLifecycle::Closed)
+    }
+
+    fn update_slot(&self) -> u64 {
+        self.update_slot.load(Ordering::Relaxed)
+    }
+
+    fn last_update_time(&self) -> u64 {
+        self.last_update_time.load(Ordering::Relaxed)
+    }
 }
@@ -0,0 +1,190 @@
+//! Assembles instructions into signed v0 transactions.
+//!
+//! The DEX specific builders (e.g. `OrcaSwapBuilder`) only produce `Instruction`s, this is where they become a
+//! transaction: compute budget instructions are prepended, the message is compiled against the supplied address
+//! lookup tables, and it's signed through the `TransactionSigner` trait so consumers can bring their own signing.
+
+use crate::common::types::AnyResult;
+use anyhow::anyhow;
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    transaction::VersionedTransaction,
+};
+
+/// The largest a serialized transaction can be, 1232 bytes: an IPv6 packet less its headers.
+pub const MAX_TRANSACTION_SIZE: usize = PACKET_DATA_SIZE;
+
+/// Anything that can sign transactions, e.g. a local keypair, a remote signer or a hardware wallet.
+///
+/// Implemented for every `solana_sdk::signer::Signer`, so `Keypair`s work out of the box.
+pub trait TransactionSigner: Send + Sync {
+    /// The pubkey the signatures are verified against.
+    fn pubkey(&self) -> Pubkey;
+
+    /// Signs the serialized message.
+    fn sign_message(&self, message: &[u8]) -> AnyResult<Signature>;
+}
+
+impl<T: Signer + Send + Sync> TransactionSigner for T {
+    fn pubkey(&self) -> Pubkey {
+        Signer::pubkey(self)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> AnyResult<Signature> {
+        self.try_sign_message(message)
+            .map_err(|e| anyhow!("Failed to sign message with {}: {}", Signer::pubkey(self), e))
+    }
+}
+
+/// A signed transaction along with its serialized size.
+#[derive(Debug, Clone)]
+pub struct SignedTransaction {
+    pub transaction: VersionedTransaction,
+    /// The serialized size in bytes, see `MAX_TRANSACTION_SIZE`.
+    pub size: usize,
+}
+
+impl SignedTransaction {
+    /// Returns true if the transaction fits in a packet, i.e. can be sent at all.
+    pub fn fits_in_packet(&self) -> bool {
+        self.size <= MAX_TRANSACTION_SIZE
+    }
+}
+
+/// Builder for a v0 transaction.
+///
+/// Compute budget instructions are only added if their limit or price is set, and always go first.
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    payer: Pubkey,
+    instructions: Vec<Instruction>,
+    compute_unit_limit: Option<u32>,
+    compute_unit_price: Option<u64>,
+    lookup_tables: Vec<AddressLookupTableAccount>,
+}
+
+impl TransactionBuilder {
+    /// Starts building a transaction whose fees are paid by `payer`.
+    pub fn new(payer: Pubkey) -> Self {
+        Self {
+            payer,
+            instructions: Vec::new(),
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            lookup_tables: Vec::new(),
+        }
+    }
+
+    /// Appends an instruction.
+    pub fn instruction(mut self, instruction: Instruction) -> Self {
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Appends instructions, in order.
+    pub fn instructions(mut self, instructions: impl IntoIterator<Item = Instruction>) -> Self {
+        self.instructions.extend(instructions);
+        self
+    }
+
+    /// The compute unit limit, which caps the transaction's compute and so its priority fee.
+    pub fn compute_unit_limit(mut self, units: u32) -> Self {
+        self.compute_unit_limit = Some(units);
+        self
+    }
+
+    /// The compute unit price in micro-lamports, i.e. the priority fee per compute unit.
+    pub fn compute_unit_price(mut self, micro_lamports: u64) -> Self {
+        self.compute_unit_price = Some(micro_lamports);
+        self
+    }
+
+    /// Adds an address lookup table the message may use to shorten its account keys.
+    ///
+    /// Note: Only tables whose addresses are actually referenced end up in the message.
+    pub fn lookup_table(mut self, lookup_table: AddressLookupTableAccount) -> Self {
+        self.lookup_tables.push(lookup_table);
+        self
+    }
+
+    /// Adds address lookup tables, see `lookup_table`.
+    pub fn lookup_tables(mut self, lookup_tables: impl IntoIterator<Item = AddressLookupTableAccount>) -> Self {
+        self.lookup_tables.extend(lookup_tables);
+        self
+    }
+
+    /// Compiles the v0 message against `recent_blockhash`.
+    pub fn compile(&self, recent_blockhash: Hash) -> AnyResult<VersionedMessage> {
+        let mut instructions = Vec::with_capacity(self.instructions.len() + 2);
+        if let Some(units) = self.compute_unit_limit {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+        if let Some(micro_lamports) = self.compute_unit_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(micro_lamports));
+        }
+        instructions.extend(self.instructions.iter().cloned());
+        let message = v0::Message::try_compile(&self.payer, &instructions, &self.lookup_tables, recent_blockhash)
+            .map_err(|e| anyhow!("Failed to compile transaction message: {}", e))?;
+        Ok(VersionedMessage::V0(message))
+    }
+
+    /// Returns the serialized size the transaction will have once signed, without signing it.
+    ///
+    /// Useful to check a transaction fits before asking a (possibly remote) signer for anything.
+    pub fn serialized_size(&self, recent_blockhash: Hash) -> AnyResult<usize> {
+        let message = self.compile(recent_blockhash)?;
+        Ok(serialized_size(&message))
+    }
+
+    /// Compiles the message and signs it with `signers`, which must be exactly the message's required signers
+    /// (the payer and any signer accounts of the instructions), in any order.
+    pub fn build(&self, recent_blockhash: Hash, signers: &[&dyn TransactionSigner]) -> AnyResult<SignedTransaction> {
+        let message = self.compile(recent_blockhash)?;
+        let transaction = sign(message, signers)?;
+        let size = serialized_size(&transaction.message);
+        Ok(SignedTransaction { transaction, size })
+    }
+}
+
+/// Signs a compiled message, matching each of its required signer pubkeys to one of `signers`.
+///
+/// Fails if a required signer is missing or a signer isn't required, as the runtime would reject either.
+pub fn sign(message: VersionedMessage, signers: &[&dyn TransactionSigner]) -> AnyResult<VersionedTransaction> {
+    let num_required_signatures = message.header().num_required_signatures as usize;
+    let required_signers = &message.static_account_keys()[..num_required_signatures];
+    if let Some(extra) = signers.iter().find(|signer| !required_signers.contains(&signer.pubkey())) {
+        return Err(anyhow!("{} isn't a required signer of the transaction", extra.pubkey()));
+    }
+    let message_bytes = message.serialize();
+    let signatures = required_signers
+        .iter()
+        .map(|required| {
+            signers
+                .iter()
+                .find(|signer| signer.pubkey() == *required)
+                .ok_or_else(|| anyhow!("Missing signer {} of the transaction", required))
+                .and_then(|signer| signer.sign_message(&message_bytes))
+        })
+        .collect::<AnyResult<Vec<Signature>>>()?;
+    Ok(VersionedTransaction { signatures, message })
+}
+
+/// Returns the serialized size of a transaction carrying `message`: the compact-u16 signature count, a 64 byte
+/// signature per required signer, then the message itself.
+pub fn serialized_size(message: &VersionedMessage) -> usize {
+    let num_signatures = message.header().num_required_signatures as usize;
+    let compact_len = match num_signatures {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    };
+    compact_len + num_signatures * std::mem::size_of::<Signature>() + message.serialize().len()
+}
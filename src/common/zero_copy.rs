@@ -0,0 +1,415 @@
+//! Alternatives to `ManagedAccount` for hot accounts, which avoid deserializing on every update.
+//!
+//! `ManagedAccount<T>` deserializes each update into an owned `T` and keeps the bytes too, which for big accounts
+//! like tick arrays doubles the memory and costs a full parse per update, whether or not anyone reads it.
+//!
+//! - `ZeroCopyAccount<T>` is for `#[repr(C)]` layouts that are `bytemuck::Pod`: it only stores the bytes, and
+//!   `get()` reinterprets them in place as a `&T`.
+//! - `LazyAccount<T>` works for any `Deserializable`: it only stores the bytes on update, and deserializes on the
+//!   first `get()` after it, caching the result until the next update.
+//!
+//! Both implement `AccountState`, so pools can mix them with `ManagedAccount`s.
+
+use crate::common::{
+    deserialize::Deserializable,
+    snapshot::AccountSnapshot,
+    state::{AccountLifecycle, AccountState, AtomicLifecycle, UpdateStamp},
+    types::AnyResult,
+};
+#[cfg(feature = "metrics")]
+use crate::common::metrics::{self, UpdateOutcome};
+#[cfg(feature = "tracing")]
+use crate::common::state::account_kind;
+use anyhow::anyhow;
+use arc_swap::{ArcSwap, ArcSwapOption, Guard};
+use bytemuck::Pod;
+use solana_sdk::pubkey::Pubkey;
+use std::any::Any;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// The bytes, update metadata and notifications shared by the account types of this module.
+struct RawAccount {
+    pubkey: Pubkey,
+    bytes: ArcSwap<Vec<u8>>,
+    /// Incremented after the bytes are stored, with release ordering, so a reader that loads it with acquire
+    /// ordering sees bytes at least as new.
+    update_slot: AtomicU64,
+    last_update_time: AtomicU64,
+    stamps: watch::Sender<UpdateStamp>,
+    lifecycle: AtomicLifecycle,
+}
+
+impl RawAccount {
+    fn new(pubkey: Pubkey, initial_bytes: Vec<u8>, initial_time: u64) -> Self {
+        let (stamps, _) = watch::channel(UpdateStamp {
+            pubkey,
+            update_slot: 1,
+            update_time: initial_time,
+            lifecycle: AccountLifecycle::Live,
+        });
+        Self {
+            pubkey,
+            bytes: ArcSwap::new(Arc::new(initial_bytes)),
+            update_slot: AtomicU64::new(1),
+            last_update_time: AtomicU64::new(initial_time),
+            stamps,
+            lifecycle: AtomicLifecycle::new(AccountLifecycle::Live),
+        }
+    }
+
+    /// Stores the bytes unless they're unchanged, returning whether they changed, see `AccountState::update`.
+    ///
+    /// Unchanged bytes revive a `Closed` account, but leave an `Errored` one be: they're the bytes it errored on.
+    fn store(&self, new_bytes: Vec<u8>, update_time: u64) -> bool {
+        if **self.bytes.load() == new_bytes {
+            self.last_update_time.store(update_time, Ordering::Relaxed);
+            if self.lifecycle.load() == AccountLifecycle::Closed {
+                self.transition(AccountLifecycle::Live);
+            }
+            return false;
+        }
+        self.bytes.store(Arc::new(new_bytes));
+        let update_slot = self.update_slot.fetch_add(1, Ordering::Release) + 1;
+        self.last_update_time.store(update_time, Ordering::Relaxed);
+        self.lifecycle.store(AccountLifecycle::Live);
+        self.stamps.send_replace(UpdateStamp {
+            pubkey: self.pubkey,
+            update_slot,
+            update_time,
+            lifecycle: AccountLifecycle::Live,
+        });
+        true
+    }
+
+    /// Moves the account to `lifecycle`, notifying subscribers if it changed.
+    fn transition(&self, lifecycle: AccountLifecycle) -> bool {
+        if !self.lifecycle.transition(lifecycle) {
+            return false;
+        }
+        self.stamps.send_replace(UpdateStamp {
+            pubkey: self.pubkey,
+            update_slot: self.update_slot.load(Ordering::Acquire),
+            update_time: self.last_update_time.load(Ordering::Relaxed),
+            lifecycle,
+        });
+        true
+    }
+
+    /// Loads the update slot, then the bytes, which are therefore at least as new as it.
+    fn load(&self) -> (u64, Arc<Vec<u8>>) {
+        let update_slot = self.update_slot.load(Ordering::Acquire);
+        (update_slot, self.bytes.load_full())
+    }
+
+    /// Builds a snapshot of `bytes`, as returned by `load` along with `update_slot`, and their `data`.
+    fn snapshot(&self, data: Arc<dyn Any + Send + Sync>, bytes: Arc<Vec<u8>>, update_slot: u64) -> AccountSnapshot {
+        AccountSnapshot {
+            pubkey: self.pubkey,
+            bytes,
+            data,
+            update_slot,
+            update_time: self.last_update_time.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// --- Zero Copy --- //
+
+/// An account read in place from its bytes, for `bytemuck::Pod` layouts.
+///
+/// `T` is the layout of the account data from byte `offset` on, e.g. 8 to skip an Anchor discriminator. It must
+/// have an alignment of 1, i.e. be `#[repr(C, packed)]` or made only of byte arrays, as account data carries no
+/// alignment guarantee. Updates only check the data is long enough.
+pub struct ZeroCopyAccount<T: Pod + Send + Sync> {
+    raw: RawAccount,
+    offset: usize,
+    _layout: PhantomData<T>,
+}
+
+/// A guarded, in place view of a `ZeroCopyAccount`'s data, see `ZeroCopyAccount::get`.
+///
+/// Holds the bytes it reads from alive, so it's unaffected by later updates.
+pub struct ZeroCopyRef<T: Pod> {
+    bytes: Guard<Arc<Vec<u8>>>,
+    offset: usize,
+    _layout: PhantomData<T>,
+}
+
+impl<T: Pod> Deref for ZeroCopyRef<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // The length and alignment were checked when the bytes were stored.
+        bytemuck::from_bytes(&self.bytes[self.offset..self.offset + std::mem::size_of::<T>()])
+    }
+}
+
+impl<T: Pod + Send + Sync> ZeroCopyAccount<T> {
+    /// Constructs the account from its on-chain data, whose `T` starts at byte `offset`.
+    ///
+    /// Fails if `T` isn't 1-aligned or the data is too short.
+    pub fn new_initialized_from_bytes(
+        pubkey: Pubkey,
+        initial_bytes: Vec<u8>,
+        initial_time: u64,
+        offset: usize,
+    ) -> AnyResult<Self> {
+        if std::mem::align_of::<T>() != 1 {
+            return Err(anyhow!(
+                "ZeroCopyAccount layouts must have an alignment of 1, {} has {}",
+                std::any::type_name::<T>(),
+                std::mem::align_of::<T>()
+            ));
+        }
+        check_len::<T>(&initial_bytes, offset)?;
+        Ok(Self {
+            raw: RawAccount::new(pubkey, initial_bytes, initial_time),
+            offset,
+            _layout: PhantomData,
+        })
+    }
+
+    /// Provides read-only access to the data, in place.
+    pub fn get(&self) -> ZeroCopyRef<T> {
+        ZeroCopyRef {
+            bytes: self.raw.bytes.load(),
+            offset: self.offset,
+            _layout: PhantomData,
+        }
+    }
+}
+
+impl<T: Pod + Send + Sync> AccountState for ZeroCopyAccount<T> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "trace",
+        skip_all,
+        err,
+        fields(
+            account = %self.raw.pubkey,
+            kind = account_kind::<T>(),
+            bytes = new_bytes.len(),
+            changed = tracing::field::Empty,
+        ),
+    ))]
+    fn update(&self, new_bytes: Vec<u8>, update_time: u64) -> AnyResult<bool> {
+        if let Err(e) = check_len::<T>(&new_bytes, self.offset) {
+            self.raw.transition(AccountLifecycle::Errored);
+            #[cfg(feature = "metrics")]
+            metrics::record_account_update::<T>(UpdateOutcome::Failed, None);
+            return Err(e);
+        }
+        let changed = self.raw.store(new_bytes, update_time);
+        if !changed {
+            // Unlike `LazyAccount`, bad bytes are never stored, so unchanged bytes are good even if the last
+            // update wasn't.
+            self.raw.transition(AccountLifecycle::Live);
+        }
+        // Nothing is deserialized, so there's no duration to record.
+        #[cfg(feature = "metrics")]
+        metrics::record_account_update::<T>(record_outcome(changed), None);
+        trace_record!("changed" = changed);
+        Ok(changed)
+    }
+
+    fn pubkey(&self) -> &Pubkey {
+        &self.raw.pubkey
+    }
+
+    fn bytes(&self) -> Guard<Arc<Vec<u8>>> {
+        self.raw.bytes.load()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn watch(&self) -> watch::Receiver<UpdateStamp> {
+        self.raw.stamps.subscribe()
+    }
+
+    /// Note: The snapshot's data is an owned copy of the `T`, so it can be downcast like any other account's.
+    fn snapshot(&self) -> AccountSnapshot {
+        let (update_slot, bytes) = self.raw.load();
+        let data: T = *bytemuck::from_bytes(&bytes[self.offset..self.offset + std::mem::size_of::<T>()]);
+        self.raw.snapshot(Arc::new(data), bytes, update_slot)
+    }
+
+    fn lifecycle(&self) -> AccountLifecycle {
+        self.raw.lifecycle.load()
+    }
+
+    fn mark_closed(&self) -> bool {
+        self.raw.transition(AccountLifecycle::Closed)
+    }
+
+    fn update_slot(&self) -> u64 {
+        self.raw.update_slot.load(Ordering::Acquire)
+    }
+
+    fn last_update_time(&self) -> u64 {
+        self.raw.last_update_time.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn record_outcome(changed: bool) -> UpdateOutcome {
+    if changed {
+        UpdateOutcome::Changed
+    } else {
+        UpdateOutcome::Unchanged
+    }
+}
+
+fn check_len<T>(bytes: &[u8], offset: usize) -> AnyResult<()> {
+    let required = offset + std::mem::size_of::<T>();
+    if bytes.len() < required {
+        return Err(anyhow!(
+            "Account data is {} bytes, {} needs {}",
+            bytes.len(),
+            std::any::type_name::<T>(),
+            required
+        ));
+    }
+    Ok(())
+}
+
+// --- Lazy --- //
+
+/// An account deserialized on demand rather than on update.
+///
+/// Updates just store the bytes, so they're cheap but can't reject bad data: a `get()` on data that doesn't
+/// deserialize fails instead.
+pub struct LazyAccount<T: Deserializable + Send + Sync + 'static> {
+    raw: RawAccount,
+    /// The last deserialized data, tagged with the `update_slot` it's at least as new as.
+    cache: ArcSwapOption<(u64, Arc<T>)>,
+}
+
+impl<T: Deserializable + Send + Sync + 'static> LazyAccount<T> {
+    /// Constructs the account from its on-chain data, without deserializing it.
+    pub fn new_from_bytes(pubkey: Pubkey, initial_bytes: Vec<u8>, initial_time: u64) -> Self {
+        Self {
+            raw: RawAccount::new(pubkey, initial_bytes, initial_time),
+            cache: ArcSwapOption::empty(),
+        }
+    }
+
+    /// Returns the deserialized data, deserializing it if it was updated since the last call.
+    ///
+    /// Concurrent first calls after an update may each deserialize, the last one's result is kept. As updates 
+    /// can't reject bad data, a failure here marks the account `AccountLifecycle::Errored`, until different bytes
+    /// arrive.
+    pub fn get(&self) -> AnyResult<Arc<T>> {
+        // Load the counter before the bytes, so the tag never claims newer data than it holds.
+        let update_slot = self.raw.update_slot.load(Ordering::Acquire);
+        if let Some(cached) = self.cached(update_slot) {
+            return Ok(cached);
+        }
+        self.deserialize(update_slot, &self.raw.bytes.load())
+    }
+
+    /// Returns the cached data if it's tagged with `update_slot`.
+    fn cached(&self, update_slot: u64) -> Option<Arc<T>> {
+        self.cache.load().as_ref().filter(|cached| cached.0 == update_slot).map(|cached| cached.1.clone())
+    }
+
+    /// Deserializes `bytes`, loaded after `update_slot`, and caches the result tagged with it.
+    fn deserialize(&self, update_slot: u64, bytes: &[u8]) -> AnyResult<Arc<T>> {
+        #[cfg(feature = "metrics")]
+        let deserialize_start = std::time::Instant::now();
+        let deserialized = match T::from_bytes(bytes) {
+            Ok(deserialized) => {
+                #[cfg(feature = "metrics")]
+                metrics::record_deserialize_duration::<T>(deserialize_start.elapsed());
+                Arc::new(deserialized)
+            }
+            Err(e) => {
+                // Unless an update has landed since, which is left for the next `get()` to judge.
+                if self.raw.update_slot.load(Ordering::Acquire) == update_slot {
+                    self.raw.transition(AccountLifecycle::Errored);
+                }
+                return Err(e);
+            }
+        };
+        self.cache.store(Some(Arc::new((update_slot, deserialized.clone()))));
+        Ok(deserialized)
+    }
+
+    /// Returns true if the data was updated since it was last deserialized, i.e. the next `get()` will parse.
+    pub fn is_stale(&self) -> bool {
+        let update_slot = self.raw.update_slot.load(Ordering::Acquire);
+        self.cache.load().as_ref().is_none_or(|cached| cached.0 != update_slot)
+    }
+}
+
+impl<T: Deserializable + Send + Sync + 'static> AccountState for LazyAccount<T> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "trace",
+        skip_all,
+        fields(
+            account = %self.raw.pubkey,
+            kind = account_kind::<T>(),
+            bytes = new_bytes.len(),
+            changed = tracing::field::Empty,
+        ),
+    ))]
+    fn update(&self, new_bytes: Vec<u8>, update_time: u64) -> AnyResult<bool> {
+        let changed = self.raw.store(new_bytes, update_time);
+        // Nothing is deserialized, so there's no duration to record.
+        #[cfg(feature = "metrics")]
+        metrics::record_account_update::<T>(record_outcome(changed), None);
+        trace_record!("changed" = changed);
+        Ok(changed)
+    }
+
+    fn pubkey(&self) -> &Pubkey {
+        &self.raw.pubkey
+    }
+
+    fn bytes(&self) -> Guard<Arc<Vec<u8>>> {
+        self.raw.bytes.load()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn watch(&self) -> watch::Receiver<UpdateStamp> {
+        self.raw.stamps.subscribe()
+    }
+
+    /// Note: Snapshotting deserializes the data if needed. If it doesn't deserialize, the snapshot's data is the
+    /// raw bytes, i.e. downcasts to `Vec<u8>` rather than `T`.
+    fn snapshot(&self) -> AccountSnapshot {
+        let (update_slot, bytes) = self.raw.load();
+        let deserialized = match self.cached(update_slot) {
+            Some(cached) => Ok(cached),
+            None => self.deserialize(update_slot, &bytes),
+        };
+        let data: Arc<dyn Any + Send + Sync> = match deserialized {
+            Ok(deserialized) => deserialized,
+            Err(_) => bytes.clone(),
+        };
+        self.raw.snapshot(data, bytes, update_slot)
+    }
+
+    fn lifecycle(&self) -> AccountLifecycle {
+        self.raw.lifecycle.load()
+    }
+
+    fn mark_closed(&self) -> bool {
+        self.raw.transition(AccountLifecycle::Closed)
+    }
+
+    fn update_slot(&self) -> u64 {
+        self.raw.update_slot.load(Ordering::Acquire)
+    }
+
+    fn last_update_time(&self) -> u64 {
+        self.raw.last_update_time.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,151 @@
+//! Bundle assembly from `TransactionBuilder`s.
+
+use crate::common::{
+    transaction::{
+        serialized_size, sign, SignedTransaction, TransactionBuilder, TransactionSigner, MAX_TRANSACTION_SIZE,
+    },
+    types::AnyResult,
+};
+use crate::jito::{tip_account, tip_instruction, MAX_BUNDLE_TRANSACTIONS, MIN_TIP_LAMPORTS};
+use anyhow::anyhow;
+use solana_sdk::{
+    hash::{hash, Hash},
+    pubkey::Pubkey,
+    transaction::VersionedTransaction,
+};
+
+/// A signed bundle, ready to send.
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    pub transactions: Vec<VersionedTransaction>,
+}
+
+impl Bundle {
+    /// Wraps already signed transactions, checking there are between 1 and `MAX_BUNDLE_TRANSACTIONS`.
+    ///
+    /// Note: Nothing checks the bundle pays a tip, use `BundleBuilder` for that.
+    pub fn new(transactions: Vec<VersionedTransaction>) -> AnyResult<Self> {
+        if transactions.is_empty() || transactions.len() > MAX_BUNDLE_TRANSACTIONS {
+            return Err(anyhow!(
+                "A bundle holds 1 to {} transactions, got {}",
+                MAX_BUNDLE_TRANSACTIONS,
+                transactions.len()
+            ));
+        }
+        Ok(Self { transactions })
+    }
+
+    /// Returns the bundle's id as the block engine derives it: the hex encoded sha256 of the transactions' (first)
+    /// signatures, base58 encoded and comma separated.
+    pub fn id(&self) -> String {
+        bundle_id(&self.transactions)
+    }
+}
+
+/// Returns the id of a bundle of `transactions`, see `Bundle::id`.
+pub fn bundle_id(transactions: &[VersionedTransaction]) -> String {
+    let signatures = transactions
+        .iter()
+        .map(|transaction| transaction.signatures.first().copied().unwrap_or_default().to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    hash(signatures.as_bytes()).to_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Builder for a `Bundle`.
+///
+/// Every transaction is compiled against the same blockhash and signed by the subset of the signers it needs.
+/// The tip instruction is appended to the last transaction.
+#[derive(Debug, Clone, Default)]
+pub struct BundleBuilder {
+    transactions: Vec<TransactionBuilder>,
+    tip: Option<(Pubkey, Pubkey, u64)>,
+}
+
+impl BundleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a transaction.
+    pub fn transaction(mut self, transaction: TransactionBuilder) -> Self {
+        self.transactions.push(transaction);
+        self
+    }
+
+    /// Appends transactions, in order.
+    pub fn transactions(mut self, transactions: impl IntoIterator<Item = TransactionBuilder>) -> Self {
+        self.transactions.extend(transactions);
+        self
+    }
+
+    /// The tip of `lamports` paid by `payer` to one of the tip accounts, see `tip_account`.
+    pub fn tip(self, payer: Pubkey, lamports: u64) -> Self {
+        self.tip_to(payer, tip_account(), lamports)
+    }
+
+    /// The tip of `lamports` paid by `payer` to `tip_account`, e.g. one returned by `JitoClient::get_tip_accounts`.
+    pub fn tip_to(mut self, payer: Pubkey, tip_account: Pubkey, lamports: u64) -> Self {
+        self.tip = Some((payer, tip_account, lamports));
+        self
+    }
+
+    /// Compiles and signs every transaction against `recent_blockhash`.
+    ///
+    /// Fails if there's no tip, the tip is below `MIN_TIP_LAMPORTS`, there are too many or no transactions, or
+    /// any transaction doesn't fit in a packet.
+    pub fn build(&self, recent_blockhash: Hash, signers: &[&dyn TransactionSigner]) -> AnyResult<Bundle> {
+        let (payer, tip_account, lamports) = self.tip.ok_or_else(|| anyhow!("A bundle must pay a tip"))?;
+        if lamports < MIN_TIP_LAMPORTS {
+            return Err(anyhow!("Tip of {} lamports is below the minimum of {}", lamports, MIN_TIP_LAMPORTS));
+        }
+        let last = self
+            .transactions
+            .len()
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("A bundle must hold at least one transaction"))?;
+
+        let transactions = self
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(i, builder)| {
+                let builder = if i == last {
+                    builder.clone().instruction(tip_instruction(&payer, &tip_account, lamports))
+                } else {
+                    builder.clone()
+                };
+                let signed = sign_required(&builder, recent_blockhash, signers)?;
+                if !signed.fits_in_packet() {
+                    return Err(anyhow!(
+                        "Transaction {} of the bundle is {} bytes, the limit is {}",
+                        i,
+                        signed.size,
+                        MAX_TRANSACTION_SIZE
+                    ));
+                }
+                Ok(signed.transaction)
+            })
+            .collect::<AnyResult<Vec<_>>>()?;
+        Bundle::new(transactions)
+    }
+}
+
+/// Signs `builder`'s transaction with the signers it requires, as bundles usually share a pool of signers.
+fn sign_required(
+    builder: &TransactionBuilder,
+    recent_blockhash: Hash,
+    signers: &[&dyn TransactionSigner],
+) -> AnyResult<SignedTransaction> {
+    let message = builder.compile(recent_blockhash)?;
+    let num_required_signatures = message.header().num_required_signatures as usize;
+    let required_signers = &message.static_account_keys()[..num_required_signatures];
+    let signers: Vec<&dyn TransactionSigner> = signers
+        .iter()
+        .filter(|signer| required_signers.contains(&signer.pubkey()))
+        .copied()
+        .collect();
+    let transaction = sign(message, &signers)?;
+    let size = serialized_size(&transaction.message);
+    Ok(SignedTransaction { transaction, size })
+}
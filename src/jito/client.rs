@@ -0,0 +1,186 @@
+//! The block engine's bundle API over a `BundleTransport`.
+
+use crate::common::types::AnyResult;
+use crate::jito::{bundle::Bundle, transport::BundleTransport};
+use anyhow::anyhow;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use std::{str::FromStr, time::Duration};
+use tokio::time::{sleep, Instant};
+
+/// The most bundle ids a status request can ask about.
+pub const MAX_BUNDLE_IDS_PER_STATUS_REQUEST: usize = 5;
+
+/// The status of a bundle as it makes its way through the block engine, from `getInflightBundleStatuses`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InflightStatus {
+    /// The block engine doesn't know the bundle, e.g. it was never received or is over 5 minutes old.
+    Invalid,
+    /// The bundle hasn't landed or failed yet.
+    Pending,
+    /// No leader landed the bundle, e.g. it was outbid, simulated to a failure or its blockhash expired.
+    Failed,
+    /// The bundle landed, see `landed_slot`.
+    Landed,
+}
+
+impl InflightStatus {
+    /// Returns true if the bundle won't change status anymore.
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Failed | Self::Landed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InflightBundleStatus {
+    pub bundle_id: String,
+    pub status: InflightStatus,
+    pub landed_slot: Option<u64>,
+}
+
+/// The on-chain status of a landed bundle, from `getBundleStatuses`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleStatus {
+    pub bundle_id: String,
+    /// The signatures of the bundle's transactions, base58 encoded.
+    pub transactions: Vec<String>,
+    pub slot: u64,
+    /// `processed`, `confirmed` or `finalized`.
+    pub confirmation_status: Option<String>,
+    /// The transaction error, as the RPC `Result` JSON, i.e. `{"Ok": null}` on success.
+    pub err: Value,
+}
+
+impl BundleStatus {
+    /// Returns true if the bundle's transactions executed without error.
+    pub fn is_ok(&self) -> bool {
+        self.err.is_null() || self.err.get("Ok").is_some()
+    }
+}
+
+/// A block engine client.
+///
+/// `T` is the transport, see `HttpTransport` and `LocalBlockEngine`.
+pub struct JitoClient<T: BundleTransport> {
+    transport: T,
+}
+
+impl<T: BundleTransport> JitoClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Returns the underlying transport, e.g. to drive a `LocalBlockEngine`.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Sends a bundle, returning its id as assigned by the block engine.
+    ///
+    /// Note: Acceptance only means the bundle was well formed, poll its status to know whether it landed.
+    pub async fn send_bundle(&self, bundle: &Bundle) -> AnyResult<String> {
+        let transactions = bundle
+            .transactions
+            .iter()
+            .map(|transaction| {
+                bincode::serialize(transaction)
+                    .map(|bytes| STANDARD.encode(bytes))
+                    .map_err(|e| anyhow!("Failed to serialize bundle transaction: {}", e))
+            })
+            .collect::<AnyResult<Vec<_>>>()?;
+        let result = self
+            .transport
+            .request("sendBundle", json!([transactions, { "encoding": "base64" }]))
+            .await?;
+        parse("sendBundle", result)
+    }
+
+    /// Returns the statuses of the bundles `bundle_ids` that are in flight or recently landed, in order.
+    pub async fn get_inflight_bundle_statuses(&self, bundle_ids: &[String]) -> AnyResult<Vec<InflightBundleStatus>> {
+        let statuses: Vec<InflightBundleStatus> = self.request_statuses("getInflightBundleStatuses", bundle_ids).await?;
+        Ok(bundle_ids
+            .iter()
+            .map(|bundle_id| {
+                statuses
+                    .iter()
+                    .find(|status| &status.bundle_id == bundle_id)
+                    .cloned()
+                    .unwrap_or_else(|| InflightBundleStatus {
+                        bundle_id: bundle_id.clone(),
+                        status: InflightStatus::Invalid,
+                        landed_slot: None,
+                    })
+            })
+            .collect())
+    }
+
+    /// Returns the on-chain statuses of the bundles `bundle_ids`, in order, `None` for those that haven't landed.
+    pub async fn get_bundle_statuses(&self, bundle_ids: &[String]) -> AnyResult<Vec<Option<BundleStatus>>> {
+        let statuses: Vec<Option<BundleStatus>> = self.request_statuses("getBundleStatuses", bundle_ids).await?;
+        Ok(bundle_ids
+            .iter()
+            .map(|bundle_id| statuses.iter().flatten().find(|status| &status.bundle_id == bundle_id).cloned())
+            .collect())
+    }
+
+    /// Returns the block engine's current tip accounts.
+    pub async fn get_tip_accounts(&self) -> AnyResult<Vec<Pubkey>> {
+        let result = self.transport.request("getTipAccounts", json!([])).await?;
+        parse::<Vec<String>>("getTipAccounts", result)?
+            .iter()
+            .map(|address| Pubkey::from_str(address).map_err(|e| anyhow!("Invalid tip account {}: {}", address, e)))
+            .collect()
+    }
+
+    /// Polls the in-flight status of `bundle_id` every `poll_interval` until it's final, see
+    /// `InflightStatus::is_final`, or `timeout` elapses, in which case it errors.
+    pub async fn wait_for_bundle(
+        &self,
+        bundle_id: &str,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> AnyResult<InflightBundleStatus> {
+        let deadline = Instant::now() + timeout;
+        let bundle_ids = [bundle_id.to_string()];
+        loop {
+            let status = self
+                .get_inflight_bundle_statuses(&bundle_ids)
+                .await?
+                .pop()
+                .ok_or_else(|| anyhow!("No status returned for bundle {}", bundle_id))?;
+            if status.status.is_final() {
+                return Ok(status);
+            }
+            if Instant::now() + poll_interval > deadline {
+                return Err(anyhow!(
+                    "Bundle {} is still {:?} after {:?}",
+                    bundle_id,
+                    status.status,
+                    timeout
+                ));
+            }
+            sleep(poll_interval).await;
+        }
+    }
+
+    /// Requests statuses, which come wrapped in an RPC context: `{"context": {...}, "value": [...]}`.
+    async fn request_statuses<S: DeserializeOwned>(&self, method: &str, bundle_ids: &[String]) -> AnyResult<Vec<S>> {
+        if bundle_ids.len() > MAX_BUNDLE_IDS_PER_STATUS_REQUEST {
+            return Err(anyhow!(
+                "{} takes at most {} bundle ids, got {}",
+                method,
+                MAX_BUNDLE_IDS_PER_STATUS_REQUEST,
+                bundle_ids.len()
+            ));
+        }
+        let mut result = self.transport.request(method, json!([bundle_ids])).await?;
+        let value = result.get_mut("value").map(Value::take).unwrap_or(Value::Null);
+        Ok(parse::<Option<Vec<S>>>(method, value)?.unwrap_or_default())
+    }
+}
+
+fn parse<R: DeserializeOwned>(method: &str, result: Value) -> AnyResult<R> {
+    serde_json::from_value(result).map_err(|e| anyhow!("Unexpected {} result: {}", method, e))
+}
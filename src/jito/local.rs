@@ -0,0 +1,298 @@
+//! An in-process stand-in for a block engine, for testing bundle flows without the network.
+
+use crate::common::types::AnyResult;
+use crate::jito::{
+    bundle::bundle_id,
+    client::{BundleStatus, InflightBundleStatus, InflightStatus},
+    transport::BundleTransport,
+    JITO_TIP_ACCOUNTS, MAX_BUNDLE_TRANSACTIONS, MIN_TIP_LAMPORTS,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_sdk::{
+    pubkey::Pubkey, system_instruction::SystemInstruction, system_program, transaction::VersionedTransaction,
+};
+use std::{collections::HashMap, sync::Mutex};
+
+/// A bundle received by a `LocalBlockEngine`.
+#[derive(Debug, Clone)]
+pub struct ReceivedBundle {
+    pub bundle_id: String,
+    pub transactions: Vec<VersionedTransaction>,
+    /// The total tip paid to the engine's tip accounts, in lamports.
+    pub tip_lamports: u64,
+    pub status: InflightStatus,
+    pub landed_slot: Option<u64>,
+}
+
+#[derive(Debug)]
+struct EngineState {
+    slot: u64,
+    auto_land: bool,
+    tip_accounts: Vec<Pubkey>,
+    bundles: HashMap<String, ReceivedBundle>,
+    order: Vec<String>,
+}
+
+/// A `BundleTransport` that is itself a (very) simple block engine.
+///
+/// It validates bundles as the real one does (size, signatures, a tip of at least `MIN_TIP_LAMPORTS` to a tip
+/// account) and records them as pending. Tests then decide their fate with `land` and `fail`, or have every
+/// bundle land on receipt with `auto_land`. Nothing is executed, so a landed bundle always succeeds.
+///
+/// Note: Tips are only recognised if the tip account is a static account key, not loaded from a lookup table.
+#[derive(Debug)]
+pub struct LocalBlockEngine {
+    engine: Mutex<EngineState>,
+}
+
+impl Default for LocalBlockEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalBlockEngine {
+    /// Starts an engine at slot 0 with the mainnet tip accounts.
+    pub fn new() -> Self {
+        Self {
+            engine: Mutex::new(EngineState {
+                slot: 0,
+                auto_land: false,
+                tip_accounts: JITO_TIP_ACCOUNTS.to_vec(),
+                bundles: HashMap::new(),
+                order: Vec::new(),
+            }),
+        }
+    }
+
+    /// Lands bundles as soon as they're received, each in its own slot.
+    pub fn auto_land(self, auto_land: bool) -> Self {
+        self.lock().auto_land = auto_land;
+        self
+    }
+
+    /// Replaces the tip accounts.
+    pub fn tip_accounts(self, tip_accounts: Vec<Pubkey>) -> Self {
+        self.lock().tip_accounts = tip_accounts;
+        self
+    }
+
+    /// Returns the current slot, which advances by one for each landed bundle.
+    pub fn slot(&self) -> u64 {
+        self.lock().slot
+    }
+
+    /// Returns the bundles received so far, in order.
+    pub fn received(&self) -> Vec<ReceivedBundle> {
+        let engine = self.lock();
+        engine.order.iter().map(|bundle_id| engine.bundles[bundle_id].clone()).collect()
+    }
+
+    /// Lands the pending bundle `bundle_id` in the next slot, returning that slot.
+    pub fn land(&self, bundle_id: &str) -> AnyResult<u64> {
+        let mut engine = self.lock();
+        let slot = engine.slot + 1;
+        let bundle = pending(&mut engine, bundle_id)?;
+        bundle.status = InflightStatus::Landed;
+        bundle.landed_slot = Some(slot);
+        engine.slot = slot;
+        Ok(slot)
+    }
+
+    /// Fails the pending bundle `bundle_id`, as if it was outbid or its simulation failed.
+    pub fn fail(&self, bundle_id: &str) -> AnyResult<()> {
+        pending(&mut self.lock(), bundle_id)?.status = InflightStatus::Failed;
+        Ok(())
+    }
+
+    /// Lands every pending bundle, in the order they were received, returning their ids.
+    pub fn land_pending(&self) -> Vec<String> {
+        let pending_ids: Vec<String> = {
+            let engine = self.lock();
+            engine
+                .order
+                .iter()
+                .filter(|bundle_id| engine.bundles[*bundle_id].status == InflightStatus::Pending)
+                .cloned()
+                .collect()
+        };
+        for bundle_id in &pending_ids {
+            // Can't fail, the bundles are known and pending.
+            let _ = self.land(bundle_id);
+        }
+        pending_ids
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, EngineState> {
+        // A panic while holding the lock can't leave the engine inconsistent, so carry on.
+        self.engine.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn send_bundle(&self, params: &Value) -> AnyResult<Value> {
+        let encoding = params.get(1).and_then(|config| config.get("encoding")).and_then(Value::as_str);
+        if encoding != Some("base64") {
+            return Err(anyhow!("Only base64 encoded bundles are supported, got {:?}", encoding));
+        }
+        let transactions = params
+            .get(0)
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("sendBundle expects an array of transactions"))?
+            .iter()
+            .map(|encoded| {
+                let bytes = encoded
+                    .as_str()
+                    .map(|encoded| STANDARD.decode(encoded))
+                    .ok_or_else(|| anyhow!("Bundle transactions must be strings"))?
+                    .map_err(|e| anyhow!("Invalid base64 transaction: {}", e))?;
+                bincode::deserialize::<VersionedTransaction>(&bytes)
+                    .map_err(|e| anyhow!("Invalid transaction: {}", e))
+            })
+            .collect::<AnyResult<Vec<_>>>()?;
+        if transactions.is_empty() || transactions.len() > MAX_BUNDLE_TRANSACTIONS {
+            return Err(anyhow!(
+                "A bundle holds 1 to {} transactions, got {}",
+                MAX_BUNDLE_TRANSACTIONS,
+                transactions.len()
+            ));
+        }
+        if let Some(i) = transactions
+            .iter()
+            .position(|transaction| transaction.verify_with_results().contains(&false))
+        {
+            return Err(anyhow!("Transaction {} of the bundle has an invalid signature", i));
+        }
+
+        let mut engine = self.lock();
+        let tip_lamports: u64 = transactions
+            .iter()
+            .map(|transaction| tip_lamports(transaction, &engine.tip_accounts))
+            .sum();
+        if tip_lamports < MIN_TIP_LAMPORTS {
+            return Err(anyhow!(
+                "Bundle tips {} lamports, the minimum is {}",
+                tip_lamports,
+                MIN_TIP_LAMPORTS
+            ));
+        }
+        let bundle_id = bundle_id(&transactions);
+        if engine.bundles.contains_key(&bundle_id) {
+            return Err(anyhow!("Bundle {} was already received", bundle_id));
+        }
+        engine.bundles.insert(
+            bundle_id.clone(),
+            ReceivedBundle {
+                bundle_id: bundle_id.clone(),
+                transactions,
+                tip_lamports,
+                status: InflightStatus::Pending,
+                landed_slot: None,
+            },
+        );
+        engine.order.push(bundle_id.clone());
+        let auto_land = engine.auto_land;
+        drop(engine);
+
+        if auto_land {
+            self.land(&bundle_id)?;
+        }
+        Ok(json!(bundle_id))
+    }
+
+    fn inflight_statuses(&self, params: &Value) -> AnyResult<Value> {
+        let engine = self.lock();
+        let statuses: Vec<InflightBundleStatus> = bundle_ids(params)?
+            .into_iter()
+            .map(|bundle_id| match engine.bundles.get(&bundle_id) {
+                Some(bundle) => InflightBundleStatus {
+                    bundle_id,
+                    status: bundle.status,
+                    landed_slot: bundle.landed_slot,
+                },
+                None => InflightBundleStatus {
+                    bundle_id,
+                    status: InflightStatus::Invalid,
+                    landed_slot: None,
+                },
+            })
+            .collect();
+        Ok(json!({ "context": { "slot": engine.slot }, "value": statuses }))
+    }
+
+    fn bundle_statuses(&self, params: &Value) -> AnyResult<Value> {
+        let engine = self.lock();
+        let statuses: Vec<Option<BundleStatus>> = bundle_ids(params)?
+            .into_iter()
+            .map(|bundle_id| {
+                let bundle = engine.bundles.get(&bundle_id)?;
+                Some(BundleStatus {
+                    bundle_id,
+                    transactions: bundle
+                        .transactions
+                        .iter()
+                        .map(|transaction| transaction.signatures[0].to_string())
+                        .collect(),
+                    slot: bundle.landed_slot?,
+                    confirmation_status: Some("confirmed".to_string()),
+                    err: json!({ "Ok": null }),
+                })
+            })
+            .collect();
+        Ok(json!({ "context": { "slot": engine.slot }, "value": statuses }))
+    }
+}
+
+#[async_trait]
+impl BundleTransport for LocalBlockEngine {
+    async fn request(&self, method: &str, params: Value) -> AnyResult<Value> {
+        match method {
+            "sendBundle" => self.send_bundle(&params),
+            "getInflightBundleStatuses" => self.inflight_statuses(&params),
+            "getBundleStatuses" => self.bundle_statuses(&params),
+            "getTipAccounts" => {
+                let tip_accounts: Vec<String> = self.lock().tip_accounts.iter().map(Pubkey::to_string).collect();
+                Ok(json!(tip_accounts))
+            }
+            _ => Err(anyhow!("Block engine rejected {}: method not found", method)),
+        }
+    }
+}
+
+fn pending<'a>(engine: &'a mut EngineState, bundle_id: &str) -> AnyResult<&'a mut ReceivedBundle> {
+    let bundle = engine
+        .bundles
+        .get_mut(bundle_id)
+        .ok_or_else(|| anyhow!("Unknown bundle {}", bundle_id))?;
+    if bundle.status != InflightStatus::Pending {
+        return Err(anyhow!("Bundle {} is {:?}, not pending", bundle_id, bundle.status));
+    }
+    Ok(bundle)
+}
+
+fn bundle_ids(params: &Value) -> AnyResult<Vec<String>> {
+    params
+        .get(0)
+        .and_then(Value::as_array)
+        .and_then(|ids| ids.iter().map(|id| id.as_str().map(str::to_string)).collect())
+        .ok_or_else(|| anyhow!("Expected an array of bundle ids"))
+}
+
+/// Sums the system transfers of `transaction` to any of `tip_accounts`.
+fn tip_lamports(transaction: &VersionedTransaction, tip_accounts: &[Pubkey]) -> u64 {
+    let account_keys = transaction.message.static_account_keys();
+    transaction
+        .message
+        .instructions()
+        .iter()
+        .filter(|instruction| account_keys.get(instruction.program_id_index as usize) == Some(&system_program::ID))
+        .filter_map(|instruction| {
+            let SystemInstruction::Transfer { lamports } = bincode::deserialize(&instruction.data).ok()? else {
+                return None;
+            };
+            let recipient = account_keys.get(*instruction.accounts.get(1)? as usize)?;
+            tip_accounts.contains(recipient).then_some(lamports)
+        })
+        .sum()
+}
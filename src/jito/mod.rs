@@ -0,0 +1,56 @@
+//! # Jito Block Engine Client
+//!
+//! Bundles are up to `MAX_BUNDLE_TRANSACTIONS` transactions the block engine lands atomically and in order, or
+//! not at all, which is what multi-leg trades need. The block engine only considers bundles that pay a tip to one
+//! of its tip accounts, see `tip_instruction`.
+//!
+//! Bundles are assembled from `TransactionBuilder`s, i.e. from the instructions of the DEX builders, by
+//! `BundleBuilder`, and sent through `JitoClient`. The client speaks the block engine's JSON-RPC API over a
+//! pluggable `BundleTransport`: `HttpTransport` for a real block engine and `LocalBlockEngine`, an in-process
+//! stand-in, for tests.
+//!
+//! Only available with the `jito` feature.
+
+pub mod bundle;
+pub mod client;
+pub mod local;
+pub mod transport;
+
+use solana_sdk::{instruction::Instruction, pubkey, pubkey::Pubkey, system_instruction};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The most transactions a bundle can hold.
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
+/// The smallest tip the block engine accepts, in lamports.
+pub const MIN_TIP_LAMPORTS: u64 = 1_000;
+
+/// The mainnet block engine's tip accounts. Tips are spread across them to avoid write lock contention.
+///
+/// Note: These rarely change, but `JitoClient::get_tip_accounts` returns the current set.
+pub const JITO_TIP_ACCOUNTS: [Pubkey; 8] = [
+    pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
+    pubkey!("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
+    pubkey!("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
+    pubkey!("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49"),
+    pubkey!("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh"),
+    pubkey!("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt"),
+    pubkey!("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL"),
+    pubkey!("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
+];
+
+/// Returns one of `JITO_TIP_ACCOUNTS`, varying from call to call.
+pub fn tip_account() -> Pubkey {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    JITO_TIP_ACCOUNTS[nanos as usize % JITO_TIP_ACCOUNTS.len()]
+}
+
+/// Builds the instruction transferring a tip of `lamports` from `payer` to `tip_account`.
+///
+/// Note: The tip should be in the bundle's last transaction, so it's only paid if everything before it lands.
+pub fn tip_instruction(payer: &Pubkey, tip_account: &Pubkey, lamports: u64) -> Instruction {
+    system_instruction::transfer(payer, tip_account, lamports)
+}
@@ -0,0 +1,86 @@
+//! Defines how `JitoClient` reaches a block engine.
+
+use crate::common::types::AnyResult;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The mainnet block engine's bundle endpoint, which routes to the closest region.
+pub const MAINNET_BUNDLES_URL: &str = "https://mainnet.block-engine.jito.wtf/api/v1/bundles";
+
+/// An abstract interface for a JSON-RPC connection to a block engine.
+///
+/// Implementations only move requests and responses, the block engine's API is implemented by `JitoClient`.
+/// See `HttpTransport` for a real block engine and `LocalBlockEngine` for a stand-in.
+#[async_trait]
+pub trait BundleTransport: Send + Sync {
+    /// Sends a JSON-RPC request, returning its `result`, or an error if the request failed or the response
+    /// carries an `error`.
+    async fn request(&self, method: &str, params: Value) -> AnyResult<Value>;
+}
+
+/// A `BundleTransport` over HTTP.
+#[derive(Debug)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    auth_uuid: Option<String>,
+    next_id: AtomicU64,
+}
+
+impl HttpTransport {
+    /// Connects to the block engine at `url`, e.g. `MAINNET_BUNDLES_URL` or a regional endpoint.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::with_client(reqwest::Client::new(), url)
+    }
+
+    /// As `new`, reusing an existing `reqwest::Client`, e.g. one with custom timeouts.
+    pub fn with_client(client: reqwest::Client, url: impl Into<String>) -> Self {
+        Self {
+            client,
+            url: url.into(),
+            auth_uuid: None,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// The UUID sent in the `x-jito-auth` header, for approved higher rate limits.
+    pub fn auth_uuid(mut self, auth_uuid: impl Into<String>) -> Self {
+        self.auth_uuid = Some(auth_uuid.into());
+        self
+    }
+}
+
+#[async_trait]
+impl BundleTransport for HttpTransport {
+    async fn request(&self, method: &str, params: Value) -> AnyResult<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let body = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(auth_uuid) = &self.auth_uuid {
+            request = request.header("x-jito-auth", auth_uuid);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to send {} to {}: {}", method, self.url, e))?;
+        let status = response.status();
+        let response: Value = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to read {} response ({}) from {}: {}", method, status, self.url, e))?;
+        into_result(method, response)
+    }
+}
+
+/// Extracts the `result` of a JSON-RPC response, or its `error` as an `anyhow::Error`.
+pub(crate) fn into_result(method: &str, mut response: Value) -> AnyResult<Value> {
+    if let Some(error) = response.get("error").filter(|error| !error.is_null()) {
+        return Err(anyhow!("Block engine rejected {}: {}", method, error));
+    }
+    match response.get_mut("result") {
+        Some(result) => Ok(result.take()),
+        None => Err(anyhow!("Block engine response to {} has no result: {}", method, response)),
+    }
+}
@@ -3,6 +3,19 @@
 //! `solana-dex-tools` is a high-performance, thread-safe, generic Rust library designed for developers building sophisticated high-frequency-trading (HFT) 
 //! applications on the Solana blockchain. We provide utilities to use the `solana-sdk` and `tokio` ecosystems, but consumers using specialised toolkits 
 //! are given the freedom to do so with our abstractions. 
+/// Records fields on the current `tracing` span, compiled out without the `tracing` feature.
+macro_rules! trace_record {
+    ($($field:literal = $value:expr),+ $(,)?) => {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            $(span.record($field, $value);)+
+        }
+    };
+}
+
 pub mod common;
 pub mod orca;
-pub mod mint;
\ No newline at end of file
+pub mod mint;
+#[cfg(feature = "jito")]
+pub mod jito;
\ No newline at end of file
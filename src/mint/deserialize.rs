@@ -8,7 +8,12 @@ use spl_token::{
 };
 
 impl Deserializable for Mint {
+    /// Token-2022 mints share the Token program's layout for their first `Mint::LEN` bytes, followed by their 
+    /// extensions, so we only unpack the base mint here. See `mint::extensions` for the rest.
     fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
-        Mint::unpack(bytes).map_err(|e| anyhow!("Failed to deserialize Mint: {}", e))
+        let base_bytes = bytes.get(..Mint::LEN).ok_or_else(|| {
+            anyhow!("Failed to deserialize Mint: expected at least {} bytes, got {}", Mint::LEN, bytes.len())
+        })?;
+        Mint::unpack(base_bytes).map_err(|e| anyhow!("Failed to deserialize Mint: {}", e))
     }
 }
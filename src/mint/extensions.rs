@@ -0,0 +1,87 @@
+//! Reads the Token-2022 extensions of a mint account that affect trading it, i.e. transfer fees and 
+//! transfer hooks.
+//! 
+//! `ManagedAccount<Mint>` only deserializes the base mint, so we parse the extensions from its raw bytes 
+//! on demand instead.
+
+use crate::common::types::AnyResult;
+use anyhow::anyhow;
+use orca_whirlpools_core::TransferFee;
+use solana_sdk::{pubkey, pubkey::Pubkey};
+use spl_token::{solana_program::program_pack::Pack, state::Mint};
+use spl_token_2022::extension::{
+    transfer_fee::TransferFeeConfig, transfer_hook::TransferHook, BaseStateWithExtensions, StateWithExtensions,
+};
+
+/// The Token program's id. We don't take it from `spl_token`, which is built on an older `solana-program`.
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+/// The Token-2022 program's id.
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = spl_token_2022::ID;
+
+/// The trading relevant extensions of a mint account.
+#[derive(Debug, Clone, Copy)]
+pub struct MintExtensions {
+    /// The owner of the mint account, i.e. the Token or Token-2022 program.
+    token_program_id: Pubkey,
+    pub transfer_fee_config: Option<TransferFeeConfig>,
+    /// The program invoked on every transfer of the token, `None` if there's no hook or it's unset.
+    pub transfer_hook_program_id: Option<Pubkey>,
+}
+
+impl Default for MintExtensions {
+    /// A Token program mint, i.e. one without extensions.
+    fn default() -> Self {
+        Self {
+            token_program_id: TOKEN_PROGRAM_ID,
+            transfer_fee_config: None,
+            transfer_hook_program_id: None,
+        }
+    }
+}
+
+impl MintExtensions {
+    /// Parses the extensions of a mint account from its owner and raw data.
+    /// 
+    /// Token program mints have none, nor do Token-2022 mints of exactly `Mint::LEN` bytes. Fails if `owner` 
+    /// is neither token program.
+    pub fn from_account(owner: &Pubkey, bytes: &[u8]) -> AnyResult<Self> {
+        if *owner != TOKEN_PROGRAM_ID && *owner != TOKEN_2022_PROGRAM_ID {
+            return Err(anyhow!("Mint account is owned by {}, which isn't a token program", owner));
+        }
+        if *owner == TOKEN_PROGRAM_ID || bytes.len() <= Mint::LEN {
+            return Ok(Self {
+                token_program_id: *owner,
+                ..Self::default()
+            });
+        }
+        let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(bytes)
+            .map_err(|e| anyhow!("Failed to deserialize Token-2022 Mint extensions: {}", e))?;
+        Ok(Self {
+            token_program_id: *owner,
+            transfer_fee_config: state.get_extension::<TransferFeeConfig>().ok().copied(),
+            transfer_hook_program_id: state
+                .get_extension::<TransferHook>()
+                .ok()
+                .and_then(|hook| Option::<Pubkey>::from(hook.program_id)),
+        })
+    }
+
+    /// Returns the token program owning the mint account.
+    /// 
+    /// This is taken from the account's owner rather than its data, as a Token-2022 mint without extensions 
+    /// is byte-for-byte a Token program mint.
+    pub fn token_program_id(&self) -> Pubkey {
+        self.token_program_id
+    }
+
+    /// Returns the transfer fee in effect at `epoch`, in the orca core crate's representation.
+    pub fn transfer_fee(&self, epoch: u64) -> Option<TransferFee> {
+        self.transfer_fee_config.map(|config| {
+            let fee = config.get_epoch_fee(epoch);
+            TransferFee {
+                fee_bps: u16::from(fee.transfer_fee_basis_points),
+                max_fee: u64::from(fee.maximum_fee),
+            }
+        })
+    }
+}
@@ -1,3 +1,5 @@
 //! This module handles logic pertaining to mint accounts. 
 
 mod deserialize;
+pub mod extensions;
+pub mod pda;
@@ -0,0 +1,18 @@
+//! Handles address derivation for token accounts.
+
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+/// The associated token account program's id.
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Given an owner, a mint and the mint's token program, derives the owner's associated token account for the mint.
+/// 
+/// Note: The token program is part of the seeds, so a Token-2022 mint's ATA differs from what the Token program 
+/// would give, see `MintExtensions::token_program_id`.
+pub fn get_associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
@@ -0,0 +1,178 @@
+//! Defines `OrcaConfigAccounts`, the protocol level accounts a whirlpool references: its `WhirlpoolsConfig`, 
+//! its `FeeTier` (or `AdaptiveFeeTier`) and the `TokenBadge`s of its mints.
+//! 
+//! These change rarely, but they hold the protocol fee rate and the authorities that can change the pool, 
+//! so they're worth watching alongside the pool itself.
+
+use crate::common::{
+    account::AccountData,
+    pool::Pool,
+    rpc::RpcProvider,
+    state::{AccountState, ManagedAccount},
+    types::AnyResult,
+};
+use crate::orca::{
+    pda,
+    pool::{FailedAccount, OrcaWhirlpool},
+};
+use anyhow::anyhow;
+use orca_whirlpools_client::{AdaptiveFeeTier, FeeTier, TokenBadge, WhirlpoolsConfig};
+use orca_whirlpools_core::WhirlpoolFacade;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+/// The logical collection of `ManagedAccount`s holding the protocol configuration of an Orca Whirlpool.
+/// 
+/// The optional accounts are `None` when they don't exist on-chain: static fee pools have a `FeeTier` and 
+/// adaptive fee pools an `AdaptiveFeeTier` (never both), and token badges only exist for some Token-2022 mints.
+pub struct OrcaConfigAccounts {
+    pub whirlpools_config: Arc<ManagedAccount<WhirlpoolsConfig>>,
+    pub fee_tier: Option<Arc<ManagedAccount<FeeTier>>>,
+    pub adaptive_fee_tier: Option<Arc<ManagedAccount<AdaptiveFeeTier>>>,
+    pub token_badge_a: Option<Arc<ManagedAccount<TokenBadge>>>,
+    pub token_badge_b: Option<Arc<ManagedAccount<TokenBadge>>>,
+}
+
+impl OrcaConfigAccounts {
+    /// Gathers `Arc` pointers to all the loaded config accounts as `AccountState` objects.
+    pub fn accounts(&self) -> Vec<Arc<dyn AccountState>> {
+        let mut accounts: Vec<Arc<dyn AccountState>> = vec![self.whirlpools_config.clone()];
+        if let Some(fee_tier) = &self.fee_tier {
+            accounts.push(fee_tier.clone());
+        }
+        if let Some(adaptive_fee_tier) = &self.adaptive_fee_tier {
+            accounts.push(adaptive_fee_tier.clone());
+        }
+        if let Some(token_badge_a) = &self.token_badge_a {
+            accounts.push(token_badge_a.clone());
+        }
+        if let Some(token_badge_b) = &self.token_badge_b {
+            accounts.push(token_badge_b.clone());
+        }
+        accounts
+    }
+
+    /// Returns the protocol's cut of the trade fee that new pools under this config start with, in basis points.
+    /// 
+    /// Note: Each pool has its own `protocol_fee_rate`, which is what actually applies to its trades.
+    pub fn default_protocol_fee_rate(&self) -> u16 {
+        self.whirlpools_config.get().default_protocol_fee_rate
+    }
+
+    /// Returns the authority that can set fee rates on pools under this config.
+    pub fn fee_authority(&self) -> Pubkey {
+        self.whirlpools_config.get().fee_authority
+    }
+
+    /// Returns the authority that can collect the protocol fees of pools under this config.
+    pub fn collect_protocol_fees_authority(&self) -> Pubkey {
+        self.whirlpools_config.get().collect_protocol_fees_authority
+    }
+
+    /// Returns the authority that can set reward emissions on pools under this config.
+    pub fn reward_emissions_super_authority(&self) -> Pubkey {
+        self.whirlpools_config.get().reward_emissions_super_authority
+    }
+}
+
+impl OrcaWhirlpool {
+    /// Asynchronously fetches the pool's config, fee tier and token badge accounts and attaches them to the 
+    /// pool, so they're included in `accounts()` and kept fresh by `refresh`.
+    /// 
+    /// Consumes the pool, as the set of accounts is fixed once the pool is shared. Like the constructor, returns 
+    /// the optional accounts that don't exist on-chain as `FailedAccount`s. Fails if the config itself is missing.
+    pub async fn with_config_accounts<C: RpcProvider + Send + Sync>(
+        mut self,
+        rpc_provider: &C,
+    ) -> AnyResult<(Self, Vec<FailedAccount>)> {
+        let whirlpool_data = self.whirlpool.get();
+        let whirlpools_config_pubkey = whirlpool_data.whirlpools_config;
+        let whirlpool_facade: WhirlpoolFacade = (**whirlpool_data).clone().into();
+        let is_adaptive = whirlpool_facade.is_initialized_with_adaptive_fee();
+        let fee_tier_pubkey = pda::get_fee_tier_address(&whirlpools_config_pubkey, whirlpool_facade.fee_tier_index())?;
+        let token_badge_a_pubkey = pda::get_token_badge_address(&whirlpools_config_pubkey, &whirlpool_data.token_mint_a)?;
+        let token_badge_b_pubkey = pda::get_token_badge_address(&whirlpools_config_pubkey, &whirlpool_data.token_mint_b)?;
+        drop(whirlpool_data);
+
+        let pubkeys_to_fetch = [
+            whirlpools_config_pubkey,
+            fee_tier_pubkey,
+            token_badge_a_pubkey,
+            token_badge_b_pubkey,
+        ];
+        let rpc_response = rpc_provider.get_multiple_accounts(&pubkeys_to_fetch).await?;
+        let accounts_time = rpc_response.response_time;
+        let mut accounts = rpc_response.result.into_iter().map(|a| a.map(|a| a.bytes().to_vec()));
+        let mut next_bytes = || accounts.next().flatten();
+
+        let mut failures = Vec::new();
+        let mut optional_account = |pubkey: Pubkey, bytes: Option<Vec<u8>>, account_type: &str| {
+            if bytes.is_none() {
+                failures.push(FailedAccount {
+                    pubkey,
+                    account_type: account_type.to_string(),
+                });
+            }
+            bytes
+        };
+
+        let whirlpools_config_bytes = next_bytes().ok_or_else(|| {
+            anyhow!("Required account WhirlpoolsConfig {} could not be fetched", whirlpools_config_pubkey)
+        })?;
+        let whirlpools_config = Arc::new(ManagedAccount::<WhirlpoolsConfig>::new_initialized_from_bytes(
+            whirlpools_config_pubkey,
+            whirlpools_config_bytes,
+            accounts_time,
+        )?);
+
+        // The fee tier and adaptive fee tier live at the same PDA, which one it is depends on the pool.
+        let fee_tier_type = if is_adaptive { "AdaptiveFeeTier" } else { "FeeTier" };
+        let fee_tier_bytes = optional_account(fee_tier_pubkey, next_bytes(), fee_tier_type);
+        let (fee_tier, adaptive_fee_tier) = match fee_tier_bytes {
+            Some(bytes) if is_adaptive => (
+                None,
+                Some(Arc::new(ManagedAccount::<AdaptiveFeeTier>::new_initialized_from_bytes(
+                    fee_tier_pubkey,
+                    bytes,
+                    accounts_time,
+                )?)),
+            ),
+            Some(bytes) => (
+                Some(Arc::new(ManagedAccount::<FeeTier>::new_initialized_from_bytes(
+                    fee_tier_pubkey,
+                    bytes,
+                    accounts_time,
+                )?)),
+                None,
+            ),
+            None => (None, None),
+        };
+
+        let token_badge = |pubkey: Pubkey, bytes: Option<Vec<u8>>| -> AnyResult<Option<Arc<ManagedAccount<TokenBadge>>>> {
+            bytes
+                .map(|bytes| {
+                    ManagedAccount::<TokenBadge>::new_initialized_from_bytes(pubkey, bytes, accounts_time).map(Arc::new)
+                })
+                .transpose()
+        };
+        let token_badge_a = token_badge(
+            token_badge_a_pubkey,
+            optional_account(token_badge_a_pubkey, next_bytes(), "TokenBadge"),
+        )?;
+        let token_badge_b = token_badge(
+            token_badge_b_pubkey,
+            optional_account(token_badge_b_pubkey, next_bytes(), "TokenBadge"),
+        )?;
+
+        self.config_accounts = Some(OrcaConfigAccounts {
+            whirlpools_config,
+            fee_tier,
+            adaptive_fee_tier,
+            token_badge_a,
+            token_badge_b,
+        });
+        let slot = Pool::snapshot(&self).slot;
+        self.publish_unlocked(slot)?;
+        Ok((self, failures))
+    }
+}
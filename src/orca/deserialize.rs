@@ -1,10 +1,13 @@
-//! Defines the deserialization of the Orca Whirlpool, TickArray and Oracle accounts.
+//! Defines the deserialization of the Orca Whirlpool, TickArray, Oracle, Position and PositionBundle accounts, 
+//! along with the WhirlpoolsConfig, FeeTier, AdaptiveFeeTier and TokenBadge config accounts.
 //! 
 //! All are done by invoking the from_bytes method from the orca-sdk, not to be confused with our method!
 
 use crate::common::{deserialize::Deserializable, types::AnyResult};
 use anyhow::anyhow;
-use orca_whirlpools_client::{Oracle, TickArray, Whirlpool};
+use orca_whirlpools_client::{
+    AdaptiveFeeTier, FeeTier, Oracle, Position, PositionBundle, TickArray, TokenBadge, Whirlpool, WhirlpoolsConfig,
+};
 
 impl Deserializable for Whirlpool {
     fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
@@ -23,3 +26,39 @@ impl Deserializable for Oracle {
         Oracle::from_bytes(bytes).map_err(|e| anyhow!("Failed to deserialize Oracle: {}", e))
     }
 }
+
+impl Deserializable for Position {
+    fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+        Position::from_bytes(bytes).map_err(|e| anyhow!("Failed to deserialize Position: {}", e))
+    }
+}
+
+impl Deserializable for PositionBundle {
+    fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+        PositionBundle::from_bytes(bytes).map_err(|e| anyhow!("Failed to deserialize PositionBundle: {}", e))
+    }
+}
+
+impl Deserializable for WhirlpoolsConfig {
+    fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+        WhirlpoolsConfig::from_bytes(bytes).map_err(|e| anyhow!("Failed to deserialize WhirlpoolsConfig: {}", e))
+    }
+}
+
+impl Deserializable for FeeTier {
+    fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+        FeeTier::from_bytes(bytes).map_err(|e| anyhow!("Failed to deserialize FeeTier: {}", e))
+    }
+}
+
+impl Deserializable for AdaptiveFeeTier {
+    fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+        AdaptiveFeeTier::from_bytes(bytes).map_err(|e| anyhow!("Failed to deserialize AdaptiveFeeTier: {}", e))
+    }
+}
+
+impl Deserializable for TokenBadge {
+    fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+        TokenBadge::from_bytes(bytes).map_err(|e| anyhow!("Failed to deserialize TokenBadge: {}", e))
+    }
+}
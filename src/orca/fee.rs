@@ -0,0 +1,332 @@
+//! Computes the effective fee rate of a swap on an Orca Whirlpool, including the adaptive (dynamic) fee
+//! charged by variable fee pools.
+//!
+//! The adaptive fee is driven by the `Oracle` account of the pool: the swap walks across "tick groups"
+//! (blocks of `tick_group_size` ticks), and the volatility accumulator grows with the distance from the
+//! reference tick group, pushing the fee up as the price moves. The references decay between trades,
+//! see `AdaptiveFeeVariablesFacade::update_reference` in the orca core crate.
+//!
+//! Note: The orca core crate has exactly this logic, but its `FeeRateManager` is crate private, so we
+//! port it here. It must stay in step with the on-chain program, so don't get creative with it!
+
+use crate::common::types::AnyResult;
+use crate::orca::{pool::OrcaWhirlpool, state::OrcaWhirlpoolState};
+use anyhow::anyhow;
+use num_integer::Integer;
+use orca_whirlpools_core::{
+    sqrt_price_to_tick_index, tick_index_to_sqrt_price, AdaptiveFeeConstantsFacade, AdaptiveFeeInfo,
+    AdaptiveFeeVariablesFacade, ADAPTIVE_FEE_CONTROL_FACTOR_DENOMINATOR, FEE_RATE_HARD_LIMIT,
+    MAX_TICK_INDEX, MIN_TICK_INDEX, VOLATILITY_ACCUMULATOR_SCALE_FACTOR,
+};
+
+/// Tracks the fee rate over the course of a single swap.
+///
+/// Static fee pools just charge `fee_rate` from the `Whirlpool` account, adaptive fee pools add the
+/// adaptive fee on top, which is re-evaluated on every tick group the swap crosses.
+#[derive(Debug, Clone)]
+pub enum FeeRateManager {
+    Adaptive {
+        a_to_b: bool,
+        tick_group_index: i32,
+        static_fee_rate: u16,
+        adaptive_fee_constants: AdaptiveFeeConstantsFacade,
+        adaptive_fee_variables: AdaptiveFeeVariablesFacade,
+        /// The (tick group index, sqrt price) at the lower edge of the range where the accumulator isn't pinned at its max.
+        core_tick_group_range_lower_bound: Option<(i32, u128)>,
+        /// The (tick group index, sqrt price) at the upper edge of the range where the accumulator isn't pinned at its max.
+        core_tick_group_range_upper_bound: Option<(i32, u128)>,
+    },
+    Static {
+        static_fee_rate: u16,
+    },
+}
+
+impl FeeRateManager {
+    /// Constructs the fee rate manager for a swap starting at `current_tick_index` at unix timestamp `timestamp` (seconds).
+    ///
+    /// Passing `adaptive_fee_info` selects the adaptive variant, in which case the volatility references
+    /// are updated for the elapsed time, exactly as the program does at the start of a swap.
+    pub fn new(
+        a_to_b: bool,
+        current_tick_index: i32,
+        timestamp: u64,
+        static_fee_rate: u16,
+        adaptive_fee_info: &Option<AdaptiveFeeInfo>,
+    ) -> AnyResult<Self> {
+        let adaptive_fee_info = match adaptive_fee_info {
+            None => return Ok(Self::Static { static_fee_rate }),
+            Some(adaptive_fee_info) => adaptive_fee_info,
+        };
+        let adaptive_fee_constants = adaptive_fee_info.constants;
+        let mut adaptive_fee_variables = adaptive_fee_info.variables;
+        let tick_group_size = adaptive_fee_constants.tick_group_size as i32;
+        let tick_group_index = Integer::div_floor(&current_tick_index, &tick_group_size);
+
+        adaptive_fee_variables
+            .update_reference(tick_group_index, timestamp, &adaptive_fee_constants)
+            .map_err(|e| anyhow!("Failed to update adaptive fee reference: {}", e))?;
+
+        // Once the swap is this many tick groups from the reference, the accumulator is pinned at its max,
+        // so the fee no longer changes and the step-by-step calculation can be skipped.
+        let max_volatility_accumulator_tick_group_index_delta = Integer::div_ceil(
+            &(adaptive_fee_constants.max_volatility_accumulator - adaptive_fee_variables.volatility_reference),
+            &(VOLATILITY_ACCUMULATOR_SCALE_FACTOR as u32),
+        ) as i32;
+
+        let core_tick_group_range_lower_index =
+            adaptive_fee_variables.tick_group_index_reference - max_volatility_accumulator_tick_group_index_delta;
+        let core_tick_group_range_upper_index =
+            adaptive_fee_variables.tick_group_index_reference + max_volatility_accumulator_tick_group_index_delta;
+        let core_tick_group_range_lower_bound_tick_index = core_tick_group_range_lower_index * tick_group_size;
+        let core_tick_group_range_upper_bound_tick_index =
+            core_tick_group_range_upper_index * tick_group_size + tick_group_size;
+
+        let core_tick_group_range_lower_bound = if core_tick_group_range_lower_bound_tick_index > MIN_TICK_INDEX {
+            Some((
+                core_tick_group_range_lower_index,
+                tick_index_to_sqrt_price(core_tick_group_range_lower_bound_tick_index),
+            ))
+        } else {
+            None
+        };
+        let core_tick_group_range_upper_bound = if core_tick_group_range_upper_bound_tick_index < MAX_TICK_INDEX {
+            Some((
+                core_tick_group_range_upper_index,
+                tick_index_to_sqrt_price(core_tick_group_range_upper_bound_tick_index),
+            ))
+        } else {
+            None
+        };
+
+        Ok(Self::Adaptive {
+            a_to_b,
+            tick_group_index,
+            static_fee_rate,
+            adaptive_fee_constants,
+            adaptive_fee_variables,
+            core_tick_group_range_lower_bound,
+            core_tick_group_range_upper_bound,
+        })
+    }
+
+    /// Re-evaluates the volatility accumulator for the current tick group, called at the top of each swap step.
+    pub fn update_volatility_accumulator(&mut self) {
+        if let Self::Adaptive {
+            tick_group_index,
+            adaptive_fee_constants,
+            adaptive_fee_variables,
+            ..
+        } = self
+        {
+            adaptive_fee_variables.update_volatility_accumulator(*tick_group_index, adaptive_fee_constants);
+        }
+    }
+
+    /// Records the timestamp if the swap moved the price by more than `major_swap_threshold_ticks`, called once the swap is done.
+    pub fn update_major_swap_timestamp(&mut self, timestamp: u64, pre_sqrt_price: u128, post_sqrt_price: u128) {
+        if let Self::Adaptive {
+            adaptive_fee_constants,
+            adaptive_fee_variables,
+            ..
+        } = self
+        {
+            adaptive_fee_variables.update_major_swap_timestamp(
+                pre_sqrt_price,
+                post_sqrt_price,
+                timestamp,
+                adaptive_fee_constants,
+            );
+        }
+    }
+
+    /// Moves on to the next tick group in the swap direction, called after a step when skip was NOT used.
+    pub fn advance_tick_group(&mut self) {
+        if let Self::Adaptive {
+            a_to_b,
+            tick_group_index,
+            ..
+        } = self
+        {
+            *tick_group_index += if *a_to_b { -1 } else { 1 };
+        }
+    }
+
+    /// Works out which tick group a skipped step landed in, called after a step when skip was used.
+    ///
+    /// Static fee managers never skip, so this is a no-op for them.
+    pub fn advance_tick_group_after_skip(&mut self, sqrt_price: u128, next_tick_sqrt_price: u128, next_tick_index: i32) {
+        if let Self::Adaptive {
+            a_to_b,
+            tick_group_index,
+            adaptive_fee_constants,
+            adaptive_fee_variables,
+            ..
+        } = self
+        {
+            let tick_group_size = adaptive_fee_constants.tick_group_size as i32;
+            let (tick_index, is_on_tick_group_boundary) = if sqrt_price == next_tick_sqrt_price {
+                (next_tick_index, next_tick_index % tick_group_size == 0)
+            } else {
+                // End of the swap loop or the boundary of the core tick group range, recompute to be safe.
+                let tick_index = sqrt_price_to_tick_index(sqrt_price);
+                let is_on_tick_group_boundary =
+                    tick_index % tick_group_size == 0 && sqrt_price == tick_index_to_sqrt_price(tick_index);
+                (tick_index, is_on_tick_group_boundary)
+            };
+
+            let last_traversed_tick_group_index = if is_on_tick_group_boundary && !*a_to_b {
+                tick_index / tick_group_size - 1
+            } else {
+                Integer::div_floor(&tick_index, &tick_group_size)
+            };
+
+            // `<` rather than `!=` so a step that collected everything as fees can't move us backwards.
+            if (*a_to_b && last_traversed_tick_group_index < *tick_group_index)
+                || (!*a_to_b && last_traversed_tick_group_index > *tick_group_index)
+            {
+                *tick_group_index = last_traversed_tick_group_index;
+                adaptive_fee_variables.update_volatility_accumulator(*tick_group_index, adaptive_fee_constants);
+            }
+
+            *tick_group_index += if *a_to_b { -1 } else { 1 };
+        }
+    }
+
+    /// Returns the fee rate (in hundredths of a basis point, i.e. over `FEE_RATE_DENOMINATOR`) for the current step.
+    pub fn get_total_fee_rate(&self) -> u32 {
+        match self {
+            Self::Static { static_fee_rate } => *static_fee_rate as u32,
+            Self::Adaptive {
+                static_fee_rate,
+                adaptive_fee_constants,
+                adaptive_fee_variables,
+                ..
+            } => {
+                let total_fee_rate = *static_fee_rate as u32
+                    + compute_adaptive_fee_rate(adaptive_fee_constants, adaptive_fee_variables);
+                total_fee_rate.min(FEE_RATE_HARD_LIMIT)
+            }
+        }
+    }
+
+    /// Bounds the step's target sqrt price to the edge of the current tick group.
+    ///
+    /// Returns `(bounded_sqrt_price, skip)`, skip being true when stepping tick group by tick group would be
+    /// pointless (no adaptive fee, no liquidity, or outside the core range), in which case
+    /// `advance_tick_group_after_skip` must be called instead of `advance_tick_group`.
+    pub fn get_bounded_sqrt_price_target(&self, sqrt_price: u128, curr_liquidity: u128) -> (u128, bool) {
+        match self {
+            Self::Static { .. } => (sqrt_price, false),
+            Self::Adaptive {
+                a_to_b,
+                tick_group_index,
+                adaptive_fee_constants,
+                core_tick_group_range_lower_bound,
+                core_tick_group_range_upper_bound,
+                ..
+            } => {
+                if adaptive_fee_constants.adaptive_fee_control_factor == 0 || curr_liquidity == 0 {
+                    return (sqrt_price, true);
+                }
+
+                if let Some((lower_tick_group_index, lower_tick_group_bound_sqrt_price)) = core_tick_group_range_lower_bound {
+                    if tick_group_index < lower_tick_group_index {
+                        return if *a_to_b {
+                            (sqrt_price, true)
+                        } else {
+                            (sqrt_price.min(*lower_tick_group_bound_sqrt_price), true)
+                        };
+                    }
+                }
+
+                if let Some((upper_tick_group_index, upper_tick_group_bound_sqrt_price)) = core_tick_group_range_upper_bound {
+                    if tick_group_index > upper_tick_group_index {
+                        return if *a_to_b {
+                            (sqrt_price.max(*upper_tick_group_bound_sqrt_price), true)
+                        } else {
+                            (sqrt_price, true)
+                        };
+                    }
+                }
+
+                let tick_group_size = adaptive_fee_constants.tick_group_size as i32;
+                let boundary_tick_index = if *a_to_b {
+                    *tick_group_index * tick_group_size
+                } else {
+                    *tick_group_index * tick_group_size + tick_group_size
+                };
+                let boundary_sqrt_price =
+                    tick_index_to_sqrt_price(boundary_tick_index.clamp(MIN_TICK_INDEX, MAX_TICK_INDEX));
+
+                if *a_to_b {
+                    (sqrt_price.max(boundary_sqrt_price), false)
+                } else {
+                    (sqrt_price.min(boundary_sqrt_price), false)
+                }
+            }
+        }
+    }
+
+    /// Returns the adaptive fee constants and (updated) variables, `None` for static fee pools.
+    ///
+    /// After a swap, these are the values the program would write back to the `Oracle` account.
+    pub fn adaptive_fee_info(&self) -> Option<AdaptiveFeeInfo> {
+        match self {
+            Self::Static { .. } => None,
+            Self::Adaptive {
+                adaptive_fee_constants,
+                adaptive_fee_variables,
+                ..
+            } => Some(AdaptiveFeeInfo {
+                constants: *adaptive_fee_constants,
+                variables: *adaptive_fee_variables,
+            }),
+        }
+    }
+}
+
+/// Computes the adaptive part of the fee rate from the volatility accumulator, i.e.
+///     - ceil(control_factor * (volatility_accumulator * tick_group_size)^2 / (control_denominator * scale_factor^2))
+/// capped at `FEE_RATE_HARD_LIMIT`.
+pub fn compute_adaptive_fee_rate(
+    adaptive_fee_constants: &AdaptiveFeeConstantsFacade,
+    adaptive_fee_variables: &AdaptiveFeeVariablesFacade,
+) -> u32 {
+    let crossed = adaptive_fee_variables.volatility_accumulator as u64 * adaptive_fee_constants.tick_group_size as u64;
+    let squared = crossed as u128 * crossed as u128;
+    let fee_rate = Integer::div_ceil(
+        &(adaptive_fee_constants.adaptive_fee_control_factor as u128 * squared),
+        &(ADAPTIVE_FEE_CONTROL_FACTOR_DENOMINATOR as u128
+            * VOLATILITY_ACCUMULATOR_SCALE_FACTOR as u128
+            * VOLATILITY_ACCUMULATOR_SCALE_FACTOR as u128),
+    );
+    fee_rate.min(FEE_RATE_HARD_LIMIT as u128) as u32
+}
+
+impl OrcaWhirlpoolState {
+    /// Returns the fee rate (over `FEE_RATE_DENOMINATOR`, i.e. in hundredths of a basis point) a swap would
+    /// pay on its first step at unix timestamp `timestamp` (seconds).
+    ///
+    /// For static fee pools this is just the whirlpool's `fee_rate`. For adaptive fee pools, the oracle's
+    /// volatility references are decayed to `timestamp` and the adaptive fee for the current tick group is
+    /// added on top. Larger swaps pay more as they cross tick groups, see `ExactInSwapQuote::trade_fee_rate_max`.
+    pub fn current_fee_rate(&self, timestamp: u64) -> AnyResult<u32> {
+        self.check_oracle_loaded()?;
+        let mut fee_rate_manager = FeeRateManager::new(
+            true,
+            self.whirlpool.tick_current_index,
+            timestamp,
+            self.whirlpool.fee_rate,
+            &self.adaptive_fee_info(),
+        )?;
+        fee_rate_manager.update_volatility_accumulator();
+        Ok(fee_rate_manager.get_total_fee_rate())
+    }
+}
+
+impl OrcaWhirlpool {
+    /// Returns the fee rate a swap would currently pay on this pool, see `OrcaWhirlpoolState::current_fee_rate`.
+    pub fn current_fee_rate(&self, timestamp: u64) -> AnyResult<u32> {
+        self.state().current_fee_rate(timestamp)
+    }
+}
@@ -0,0 +1,751 @@
+//! Builds the Orca liquidity management instructions: open_position, increase_liquidity_v2, decrease_liquidity_v2,
+//! collect_fees_v2, collect_reward_v2 and close_position.
+//!
+//! All of these share a position authority, its token accounts and the pool's token programs, so rather than a
+//! builder per instruction we have a single `OrcaLiquidityBuilder` holding that context, with a method per
+//! operation. Token amounts are quoted from the pool's cached sqrt price, and adding liquidity prepends an
+//! initialize_tick_array instruction for each missing tick array at the ends of the range. Tick arrays the pool
+//! can't vouch for (see `OrcaWhirlpool::tick_array_initialized`) are probed over RPC.
+
+use crate::common::{deserialize::Deserializable, rpc::RpcProvider, types::AnyResult};
+use crate::mint::{
+    extensions::{MintExtensions, TOKEN_PROGRAM_ID},
+    pda::{get_associated_token_address, ASSOCIATED_TOKEN_PROGRAM_ID},
+};
+use crate::orca::{
+    pda,
+    pool::{tick_array_start_index, OrcaWhirlpool},
+    position::OrcaPosition,
+    state::OrcaWhirlpoolState,
+    swap::{transfer_hook_remaining_accounts, MEMO_PROGRAM_ID},
+};
+use anyhow::anyhow;
+use orca_whirlpools_client::{
+    AccountsType, ClosePosition, CollectFeesV2, CollectFeesV2InstructionArgs, CollectRewardV2,
+    CollectRewardV2InstructionArgs, DecreaseLiquidityV2, DecreaseLiquidityV2InstructionArgs, IncreaseLiquidityV2,
+    IncreaseLiquidityV2InstructionArgs, InitializeTickArray, InitializeTickArrayInstructionArgs, OpenPosition,
+    OpenPositionInstructionArgs, Position, RemainingAccountsInfo, TickArray, UpdateFeesAndRewards,
+};
+use orca_whirlpools_core::{
+    decrease_liquidity_quote, decrease_liquidity_quote_a, decrease_liquidity_quote_b, increase_liquidity_quote,
+    increase_liquidity_quote_a, increase_liquidity_quote_b, is_tick_index_in_bounds, is_tick_initializable,
+    CollectFeesQuote, CollectRewardQuote, DecreaseLiquidityQuote, IncreaseLiquidityQuote, TransferFee, NUM_REWARDS,
+};
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How much liquidity to add, either directly or as the amount of one token to deposit.
+#[derive(Debug, Clone, Copy)]
+pub enum IncreaseLiquidityParam {
+    Liquidity(u128),
+    TokenA(u64),
+    TokenB(u64),
+}
+
+/// How much liquidity to remove, either directly or as the amount of one token to withdraw.
+#[derive(Debug, Clone, Copy)]
+pub enum DecreaseLiquidityParam {
+    Liquidity(u128),
+    TokenA(u64),
+    TokenB(u64),
+}
+
+/// Built liquidity management instructions, in execution order, along with the quote they were built from.
+#[derive(Debug, Clone)]
+pub struct OrcaLiquidityInstructions<Q> {
+    pub instructions: Vec<Instruction>,
+    pub quote: Q,
+}
+
+/// Builder for the liquidity management instructions of positions in a single whirlpool.
+///
+/// Required: the position authority, given on construction. The token accounts default to the authority's
+/// associated token accounts, see the setters for the rest.
+#[derive(Clone)]
+pub struct OrcaLiquidityBuilder<'a> {
+    pool: &'a OrcaWhirlpool,
+    state: OrcaWhirlpoolState,
+    position_authority: Pubkey,
+    funder: Option<Pubkey>,
+    token_owner_account_a: Option<Pubkey>,
+    token_owner_account_b: Option<Pubkey>,
+    slippage_tolerance_bps: u16,
+    epoch: Option<u64>,
+    token_program_a: Option<Pubkey>,
+    token_program_b: Option<Pubkey>,
+    position_token_program: Pubkey,
+    transfer_hook_accounts_a: Option<Vec<AccountMeta>>,
+    transfer_hook_accounts_b: Option<Vec<AccountMeta>>,
+}
+
+impl OrcaWhirlpool {
+    /// Starts building liquidity management instructions for `position_authority`'s positions in the pool.
+    pub fn liquidity_builder(&self, position_authority: Pubkey) -> OrcaLiquidityBuilder<'_> {
+        OrcaLiquidityBuilder::new(self, position_authority)
+    }
+}
+
+impl<'a> OrcaLiquidityBuilder<'a> {
+    /// Starts building liquidity management instructions for `position_authority`'s positions in `pool`, against
+    /// the pool's current state.
+    pub fn new(pool: &'a OrcaWhirlpool, position_authority: Pubkey) -> Self {
+        Self {
+            pool,
+            state: pool.state(),
+            position_authority,
+            funder: None,
+            token_owner_account_a: None,
+            token_owner_account_b: None,
+            slippage_tolerance_bps: 0,
+            epoch: None,
+            token_program_a: None,
+            token_program_b: None,
+            position_token_program: TOKEN_PROGRAM_ID,
+            transfer_hook_accounts_a: None,
+            transfer_hook_accounts_b: None,
+        }
+    }
+
+    /// The account paying rent for new accounts (positions and tick arrays). Defaults to the position authority.
+    pub fn funder(mut self, funder: Pubkey) -> Self {
+        self.funder = Some(funder);
+        self
+    }
+
+    /// The position authority's token accounts for tokens A and B. Defaults to its associated token accounts.
+    pub fn token_owner_accounts(mut self, token_owner_account_a: Pubkey, token_owner_account_b: Pubkey) -> Self {
+        self.token_owner_account_a = Some(token_owner_account_a);
+        self.token_owner_account_b = Some(token_owner_account_b);
+        self
+    }
+
+    /// The slippage tolerance in basis points, which bounds the token amounts deposited or withdrawn. Defaults to 0.
+    pub fn slippage_tolerance_bps(mut self, slippage_tolerance_bps: u16) -> Self {
+        self.slippage_tolerance_bps = slippage_tolerance_bps;
+        self
+    }
+
+    /// The epoch the Token-2022 transfer fees are quoted at. Only required if a mint has a transfer fee.
+    pub fn epoch(mut self, epoch: u64) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Overrides the token programs of tokens A and B, see `MintExtensions::token_program_id` for the default.
+    pub fn token_programs(mut self, token_program_a: Pubkey, token_program_b: Pubkey) -> Self {
+        self.token_program_a = Some(token_program_a);
+        self.token_program_b = Some(token_program_b);
+        self
+    }
+
+    /// The token program of existing positions' mints, used to derive the position token accounts. Defaults to
+    /// the Token program, positions opened with token extensions need Token-2022 here.
+    pub fn position_token_program(mut self, position_token_program: Pubkey) -> Self {
+        self.position_token_program = position_token_program;
+        self
+    }
+
+    /// The accounts token A's transfer hook needs, see `OrcaSwapBuilder::transfer_hook_accounts_a`.
+    pub fn transfer_hook_accounts_a(mut self, accounts: Vec<AccountMeta>) -> Self {
+        self.transfer_hook_accounts_a = Some(accounts);
+        self
+    }
+
+    /// The accounts token B's transfer hook needs, see `OrcaSwapBuilder::transfer_hook_accounts_a`.
+    pub fn transfer_hook_accounts_b(mut self, accounts: Vec<AccountMeta>) -> Self {
+        self.transfer_hook_accounts_b = Some(accounts);
+        self
+    }
+
+    // --- Operations --- //
+
+    /// Builds an open_position instruction for a new position over `[tick_lower_index, tick_upper_index]`, owned
+    /// through `position_mint`, followed by an increase_liquidity_v2 instruction if `liquidity` is given.
+    ///
+    /// `position_mint` must be a fresh keypair, which signs the transaction alongside the funder. `rpc_client` is
+    /// only called to probe tick arrays the pool doesn't know about, see `increase_liquidity`.
+    pub async fn open_position(
+        &self,
+        position_mint: Pubkey,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        liquidity: Option<IncreaseLiquidityParam>,
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+    ) -> AnyResult<OrcaLiquidityInstructions<Option<IncreaseLiquidityQuote>>> {
+        self.check_tick_range(tick_lower_index, tick_upper_index)?;
+        let (position, position_bump) = pda::get_position_address(&position_mint)?;
+        let position_token_account =
+            get_associated_token_address(&self.position_authority, &position_mint, &TOKEN_PROGRAM_ID);
+        let open_position = OpenPosition {
+            funder: self.resolved_funder(),
+            owner: self.position_authority,
+            position,
+            position_mint,
+            position_token_account,
+            whirlpool: self.state.pubkey,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+        }
+        .instruction(OpenPositionInstructionArgs {
+            position_bump,
+            tick_lower_index,
+            tick_upper_index,
+        });
+
+        let Some(liquidity) = liquidity else {
+            return Ok(OrcaLiquidityInstructions {
+                instructions: vec![open_position],
+                quote: None,
+            });
+        };
+        let increase = self
+            .increase(position, position_token_account, tick_lower_index, tick_upper_index, liquidity, rpc_client)
+            .await?;
+        // Opening a position doesn't touch the tick arrays, they only need initializing before liquidity is added.
+        let mut instructions = vec![open_position];
+        instructions.extend(increase.instructions);
+        Ok(OrcaLiquidityInstructions {
+            instructions,
+            quote: Some(increase.quote),
+        })
+    }
+
+    /// Builds an increase_liquidity_v2 instruction adding `liquidity` to `position`, preceded by initialize_tick_array
+    /// instructions for any missing tick arrays at the ends of its range.
+    ///
+    /// Tick arrays the pool can't vouch for, e.g. those outside the window in `TickArrayMode::Windowed`, are probed
+    /// with a single `get_multiple_accounts` call to `rpc_client`.
+    pub async fn increase_liquidity(
+        &self,
+        position: &OrcaPosition,
+        liquidity: IncreaseLiquidityParam,
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+    ) -> AnyResult<OrcaLiquidityInstructions<IncreaseLiquidityQuote>> {
+        let position_data = self.position_data(position)?;
+        self.increase(
+            *position.pubkey(),
+            self.position_token_account(&position_data),
+            position_data.tick_lower_index,
+            position_data.tick_upper_index,
+            liquidity,
+            rpc_client,
+        )
+        .await
+    }
+
+    /// Builds a decrease_liquidity_v2 instruction removing `liquidity` from `position`.
+    pub fn decrease_liquidity(
+        &self,
+        position: &OrcaPosition,
+        liquidity: DecreaseLiquidityParam,
+    ) -> AnyResult<OrcaLiquidityInstructions<DecreaseLiquidityQuote>> {
+        let position_data = self.position_data(position)?;
+        let (tick_lower_index, tick_upper_index) = (position_data.tick_lower_index, position_data.tick_upper_index);
+        let (transfer_fee_a, transfer_fee_b) = self.transfer_fees()?;
+        let sqrt_price = self.state.whirlpool.sqrt_price;
+        let slippage = self.slippage_tolerance_bps;
+        let quote = match liquidity {
+            DecreaseLiquidityParam::Liquidity(liquidity) => decrease_liquidity_quote(
+                liquidity, slippage, sqrt_price, tick_lower_index, tick_upper_index, transfer_fee_a, transfer_fee_b,
+            ),
+            DecreaseLiquidityParam::TokenA(amount) => decrease_liquidity_quote_a(
+                amount, slippage, sqrt_price, tick_lower_index, tick_upper_index, transfer_fee_a, transfer_fee_b,
+            ),
+            DecreaseLiquidityParam::TokenB(amount) => decrease_liquidity_quote_b(
+                amount, slippage, sqrt_price, tick_lower_index, tick_upper_index, transfer_fee_a, transfer_fee_b,
+            ),
+        }
+        .map_err(|e| anyhow!("Failed to quote decreasing liquidity of position {}: {}", position.pubkey(), e))?;
+        if quote.liquidity_delta > position_data.liquidity {
+            return Err(anyhow!(
+                "Position {} only has {} liquidity, can't remove {}",
+                position.pubkey(),
+                position_data.liquidity,
+                quote.liquidity_delta
+            ));
+        }
+
+        let (token_program_a, token_program_b) = self.resolved_token_programs()?;
+        let (token_owner_account_a, token_owner_account_b) = self.resolved_token_owner_accounts()?;
+        let (remaining_accounts_info, remaining_accounts) = self.transfer_hook_remaining_accounts()?;
+        let instruction = DecreaseLiquidityV2 {
+            whirlpool: self.state.pubkey,
+            token_program_a,
+            token_program_b,
+            memo_program: MEMO_PROGRAM_ID,
+            position_authority: self.position_authority,
+            position: *position.pubkey(),
+            position_token_account: self.position_token_account(&position_data),
+            token_mint_a: self.state.whirlpool.token_mint_a,
+            token_mint_b: self.state.whirlpool.token_mint_b,
+            token_owner_account_a,
+            token_owner_account_b,
+            token_vault_a: self.state.whirlpool.token_vault_a,
+            token_vault_b: self.state.whirlpool.token_vault_b,
+            tick_array_lower: self.tick_array_address(tick_lower_index)?,
+            tick_array_upper: self.tick_array_address(tick_upper_index)?,
+        }
+        .instruction_with_remaining_accounts(
+            DecreaseLiquidityV2InstructionArgs {
+                liquidity_amount: quote.liquidity_delta,
+                token_min_a: quote.token_min_a,
+                token_min_b: quote.token_min_b,
+                remaining_accounts_info,
+            },
+            &remaining_accounts,
+        );
+        Ok(OrcaLiquidityInstructions {
+            instructions: vec![instruction],
+            quote,
+        })
+    }
+
+    /// Builds a collect_fees_v2 instruction for `position`, preceded by an update_fees_and_rewards instruction if
+    /// the position has liquidity, so fees accrued since its last update are collected too.
+    ///
+    /// The quote is from `OrcaPosition::fees_owed`, which needs the tick arrays at both ends of the range. Those the
+    /// pool doesn't track are fetched from `rpc_client`.
+    pub async fn collect_fees(
+        &self,
+        position: &OrcaPosition,
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+    ) -> AnyResult<OrcaLiquidityInstructions<CollectFeesQuote>> {
+        let position_data = self.position_data(position)?;
+        let (state, _) = self.collect_state(&position_data, &[], rpc_client).await?;
+        let quote = position.fees_owed(&state)?;
+        let mut instructions = self.update_fees_and_rewards(position, &position_data)?;
+
+        let (token_program_a, token_program_b) = self.resolved_token_programs()?;
+        let (token_owner_account_a, token_owner_account_b) = self.resolved_token_owner_accounts()?;
+        let (remaining_accounts_info, remaining_accounts) = self.transfer_hook_remaining_accounts()?;
+        instructions.push(
+            CollectFeesV2 {
+                whirlpool: self.state.pubkey,
+                position_authority: self.position_authority,
+                position: *position.pubkey(),
+                position_token_account: self.position_token_account(&position_data),
+                token_mint_a: self.state.whirlpool.token_mint_a,
+                token_mint_b: self.state.whirlpool.token_mint_b,
+                token_owner_account_a,
+                token_vault_a: self.state.whirlpool.token_vault_a,
+                token_owner_account_b,
+                token_vault_b: self.state.whirlpool.token_vault_b,
+                token_program_a,
+                token_program_b,
+                memo_program: MEMO_PROGRAM_ID,
+            }
+            .instruction_with_remaining_accounts(
+                CollectFeesV2InstructionArgs { remaining_accounts_info },
+                &remaining_accounts,
+            ),
+        );
+        Ok(OrcaLiquidityInstructions {
+            instructions,
+            quote,
+        })
+    }
+
+    /// Builds a collect_reward_v2 instruction for reward `reward_index` of `position`, preceded by an
+    /// update_fees_and_rewards instruction if the position has liquidity.
+    ///
+    /// The reward mint isn't tracked by the pool, so it's fetched from `rpc_client` for its token program and
+    /// extensions, along with any untracked tick arrays at the ends of the range. Transfer hook accounts must be
+    /// given if the mint has a hook. The reward is paid to the authority's associated token account.
+    /// The quote is from `OrcaPosition::rewards_owed` at the system clock's time.
+    pub async fn collect_reward(
+        &self,
+        position: &OrcaPosition,
+        reward_index: u8,
+        transfer_hook_accounts: Option<Vec<AccountMeta>>,
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+    ) -> AnyResult<OrcaLiquidityInstructions<CollectRewardQuote>> {
+        let position_data = self.position_data(position)?;
+        let reward_info = self
+            .state
+            .whirlpool
+            .reward_infos
+            .get(reward_index as usize)
+            .filter(|reward_info| reward_info.mint != Pubkey::default())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Whirlpool {} has no reward at index {} (of {})",
+                    self.state.pubkey,
+                    reward_index,
+                    NUM_REWARDS
+                )
+            })?;
+        let (state, accounts) = self.collect_state(&position_data, &[reward_info.mint], rpc_client).await?;
+        let reward_mint = accounts[0]
+            .as_ref()
+            .ok_or_else(|| anyhow!("Reward mint {} of whirlpool {} not found", reward_info.mint, self.state.pubkey))?;
+        let reward_extensions = MintExtensions::from_account(&reward_mint.owner, &reward_mint.data)?;
+        let reward_token_program = reward_extensions.token_program_id();
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let quote = position.rewards_owed(&state, timestamp)?.rewards[reward_index as usize];
+        let mut instructions = self.update_fees_and_rewards(position, &position_data)?;
+
+        let (remaining_accounts_info, remaining_accounts) = transfer_hook_remaining_accounts(
+            &self.state.pubkey,
+            [(AccountsType::TransferHookReward, &reward_extensions, transfer_hook_accounts)],
+        )?;
+        instructions.push(
+            CollectRewardV2 {
+                whirlpool: self.state.pubkey,
+                position_authority: self.position_authority,
+                position: *position.pubkey(),
+                position_token_account: self.position_token_account(&position_data),
+                reward_owner_account: get_associated_token_address(
+                    &self.position_authority,
+                    &reward_info.mint,
+                    &reward_token_program,
+                ),
+                reward_mint: reward_info.mint,
+                reward_vault: reward_info.vault,
+                reward_token_program,
+                memo_program: MEMO_PROGRAM_ID,
+            }
+            .instruction_with_remaining_accounts(
+                CollectRewardV2InstructionArgs {
+                    reward_index,
+                    remaining_accounts_info,
+                },
+                &remaining_accounts,
+            ),
+        );
+        Ok(OrcaLiquidityInstructions {
+            instructions,
+            quote,
+        })
+    }
+
+    /// Builds a close_position instruction for `position`, returning its rent to `receiver`.
+    ///
+    /// The program only closes empty positions: no liquidity and nothing left to collect. We check the liquidity
+    /// from the cached position, the owed fees and rewards are only known on-chain after an update.
+    pub fn close_position(&self, position: &OrcaPosition, receiver: Pubkey) -> AnyResult<OrcaLiquidityInstructions<()>> {
+        let position_data = self.position_data(position)?;
+        if position_data.liquidity != 0 {
+            return Err(anyhow!(
+                "Position {} still has {} liquidity, decrease it to 0 before closing",
+                position.pubkey(),
+                position_data.liquidity
+            ));
+        }
+        let instruction = ClosePosition {
+            position_authority: self.position_authority,
+            receiver,
+            position: *position.pubkey(),
+            position_mint: position_data.position_mint,
+            position_token_account: self.position_token_account(&position_data),
+            token_program: self.position_token_program,
+        }
+        .instruction();
+        Ok(OrcaLiquidityInstructions {
+            instructions: vec![instruction],
+            quote: (),
+        })
+    }
+
+    // --- Helpers --- //
+
+    /// Quotes and builds an increase_liquidity_v2 instruction, preceded by any tick array initializations.
+    async fn increase(
+        &self,
+        position: Pubkey,
+        position_token_account: Pubkey,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        liquidity: IncreaseLiquidityParam,
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+    ) -> AnyResult<OrcaLiquidityInstructions<IncreaseLiquidityQuote>> {
+        let (transfer_fee_a, transfer_fee_b) = self.transfer_fees()?;
+        let sqrt_price = self.state.whirlpool.sqrt_price;
+        let slippage = self.slippage_tolerance_bps;
+        let quote = match liquidity {
+            IncreaseLiquidityParam::Liquidity(liquidity) => increase_liquidity_quote(
+                liquidity, slippage, sqrt_price, tick_lower_index, tick_upper_index, transfer_fee_a, transfer_fee_b,
+            ),
+            IncreaseLiquidityParam::TokenA(amount) => increase_liquidity_quote_a(
+                amount, slippage, sqrt_price, tick_lower_index, tick_upper_index, transfer_fee_a, transfer_fee_b,
+            ),
+            IncreaseLiquidityParam::TokenB(amount) => increase_liquidity_quote_b(
+                amount, slippage, sqrt_price, tick_lower_index, tick_upper_index, transfer_fee_a, transfer_fee_b,
+            ),
+        }
+        .map_err(|e| anyhow!("Failed to quote increasing liquidity of position {}: {}", position, e))?;
+
+        let mut instructions = self
+            .initialize_tick_arrays(&[tick_lower_index, tick_upper_index], rpc_client)
+            .await?;
+        let (token_program_a, token_program_b) = self.resolved_token_programs()?;
+        let (token_owner_account_a, token_owner_account_b) = self.resolved_token_owner_accounts()?;
+        let (remaining_accounts_info, remaining_accounts) = self.transfer_hook_remaining_accounts()?;
+        instructions.push(
+            IncreaseLiquidityV2 {
+                whirlpool: self.state.pubkey,
+                token_program_a,
+                token_program_b,
+                memo_program: MEMO_PROGRAM_ID,
+                position_authority: self.position_authority,
+                position,
+                position_token_account,
+                token_mint_a: self.state.whirlpool.token_mint_a,
+                token_mint_b: self.state.whirlpool.token_mint_b,
+                token_owner_account_a,
+                token_owner_account_b,
+                token_vault_a: self.state.whirlpool.token_vault_a,
+                token_vault_b: self.state.whirlpool.token_vault_b,
+                tick_array_lower: self.tick_array_address(tick_lower_index)?,
+                tick_array_upper: self.tick_array_address(tick_upper_index)?,
+            }
+            .instruction_with_remaining_accounts(
+                IncreaseLiquidityV2InstructionArgs {
+                    liquidity_amount: quote.liquidity_delta,
+                    token_max_a: quote.token_max_a,
+                    token_max_b: quote.token_max_b,
+                    remaining_accounts_info,
+                },
+                &remaining_accounts,
+            ),
+        );
+        Ok(OrcaLiquidityInstructions { instructions, quote })
+    }
+
+    /// Builds initialize_tick_array instructions for the missing tick arrays containing `tick_indexes`.
+    ///
+    /// The pool answers for the tick arrays it knows about, the rest are probed with one `get_multiple_accounts`.
+    async fn initialize_tick_arrays(
+        &self,
+        tick_indexes: &[i32],
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+    ) -> AnyResult<Vec<Instruction>> {
+        let tick_spacing = self.state.whirlpool.tick_spacing;
+        let mut start_tick_indexes: Vec<i32> = tick_indexes
+            .iter()
+            .map(|tick_index| pda::get_tick_array_start_index(*tick_index, &tick_spacing))
+            .collect();
+        start_tick_indexes.dedup();
+
+        let mut missing = Vec::new();
+        let mut unknown = Vec::new();
+        for start_tick_index in start_tick_indexes {
+            let tick_array = pda::get_tick_array_address(&self.state.pubkey, start_tick_index)?;
+            match self.pool.tick_array_initialized(start_tick_index)? {
+                Some(true) => {}
+                Some(false) => missing.push((start_tick_index, tick_array)),
+                None => unknown.push((start_tick_index, tick_array)),
+            }
+        }
+        if !unknown.is_empty() {
+            let pubkeys: Vec<Pubkey> = unknown.iter().map(|(_, tick_array)| *tick_array).collect();
+            let response = rpc_client
+                .get_multiple_accounts(&pubkeys)
+                .await
+                .map_err(|e| anyhow!("Failed to probe tick arrays of whirlpool {}: {}", self.state.pubkey, e))?;
+            if response.result.len() != pubkeys.len() {
+                return Err(anyhow!(
+                    "get_multiple_accounts returned {} accounts for {} tick arrays",
+                    response.result.len(),
+                    pubkeys.len()
+                ));
+            }
+            missing.extend(
+                unknown
+                    .into_iter()
+                    .zip(response.result)
+                    .filter_map(|(tick_array, account)| account.is_none().then_some(tick_array)),
+            );
+            missing.sort_unstable_by_key(|(start_tick_index, _)| *start_tick_index);
+        }
+
+        Ok(missing
+            .into_iter()
+            .map(|(start_tick_index, tick_array)| {
+                InitializeTickArray {
+                    whirlpool: self.state.pubkey,
+                    funder: self.resolved_funder(),
+                    tick_array,
+                    system_program: system_program::ID,
+                }
+                .instruction(InitializeTickArrayInstructionArgs { start_tick_index })
+            })
+            .collect())
+    }
+
+    /// Builds an update_fees_and_rewards instruction for `position` if it has liquidity, the program rejects it otherwise.
+    fn update_fees_and_rewards(&self, position: &OrcaPosition, position_data: &Position) -> AnyResult<Vec<Instruction>> {
+        if position_data.liquidity == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(vec![UpdateFeesAndRewards {
+            whirlpool: self.state.pubkey,
+            position: *position.pubkey(),
+            tick_array_lower: self.tick_array_address(position_data.tick_lower_index)?,
+            tick_array_upper: self.tick_array_address(position_data.tick_upper_index)?,
+        }
+        .instruction()])
+    }
+
+    /// Checks a new position's range is valid for the pool: ordered, in bounds and on initializable ticks.
+    fn check_tick_range(&self, tick_lower_index: i32, tick_upper_index: i32) -> AnyResult<()> {
+        let tick_spacing = self.state.whirlpool.tick_spacing;
+        let valid = |tick_index: i32| {
+            is_tick_index_in_bounds(tick_index) && is_tick_initializable(tick_index, tick_spacing)
+        };
+        if tick_lower_index >= tick_upper_index || !valid(tick_lower_index) || !valid(tick_upper_index) {
+            return Err(anyhow!(
+                "Invalid position range [{}, {}] for whirlpool {} with tick spacing {}",
+                tick_lower_index,
+                tick_upper_index,
+                self.state.pubkey,
+                tick_spacing
+            ));
+        }
+        Ok(())
+    }
+
+    /// Loads the position's data, checking it belongs to the pool.
+    fn position_data(&self, position: &OrcaPosition) -> AnyResult<Position> {
+        let position_data = (**position.position.get()).clone();
+        if position_data.whirlpool != self.state.pubkey {
+            return Err(anyhow!(
+                "Position {} belongs to whirlpool {}, not {}",
+                position.pubkey(),
+                position_data.whirlpool,
+                self.state.pubkey
+            ));
+        }
+        Ok(position_data)
+    }
+
+    fn position_token_account(&self, position_data: &Position) -> Pubkey {
+        get_associated_token_address(
+            &self.position_authority,
+            &position_data.position_mint,
+            &self.position_token_program,
+        )
+    }
+
+    /// Fetches what collecting from a position needs that the pool doesn't track, in one `get_multiple_accounts`:
+    /// the tick arrays at the ends of its range and `accounts`. Returns the pool state with those tick arrays added,
+    /// along with `accounts`.
+    ///
+    /// A position without liquidity is quoted without its range ticks, so its tick arrays aren't fetched.
+    async fn collect_state(
+        &self,
+        position_data: &Position,
+        accounts: &[Pubkey],
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+    ) -> AnyResult<(OrcaWhirlpoolState, Vec<Option<Account>>)> {
+        let mut untracked = Vec::new();
+        if position_data.liquidity != 0 {
+            let tick_spacing = self.state.whirlpool.tick_spacing;
+            for tick_index in [position_data.tick_lower_index, position_data.tick_upper_index] {
+                let start_tick_index = pda::get_tick_array_start_index(tick_index, &tick_spacing);
+                if self.state.tick_array(start_tick_index).is_none() && !untracked.contains(&start_tick_index) {
+                    untracked.push(start_tick_index);
+                }
+            }
+        }
+        let mut state = self.state.clone();
+        if untracked.is_empty() && accounts.is_empty() {
+            return Ok((state, Vec::new()));
+        }
+
+        let mut pubkeys = accounts.to_vec();
+        for start_tick_index in &untracked {
+            pubkeys.push(pda::get_tick_array_address(&self.state.pubkey, *start_tick_index)?);
+        }
+        let response = rpc_client
+            .get_multiple_accounts(&pubkeys)
+            .await
+            .map_err(|e| anyhow!("Failed to fetch accounts for whirlpool {}: {}", self.state.pubkey, e))?;
+        if response.result.len() != pubkeys.len() {
+            return Err(anyhow!(
+                "get_multiple_accounts returned {} accounts for {} requested",
+                response.result.len(),
+                pubkeys.len()
+            ));
+        }
+        let mut fetched = response.result;
+        let tick_arrays = fetched.split_off(accounts.len());
+        for (pubkey, account) in pubkeys[accounts.len()..].iter().zip(tick_arrays) {
+            let account = account.ok_or_else(|| anyhow!("Tick array {} not found", pubkey))?;
+            state
+                .tick_arrays
+                .push(Arc::new(<TickArray as Deserializable>::from_bytes(&account.data)?));
+        }
+        state.tick_arrays.sort_unstable_by_key(|tick_array| tick_array_start_index(tick_array));
+        Ok((state, fetched))
+    }
+
+    fn tick_array_address(&self, tick_index: i32) -> AnyResult<Pubkey> {
+        let start_tick_index = pda::get_tick_array_start_index(tick_index, &self.state.whirlpool.tick_spacing);
+        pda::get_tick_array_address(&self.state.pubkey, start_tick_index)
+    }
+
+    fn resolved_funder(&self) -> Pubkey {
+        self.funder.unwrap_or(self.position_authority)
+    }
+
+    fn mint_extensions(&self) -> AnyResult<(MintExtensions, MintExtensions)> {
+        Ok((self.state.mint_a_extensions()?, self.state.mint_b_extensions()?))
+    }
+
+    fn resolved_token_programs(&self) -> AnyResult<(Pubkey, Pubkey)> {
+        let (mint_a_extensions, mint_b_extensions) = self.mint_extensions()?;
+        Ok((
+            self.token_program_a.unwrap_or_else(|| mint_a_extensions.token_program_id()),
+            self.token_program_b.unwrap_or_else(|| mint_b_extensions.token_program_id()),
+        ))
+    }
+
+    fn resolved_token_owner_accounts(&self) -> AnyResult<(Pubkey, Pubkey)> {
+        let (token_program_a, token_program_b) = self.resolved_token_programs()?;
+        Ok((
+            self.token_owner_account_a.unwrap_or_else(|| {
+                get_associated_token_address(&self.position_authority, &self.state.whirlpool.token_mint_a, &token_program_a)
+            }),
+            self.token_owner_account_b.unwrap_or_else(|| {
+                get_associated_token_address(&self.position_authority, &self.state.whirlpool.token_mint_b, &token_program_b)
+            }),
+        ))
+    }
+
+    /// Returns the transfer fees at the given epoch, failing if a mint has one and no epoch was given.
+    fn transfer_fees(&self) -> AnyResult<(Option<TransferFee>, Option<TransferFee>)> {
+        match self.epoch {
+            Some(epoch) => self.state.transfer_fees(epoch),
+            None => {
+                let (mint_a_extensions, mint_b_extensions) = self.mint_extensions()?;
+                if mint_a_extensions.transfer_fee_config.is_some() || mint_b_extensions.transfer_fee_config.is_some() {
+                    return Err(anyhow!(
+                        "Whirlpool {} has a Token-2022 transfer fee, liquidity quotes need an epoch",
+                        self.state.pubkey
+                    ));
+                }
+                Ok((None, None))
+            }
+        }
+    }
+
+    fn transfer_hook_remaining_accounts(&self) -> AnyResult<(Option<RemainingAccountsInfo>, Vec<AccountMeta>)> {
+        let (mint_a_extensions, mint_b_extensions) = self.mint_extensions()?;
+        transfer_hook_remaining_accounts(
+            &self.state.pubkey,
+            [
+                (AccountsType::TransferHookA, &mint_a_extensions, self.transfer_hook_accounts_a.clone()),
+                (AccountsType::TransferHookB, &mint_b_extensions, self.transfer_hook_accounts_b.clone()),
+            ],
+        )
+    }
+}
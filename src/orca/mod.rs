@@ -5,8 +5,24 @@
 //! 
 //! In particular, we define `OrcaWhirlpool`, a liquidity pool on the Orca DEX, as a logical 
 //! grouping of `ManagedAccount`s with types `T` from the orca program, e.g. `Whirlpool`, `TickArray` 
-//! and `Oracle`, and implement the `Pool` trait for it.
+//! and `Oracle`, and implement the `Pool` trait for it. 
+//! 
+//! Liquidity positions are modelled the same way by `OrcaPosition`, a `ManagedAccount<Position>` that is 
+//! valued against the state of its `OrcaWhirlpool`.
+//! 
+//! Trades are built from the same cached state, see `OrcaSwapBuilder` and `OrcaTwoHopSwapBuilder`, as are
+//! liquidity management instructions, see `OrcaLiquidityBuilder`.
 
+pub mod config;
 mod deserialize;
+pub mod fee;
+pub mod liquidity;
 pub mod pda;
-pub mod pool;
\ No newline at end of file
+pub mod pool;
+pub mod position;
+pub mod quote;
+pub mod simulate;
+pub mod state;
+pub mod swap;
+pub mod tick_array;
+pub mod two_hop;
\ No newline at end of file
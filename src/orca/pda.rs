@@ -9,6 +9,9 @@ use orca_whirlpools_core::{
 };
 use num_integer::Integer;
 
+/// The largest absolute tick index supported by whirlpools, see `get_tick_array_addresses` for the derivation.
+const ABS_MAX_TICK_IDX: i32 = 443636;
+
 /// Quick fn to get the whirlpool master pubkey
 /// 
 /// Returns:
@@ -42,11 +45,17 @@ pub fn parse_whirlpool_master_pubkey() -> Pubkey {
 /// Note: Many of these tick arrays are uninitialized, but theres no way to check without 
 /// an RPC call. We begin by trying all, and it's reasonable to assume any that failed 
 /// are simply uninitialized.
+#[cfg_attr(feature = "tracing", tracing::instrument(
+    level = "trace",
+    skip_all,
+    err,
+    fields(whirlpool = %whirlpool_pubkey, tick_spacing = *tick_spacing, count = tracing::field::Empty),
+))]
 pub fn get_tick_array_addresses(
     whirlpool_pubkey: &Pubkey,
     tick_spacing: &u16,
 ) -> AnyResult<Vec<Pubkey>> {
-    let abs_max_tick_idx: i32 = 443636;
+    let abs_max_tick_idx: i32 = ABS_MAX_TICK_IDX;
     let tick_array_width: i32 = TICK_ARRAY_SIZE as i32 * *tick_spacing as i32;
     let mut tick_array_pubkeys: Vec<Pubkey> = Vec::new();
     let mut curr_start_tick_idx: i32 = Integer::div_floor(&(-abs_max_tick_idx), &tick_array_width) * tick_array_width;
@@ -59,6 +68,62 @@ pub fn get_tick_array_addresses(
         tick_array_pubkeys.push(tick_array_address);
         curr_start_tick_idx += tick_array_width;
     }
+    trace_record!("count" = tick_array_pubkeys.len());
+    Ok(tick_array_pubkeys)
+}
+
+/// Given a tick index and the whirlpool's tick spacing, returns the start tick index of the tick array 
+/// containing it. 
+/// 
+/// This is the floor(i / (TICK_ARRAY_SIZE * tick_spacing)) * (TICK_ARRAY_SIZE * tick_spacing) formula 
+/// from `get_tick_array_addresses`.
+pub fn get_tick_array_start_index(tick_index: i32, tick_spacing: &u16) -> i32 {
+    let tick_array_width: i32 = TICK_ARRAY_SIZE as i32 * *tick_spacing as i32;
+    Integer::div_floor(&tick_index, &tick_array_width) * tick_array_width
+}
+
+/// Given a whirlpool pubkey and its current tick, returns the pubkeys of the `radius` tick arrays either 
+/// side of the tick array containing the current tick, along with that tick array itself. 
+/// 
+/// The window is ordered by ascending start tick index and is truncated at the edges of the viable tick 
+/// range [-443636, 443636], so it holds at most 2 * radius + 1 pubkeys. 
+/// 
+/// Note: As with `get_tick_array_addresses`, some of these tick arrays may be uninitialized.
+#[cfg_attr(feature = "tracing", tracing::instrument(
+    level = "trace",
+    skip_all,
+    err,
+    fields(
+        whirlpool = %whirlpool_pubkey,
+        tick_spacing = *tick_spacing,
+        tick_current_index,
+        radius,
+        count = tracing::field::Empty,
+    ),
+))]
+pub fn get_tick_array_window_addresses(
+    whirlpool_pubkey: &Pubkey,
+    tick_spacing: &u16,
+    tick_current_index: i32,
+    radius: usize,
+) -> AnyResult<Vec<Pubkey>> {
+    let tick_array_width: i32 = TICK_ARRAY_SIZE as i32 * *tick_spacing as i32;
+    let smallest_start_tick_idx: i32 = get_tick_array_start_index(-ABS_MAX_TICK_IDX, tick_spacing);
+    let largest_start_tick_idx: i32 = get_tick_array_start_index(ABS_MAX_TICK_IDX, tick_spacing);
+    let centre_start_tick_idx: i32 = get_tick_array_start_index(tick_current_index, tick_spacing);
+    // Clamp in i64 so a large radius can't overflow the tick arithmetic.
+    let first_start_tick_idx = (centre_start_tick_idx as i64 - radius as i64 * tick_array_width as i64)
+        .max(smallest_start_tick_idx as i64) as i32;
+    let last_start_tick_idx = (centre_start_tick_idx as i64 + radius as i64 * tick_array_width as i64)
+        .min(largest_start_tick_idx as i64) as i32;
+
+    let mut tick_array_pubkeys: Vec<Pubkey> = Vec::new();
+    let mut curr_start_tick_idx: i32 = first_start_tick_idx;
+    while curr_start_tick_idx <= last_start_tick_idx {
+        tick_array_pubkeys.push(get_tick_array_address(whirlpool_pubkey, curr_start_tick_idx)?);
+        curr_start_tick_idx += tick_array_width;
+    }
+    trace_record!("count" = tick_array_pubkeys.len());
     Ok(tick_array_pubkeys)
 }
 
@@ -105,4 +170,82 @@ pub fn get_tick_array_address(
     }
     // Unwrap the pubkey from the tuple w/ .0 as we don't need the discriminant
     Ok(tick_array_address_result.unwrap().0)
-}
\ No newline at end of file
+}
+
+/// Given a position mint, derives the corresponding position pubkey and its bump.
+/// 
+/// The bump is needed to open the position, see `open_position`.
+pub fn get_position_address(position_mint: &Pubkey) -> AnyResult<(Pubkey, u8)> {
+    let seeds = &[b"position", position_mint.as_ref()];
+    let whirlpool_master_pubkey: Pubkey = parse_whirlpool_master_pubkey();
+    Pubkey::try_find_program_address(seeds, &whirlpool_master_pubkey)
+        .ok_or_else(|| anyhow!("Failed to get position address"))
+}
+
+/// Given a position bundle mint, derives the corresponding position bundle pubkey and its bump.
+pub fn get_position_bundle_address(position_bundle_mint: &Pubkey) -> AnyResult<(Pubkey, u8)> {
+    let seeds = &[b"position_bundle", position_bundle_mint.as_ref()];
+    let whirlpool_master_pubkey: Pubkey = parse_whirlpool_master_pubkey();
+    Pubkey::try_find_program_address(seeds, &whirlpool_master_pubkey)
+        .ok_or_else(|| anyhow!("Failed to get position bundle address"))
+}
+
+/// Given a position bundle mint and an index into the bundle, derives the bundled position pubkey and its bump.
+/// 
+/// Note: Bundled positions are seeded by the bundle's mint (`PositionBundle::position_bundle_mint`), not the 
+/// position bundle account. As with tick arrays, the index is seeded by its string representation rather than its 
+/// bytes.
+pub fn get_bundled_position_address(
+    position_bundle_mint: &Pubkey,
+    bundle_index: u8,
+) -> AnyResult<(Pubkey, u8)> {
+    let bundle_index_str = bundle_index.to_string();
+    let seeds = &[
+        b"bundled_position",
+        position_bundle_mint.as_ref(),
+        bundle_index_str.as_bytes(),
+    ];
+    let whirlpool_master_pubkey: Pubkey = parse_whirlpool_master_pubkey();
+    Pubkey::try_find_program_address(seeds, &whirlpool_master_pubkey)
+        .ok_or_else(|| anyhow!("Failed to get bundled position address"))
+}
+
+/// Given a whirlpools config pubkey and a fee tier index, derives the corresponding fee tier pubkey.
+/// 
+/// For static fee pools the fee tier index is just the tick spacing, in general it's the whirlpool's 
+/// `fee_tier_index_seed` read as a little-endian u16.
+pub fn get_fee_tier_address(whirlpools_config_pubkey: &Pubkey, fee_tier_index: u16) -> AnyResult<Pubkey> {
+    let fee_tier_index_bytes = fee_tier_index.to_le_bytes();
+    let seeds = &[
+        b"fee_tier".as_ref(),
+        whirlpools_config_pubkey.as_ref(),
+        fee_tier_index_bytes.as_ref(),
+    ];
+    let whirlpool_master_pubkey: Pubkey = parse_whirlpool_master_pubkey();
+    Pubkey::try_find_program_address(seeds, &whirlpool_master_pubkey)
+        .map(|(pubkey, _)| pubkey)
+        .ok_or_else(|| anyhow!("Failed to get fee tier address"))
+}
+
+/// Given a whirlpools config pubkey and a fee tier index, derives the corresponding adaptive fee tier pubkey.
+/// 
+/// Note: Adaptive fee tiers share their seeds with fee tiers, they're just stored at fee tier indexes that 
+/// differ from the tick spacing. So this is the same address as `get_fee_tier_address`, it's here for clarity.
+pub fn get_adaptive_fee_tier_address(whirlpools_config_pubkey: &Pubkey, fee_tier_index: u16) -> AnyResult<Pubkey> {
+    get_fee_tier_address(whirlpools_config_pubkey, fee_tier_index)
+}
+
+/// Given a whirlpools config pubkey and a token mint, derives the corresponding token badge pubkey.
+/// 
+/// Note: Token badges only exist for Token-2022 mints the config authority has explicitly allowed.
+pub fn get_token_badge_address(whirlpools_config_pubkey: &Pubkey, token_mint: &Pubkey) -> AnyResult<Pubkey> {
+    let seeds = &[
+        b"token_badge".as_ref(),
+        whirlpools_config_pubkey.as_ref(),
+        token_mint.as_ref(),
+    ];
+    let whirlpool_master_pubkey: Pubkey = parse_whirlpool_master_pubkey();
+    Pubkey::try_find_program_address(seeds, &whirlpool_master_pubkey)
+        .map(|(pubkey, _)| pubkey)
+        .ok_or_else(|| anyhow!("Failed to get token badge address"))
+}
@@ -2,12 +2,21 @@
 
 use crate::common::{
     account::AccountData,
-    pool::Pool,
+    pool::{Pool, PoolHealth},
+    refresh::{RefreshPolicy, RefreshReport},
     rpc::RpcProvider,
-    state::{AccountState, ManagedAccount},
+    snapshot::PoolSnapshot,
+    state::{AccountLifecycle, AccountState, ManagedAccount},
+};
+use crate::orca::{
+    config::OrcaConfigAccounts,
+    pda,
+    state::OrcaWhirlpoolState,
+    swap::MEMO_PROGRAM_ID,
+    tick_array::{TickArrayAccount, TickArrayStorage},
 };
-use crate::orca::pda;
 use anyhow::anyhow;
+use arc_swap::ArcSwap;
 use crate::common::types::AnyResult;
 use async_trait::async_trait;
 use orca_whirlpools_client::{Oracle, TickArray, Whirlpool};
@@ -15,18 +24,98 @@ use solana_sdk::pubkey::Pubkey;
 use solana_sdk::account::Account;
 use spl_token::state::Mint;
 use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
 
 // --- Orca Whirlpool Struct Definition --- //
 
 /// The logical collection of `ManagedAccount`s that define an Orca Whirlpool.
 pub struct OrcaWhirlpool {
     pub whirlpool: Arc<ManagedAccount<Whirlpool>>,
-    pub tick_arrays: Vec<Arc<ManagedAccount<TickArray>>>,
+    /// The tick arrays currently tracked by the pool, see `tick_arrays`.
+    ///
+    /// Wrapped in an `ArcSwap` as the set itself can change during a refresh (see `TickArrayMode::Windowed`),
+    /// readers just `load()` the current set and keep using it even if it's swapped out from under them.
+    tick_arrays: ArcSwap<Vec<Arc<TickArrayAccount>>>,
     // An `Option` is used because not all pools have an oracle account.
     pub oracle: Option<Arc<ManagedAccount<Oracle>>>,
     pub mint_a: Arc<ManagedAccount<Mint>>,
     pub mint_b: Arc<ManagedAccount<Mint>>,
+    /// The owners of the mint accounts, i.e. the token programs of tokens A and B, fixed at construction.
+    token_programs: (Pubkey, Pubkey),
+    /// The config, fee tier and token badge accounts, only loaded on request, see `OrcaWhirlpool::with_config_accounts`.
+    pub config_accounts: Option<OrcaConfigAccounts>,
+    /// How the pool decides which tick arrays to track, fixed at construction.
+    pub tick_array_mode: TickArrayMode,
+    /// How the pool stores its tick arrays, fixed at construction.
+    pub tick_array_storage: TickArrayStorage,
+    /// Tick array PDAs that didn't exist on-chain when last probed, re-probed by `rediscover`.
+    missing_tick_arrays: ArcSwap<Vec<Pubkey>>,
+    /// Tick arrays the last refresh dropped from the window because they were closed, kept for `health` to report.
+    dropped_tick_arrays: ArcSwap<Vec<Arc<TickArrayAccount>>>,
+    /// Re-probe the missing tick arrays every this many refreshes, 0 disables this.
+    rediscovery_interval: AtomicU64,
+    /// The number of refreshes so far, used to schedule rediscovery.
+    refresh_count: AtomicU64,
+    /// The `refresh_count` as of which `missing_tick_arrays` was last probed, see `tick_array_initialized`.
+    missing_probed_at: AtomicU64,
+    /// Set as soon as a refresh or rediscovery changes an account or the tracked tick arrays, and cleared by
+    /// `publish_unlocked`. So the changes of one that errors or is cancelled part way are published by the next.
+    unpublished_changes: AtomicBool,
+    /// The pool's accounts as of the last complete refresh, swapped in one go, see `Pool::snapshot`.
+    published: ArcSwap<PublishedState>,
+    /// Serialises refreshes and rediscoveries, so snapshots are published in order.
+    refresh_lock: Mutex<()>,
+}
+
+/// A published snapshot along with the typed view built from it, so `state()` doesn't re-downcast each call.
+struct PublishedState {
+    snapshot: Arc<PoolSnapshot>,
+    state: OrcaWhirlpoolState,
+}
+
+impl PublishedState {
+    /// Snapshots `accounts` and builds the typed view, see `OrcaWhirlpoolState::from_snapshot`.
+    fn new(
+        pubkey: Pubkey,
+        sequence: u64,
+        slot: Option<u64>,
+        accounts: &[Arc<dyn AccountState>],
+        oracle: Option<&Pubkey>,
+        token_programs: (Pubkey, Pubkey),
+    ) -> AnyResult<Self> {
+        let snapshot = PoolSnapshot {
+            pubkey,
+            sequence,
+            slot,
+            accounts: accounts.iter().map(|account| account.snapshot()).collect(),
+        };
+        let state = OrcaWhirlpoolState::from_snapshot(&snapshot, oracle, token_programs)?;
+        Ok(Self {
+            snapshot: Arc::new(snapshot),
+            state,
+        })
+    }
+}
+
+/// Determines which tick arrays an `OrcaWhirlpool` tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TickArrayMode {
+    /// Every initialized tick array in the viable tick range is tracked, see `pda::get_tick_array_addresses`. 
+    /// 
+    /// For small tick spacings this is thousands of PDAs, so only use this if you really need the whole book.
+    #[default]
+    Full,
+    /// Only the `radius` tick arrays either side of the tick array containing the current tick are tracked 
+    /// (so at most 2 * radius + 1 accounts). 
+    /// 
+    /// The window is re-centred on each refresh whenever `tick_current_index` moves, and every PDA in the 
+    /// window is re-fetched, so tick arrays initialized inside the window are picked up automatically 
+    /// (`OrcaWhirlpool::rediscover` is a no-op in this mode).
+    Windowed { radius: usize },
 }
 
 /// Holds information about an account that failed to be fetched. 
@@ -55,26 +144,72 @@ impl Pool for OrcaWhirlpool {
         self.whirlpool.pubkey()
     }
 
+    /// Returns the accounts every swap on the pool references: the whirlpool, its vaults, mints and oracle PDA, the 
+    /// token, memo and whirlpool programs, and the tick arrays a swap in either direction would use right now.
+    /// 
+    /// Note: The tick arrays follow the price, so a lookup table built from these needs extending as it moves.
+    fn static_accounts(&self) -> Vec<Pubkey> {
+        let state = self.state();
+        let whirlpool_data = &state.whirlpool;
+        let mut static_accounts = vec![
+            state.pubkey,
+            whirlpool_data.token_vault_a,
+            whirlpool_data.token_vault_b,
+            whirlpool_data.token_mint_a,
+            whirlpool_data.token_mint_b,
+            state.token_program_a,
+            state.token_program_b,
+            MEMO_PROGRAM_ID,
+            pda::parse_whirlpool_master_pubkey(),
+        ];
+        if let Ok((oracle, _)) = pda::get_oracle_address(&state.pubkey) {
+            static_accounts.push(oracle);
+        }
+        for a_to_b in [true, false] {
+            for start_tick_index in state.swap_tick_array_start_indexes(a_to_b) {
+                if let Ok(tick_array) = pda::get_tick_array_address(&state.pubkey, start_tick_index) {
+                    static_accounts.push(tick_array);
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        static_accounts.retain(|pubkey| seen.insert(*pubkey));
+        static_accounts
+    }
+
     /// Gathers `Arc` pointers to all accounts in the pool as `AccountState` objects for the pool.
     ///
     /// Allows for generic operations on all accounts in the pool without knowing their concrete types. 
     fn accounts(&self) -> Vec<Arc<dyn AccountState>> {
-        let mut accounts: Vec<Arc<dyn AccountState>> = vec![
-            self.whirlpool.clone(),
-            self.mint_a.clone(),
-            self.mint_b.clone(),
-        ];
+        let mut accounts: Vec<Arc<dyn AccountState>> = self.fixed_accounts();
         accounts.extend(
             self.tick_arrays
+                .load()
                 .iter()
                 .map(|ta| ta.clone() as Arc<dyn AccountState>),
         );
-        if let Some(oracle) = &self.oracle {
-            accounts.push(oracle.clone());
-        }
         accounts
     }
 
+    /// Returns the snapshot published by the last complete refresh (or the constructor, or `publish`).
+    /// 
+    /// Unlike reading the `ManagedAccount`s directly, this never mixes accounts from different refreshes, and 
+    /// a refresh that fails part way leaves the previous snapshot in place.
+    fn snapshot(&self) -> Arc<PoolSnapshot> {
+        self.published.load().snapshot.clone()
+    }
+
+    /// Returns the lifecycle of the pool's accounts, see `PoolHealth`. 
+    /// 
+    /// In `TickArrayMode::Windowed` closed tick arrays are dropped from `accounts()`, so they're kept aside and
+    /// reported as closed until the next refresh.
+    fn health(&self) -> PoolHealth {
+        let mut accounts = self.accounts();
+        let dropped = self.dropped_tick_arrays.load();
+        accounts.extend(dropped.iter().map(|ta| ta.clone() as Arc<dyn AccountState>));
+        PoolHealth::new(self.pubkey(), &accounts)
+    }
+
     /// Downcasts the `&dyn Pool` trait object back to a concrete `&OrcaWhirlpool`.
     fn as_any(&self) -> &dyn Any {
         self
@@ -91,21 +226,107 @@ impl Pool for OrcaWhirlpool {
     /// NOTE: Your get_multiple_accounts implementation must also be order preserving, otherwise the zip is nonsensical. 
     /// For non-order preserving RpcProviders, you will need a new implementation. But the Orphan rule will get you here. 
     /// If this niche case ever arrives, email me! I'll see what I can do. 
-    async fn refresh(&self, rpc_client: &dyn RpcProvider<AccountType = Self::AccountType>) -> AnyResult<()> {
-        let accounts_to_update: Vec<_> = self.accounts().iter().map(|a| *a.pubkey()).collect();
+    /// 
+    /// NOTE: In `TickArrayMode::Windowed` we fetch the whole window rather than just the tracked tick arrays, and 
+    /// pay a second RPC call whenever the refreshed whirlpool has moved far enough to shift the window.
+    /// 
+    /// NOTE: If a rediscovery interval is set (see `OrcaWhirlpool::set_rediscovery_interval`) then every n-th 
+    /// refresh also pays for `OrcaWhirlpool::rediscover`.
+    /// 
+    /// NOTE: Concurrent refreshes run one at a time. Each that changed anything publishes a new snapshot (see
+    /// `Pool::snapshot`) once all its accounts are updated. Under `RefreshPolicy::Abort` a failed account stops
+    /// the refresh before publishing, its changes are published by the next refresh that completes.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "debug",
+        skip_all,
+        err,
+        fields(
+            pool = %self.whirlpool.pubkey(),
+            mode = ?self.tick_array_mode,
+            ?policy,
+            accounts = tracing::field::Empty,
+            changed = tracing::field::Empty,
+            unchanged = tracing::field::Empty,
+            missing = tracing::field::Empty,
+            failed = tracing::field::Empty,
+            rpc_calls = tracing::field::Empty,
+            slot = tracing::field::Empty,
+        ),
+    ))]
+    async fn refresh_with_policy(
+        &self,
+        rpc_client: &dyn RpcProvider<AccountType = Self::AccountType>,
+        policy: RefreshPolicy,
+    ) -> AnyResult<RefreshReport> {
+        let _refresh_guard = self.refresh_lock.lock().await;
+        let mut report = RefreshReport::default();
+        let fixed_accounts = self.fixed_accounts();
 
+        let tick_arrays = self.tick_arrays.load_full();
+        let tick_array_pubkeys: Vec<Pubkey> = match self.tick_array_mode {
+            TickArrayMode::Full => tick_arrays.iter().map(|ta| *ta.pubkey()).collect(),
+            TickArrayMode::Windowed { radius } => self.tick_array_window(radius)?,
+        };
+
+        let mut accounts_to_update: Vec<Pubkey> = fixed_accounts.iter().map(|a| *a.pubkey()).collect();
+        accounts_to_update.extend_from_slice(&tick_array_pubkeys);
+        trace_record!("accounts" = accounts_to_update.len());
+
+        let request_start = Instant::now();
         let rpc_response = rpc_client.get_multiple_accounts(&accounts_to_update).await?;
-        let accounts_data = rpc_response.result;
+        report.record_rpc_call(request_start.elapsed(), rpc_response.slot);
+        let mut accounts_data = rpc_response.result.into_iter();
         let update_time = rpc_response.response_time;
 
-        for (managed_account, account_data_option) in self.accounts().into_iter().zip(accounts_data.into_iter()) {
-            if let Some(account_data) = account_data_option {
-                let bytes = account_data.into_bytes();
-                managed_account.update(bytes, update_time)?;
+        // The fixed accounts come first, `zip` stops once they're exhausted leaving the tick arrays in the iterator.
+        for (managed_account, account_data_option) in fixed_accounts.iter().zip(&mut accounts_data) {
+            let bytes_option = account_data_option.map(|account_data| account_data.into_bytes());
+            self.apply_update(&mut report, policy, managed_account.as_ref(), bytes_option, update_time)?;
+        }
+
+        let tick_arrays_data: Vec<(Pubkey, Option<Vec<u8>>)> = tick_array_pubkeys
+            .into_iter()
+            .zip(accounts_data.map(|a| a.map(|a| a.into_bytes())))
+            .collect();
+
+        match self.tick_array_mode {
+            TickArrayMode::Full => {
+                for (managed_account, (_, bytes_option)) in tick_arrays.iter().zip(tick_arrays_data) {
+                    self.apply_update(&mut report, policy, managed_account.as_ref(), bytes_option, update_time)?;
+                }
+            }
+            TickArrayMode::Windowed { radius } => {
+                self.update_tick_array_window(
+                    rpc_client,
+                    &tick_arrays,
+                    tick_arrays_data,
+                    update_time,
+                    radius,
+                    policy,
+                    &mut report,
+                )
+                .await?;
             }
         }
 
-        Ok(())
+        let refresh_count = self.refresh_count.fetch_add(1, Ordering::Relaxed) + 1;
+        let rediscovery_interval = self.rediscovery_interval.load(Ordering::Relaxed);
+        if rediscovery_interval > 0 && refresh_count.is_multiple_of(rediscovery_interval) {
+            self.rediscover_unlocked(rpc_client, policy, &mut report).await?;
+        }
+
+        if self.unpublished_changes.load(Ordering::Relaxed) {
+            self.publish_unlocked(report.slot)?;
+        }
+        trace_record!(
+            "changed" = report.changed.len(),
+            "unchanged" = report.unchanged.len(),
+            "missing" = report.missing.len(),
+            "failed" = report.failed.len(),
+            "rpc_calls" = report.rpc_calls,
+            "slot" = report.slot,
+        );
+        Ok(report)
     }
 }
 
@@ -122,15 +343,55 @@ impl OrcaWhirlpool {
     /// Note: This time we don't require you to implement get_multiple_accounts for n accounts, we only require it work for the 
     /// maximal number in one call. We do the batching ourselves. This is just legacy code I don't fancy replacing, and may change 
     /// if I think of a reason its slower. Again, this runs once in a pool's lifetime, so not a huge deal.
+    /// 
+    /// Note: This tracks every tick array, i.e. `TickArrayMode::Full`. See `new_initialized_from_rpc_with_mode`.
     pub async fn new_initialized_from_rpc<C: RpcProvider + Send + Sync>(
         pubkey: &Pubkey,
         rpc_provider: &C,
+    ) -> AnyResult<(Self, Vec<FailedAccount>)> {
+        Self::new_initialized_from_rpc_with_mode(pubkey, rpc_provider, TickArrayMode::Full).await
+    }
+
+    /// As `new_initialized_from_rpc`, but with the tick arrays to track chosen by `tick_array_mode`. 
+    /// 
+    /// In `TickArrayMode::Windowed` only the window around the whirlpool's current tick is fetched, so the 
+    /// initial load is a handful of accounts rather than thousands for small tick spacings.
+    pub async fn new_initialized_from_rpc_with_mode<C: RpcProvider + Send + Sync>(
+        pubkey: &Pubkey,
+        rpc_provider: &C,
+        tick_array_mode: TickArrayMode,
+    ) -> AnyResult<(Self, Vec<FailedAccount>)> {
+        Self::new_initialized_from_rpc_with_storage(pubkey, rpc_provider, tick_array_mode, TickArrayStorage::Eager)
+            .await
+    }
+
+    /// As `new_initialized_from_rpc_with_mode`, but with the tick arrays stored as chosen by `tick_array_storage`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "debug",
+        skip_all,
+        err,
+        fields(
+            pool = %pubkey,
+            mode = ?tick_array_mode,
+            storage = ?tick_array_storage,
+            accounts = tracing::field::Empty,
+            chunks = tracing::field::Empty,
+            missing_tick_arrays = tracing::field::Empty,
+            slot = tracing::field::Empty,
+        ),
+    ))]
+    pub async fn new_initialized_from_rpc_with_storage<C: RpcProvider + Send + Sync>(
+        pubkey: &Pubkey,
+        rpc_provider: &C,
+        tick_array_mode: TickArrayMode,
+        tick_array_storage: TickArrayStorage,
     ) -> AnyResult<(Self, Vec<FailedAccount>)> {
         let whirlpool_response = rpc_provider
             .get_account(pubkey)
             .await
             .map_err(|e| anyhow!("Failed to fetch main whirlpool account {}: {}", pubkey, e))?;
         let whirlpool_time = whirlpool_response.response_time;
+        let mut whirlpool_slot = whirlpool_response.slot;
         let whirlpool_account = whirlpool_response.result;
         let whirlpool_data = Whirlpool::from_bytes(whirlpool_account.bytes())?;
 
@@ -146,22 +407,31 @@ impl OrcaWhirlpool {
             None
         };
 
-        let tick_arrays_pubkeys =
-            pda::get_tick_array_addresses(pubkey, &whirlpool_data.tick_spacing)?;
+        let tick_arrays_pubkeys = match tick_array_mode {
+            TickArrayMode::Full => pda::get_tick_array_addresses(pubkey, &whirlpool_data.tick_spacing)?,
+            TickArrayMode::Windowed { radius } => pda::get_tick_array_window_addresses(
+                pubkey,
+                &whirlpool_data.tick_spacing,
+                whirlpool_data.tick_current_index,
+                radius,
+            )?,
+        };
         pubkeys_to_fetch.extend_from_slice(&tick_arrays_pubkeys);
 
-        let mut account_map = std::collections::HashMap::new();
+        let mut account_map = HashMap::new();
         let mut failures = Vec::new();
         let limit = rpc_provider.max_accounts_per_rpc_call();
+        trace_record!("accounts" = pubkeys_to_fetch.len(), "chunks" = pubkeys_to_fetch.len().div_ceil(limit.max(1)));
         // iterate over chunks of maximal size, minimising the number of RPC calls.
         for chunk in pubkeys_to_fetch.chunks(limit) {
             let rpc_response = rpc_provider.get_multiple_accounts(chunk).await?;
             let accounts_time = rpc_response.response_time;
+            whirlpool_slot = whirlpool_slot.max(rpc_response.slot);
             let accounts = rpc_response.result;
             for (i, account_option) in accounts.into_iter().enumerate() {
                 if let Some(account) = account_option {
                     // Store the data along with the timestamp
-                    account_map.insert(chunk[i], (account.bytes().to_vec(), accounts_time));
+                    account_map.insert(chunk[i], (account.bytes().to_vec(), accounts_time, *account.owner()));
                 }
             }
         }
@@ -178,7 +448,7 @@ impl OrcaWhirlpool {
             whirlpool_time,
         )?);
 
-        let (mint_a_data, mint_a_time) = get_data(&whirlpool_data.token_mint_a).ok_or_else(|| {
+        let (mint_a_data, mint_a_time, token_program_a) = get_data(&whirlpool_data.token_mint_a).ok_or_else(|| {
             anyhow!(
                 "Required account Mint A {} could not be fetched",
                 whirlpool_data.token_mint_a
@@ -190,7 +460,7 @@ impl OrcaWhirlpool {
             mint_a_time,
         )?);
 
-        let (mint_b_data, mint_b_time) = get_data(&whirlpool_data.token_mint_b).ok_or_else(|| {
+        let (mint_b_data, mint_b_time, token_program_b) = get_data(&whirlpool_data.token_mint_b).ok_or_else(|| {
             anyhow!(
                 "Required account Mint B {} could not be fetched",
                 whirlpool_data.token_mint_b
@@ -203,7 +473,7 @@ impl OrcaWhirlpool {
         )?);
 
         let oracle = if let Some(opk) = oracle_pubkey {
-            if let Some((oracle_data, oracle_time)) = get_data(&opk) {
+            if let Some((oracle_data, oracle_time, _)) = get_data(&opk) {
                 Some(Arc::new(
                     ManagedAccount::<Oracle>::new_initialized_from_bytes(
                         opk,
@@ -223,15 +493,17 @@ impl OrcaWhirlpool {
         };
 
         let mut tick_arrays = Vec::new();
+        let mut missing_tick_arrays = Vec::new();
         for ta_pubkey in &tick_arrays_pubkeys {
-            if let Some((ta_data, ta_time)) = get_data(ta_pubkey) {
+            if let Some((ta_data, ta_time, _)) = get_data(ta_pubkey) {
                 tick_arrays.push(Arc::new(
-                    ManagedAccount::<TickArray>::new_initialized_from_bytes(
-                        *ta_pubkey, ta_data, ta_time,
+                    TickArrayAccount::new_initialized_from_bytes(
+                        tick_array_storage, *ta_pubkey, ta_data, ta_time,
                     )?,
                 ));
             } else {
                 // It's expected that not all tick arrays will exist on-chain.
+                missing_tick_arrays.push(*ta_pubkey);
                 failures.push(FailedAccount {
                     pubkey: *ta_pubkey,
                     account_type: "TickArray".to_string(),
@@ -239,15 +511,393 @@ impl OrcaWhirlpool {
             }
         }
 
-        // 5. Assemble and return the `OrcaWhirlpool` struct with the `Arc`s.
+        trace_record!("missing_tick_arrays" = missing_tick_arrays.len());
+        trace_record!("slot" = whirlpool_slot);
+
+        // 5. Assemble and return the `OrcaWhirlpool` struct with the `Arc`s, publishing its initial state.
+        let token_programs = (token_program_a, token_program_b);
+        let mut accounts: Vec<Arc<dyn AccountState>> = vec![whirlpool.clone(), mint_a.clone(), mint_b.clone()];
+        if let Some(oracle) = &oracle {
+            accounts.push(oracle.clone());
+        }
+        accounts.extend(tick_arrays.iter().map(|ta| ta.clone() as Arc<dyn AccountState>));
+        let published = PublishedState::new(
+            *pubkey,
+            0,
+            whirlpool_slot,
+            &accounts,
+            oracle.as_ref().map(|oracle| oracle.pubkey()),
+            token_programs,
+        )?;
         let pool = Self {
             whirlpool,
-            tick_arrays,
+            tick_arrays: ArcSwap::from_pointee(tick_arrays),
             oracle,
             mint_a,
             mint_b,
+            token_programs,
+            config_accounts: None,
+            tick_array_mode,
+            tick_array_storage,
+            missing_tick_arrays: ArcSwap::from_pointee(missing_tick_arrays),
+            dropped_tick_arrays: ArcSwap::from_pointee(Vec::new()),
+            rediscovery_interval: AtomicU64::new(0),
+            refresh_count: AtomicU64::new(0),
+            missing_probed_at: AtomicU64::new(0),
+            unpublished_changes: AtomicBool::new(false),
+            published: ArcSwap::from_pointee(published),
+            refresh_lock: Mutex::new(()),
         };
 
         Ok((pool, failures))
     }
+
+    /// Returns the typed view of the last published snapshot, see `Pool::snapshot`.
+    /// 
+    /// Note: Only refreshes, rediscoveries and `publish` publish. Updates pushed into the accounts directly, e.g. 
+    /// through `AccountState::update` from a websocket feed, aren't reflected here until `publish` is called.
+    pub fn state(&self) -> OrcaWhirlpoolState {
+        self.published.load().state.clone()
+    }
+
+    /// Snapshots every account and publishes the snapshot, with its typed view, in a single swap. 
+    /// 
+    /// Refreshes publish by themselves, call this after pushing updates into the accounts directly (see `state`). 
+    /// `slot` is the slot the pushed data is from, if known. Waits for any refresh in flight, so snapshots are 
+    /// published in order.
+    pub async fn publish(&self, slot: Option<u64>) -> AnyResult<()> {
+        let _refresh_guard = self.refresh_lock.lock().await;
+        self.publish_unlocked(slot)
+    }
+
+    /// `publish` without taking the refresh lock. 
+    /// 
+    /// Callers must hold the refresh lock (or own the pool), otherwise snapshots could be published out of order.
+    pub(crate) fn publish_unlocked(&self, slot: Option<u64>) -> AnyResult<()> {
+        let published = PublishedState::new(
+            *self.whirlpool.pubkey(),
+            self.refresh_count.load(Ordering::Relaxed),
+            slot,
+            &self.accounts(),
+            self.oracle.as_ref().map(|oracle| oracle.pubkey()),
+            self.token_programs,
+        )?;
+        self.published.store(Arc::new(published));
+        self.unpublished_changes.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Gathers the accounts whose pubkeys are fixed for the pool's lifetime, i.e. everything but the tick arrays.
+    fn fixed_accounts(&self) -> Vec<Arc<dyn AccountState>> {
+        let mut accounts: Vec<Arc<dyn AccountState>> = vec![
+            self.whirlpool.clone(),
+            self.mint_a.clone(),
+            self.mint_b.clone(),
+        ];
+        if let Some(oracle) = &self.oracle {
+            accounts.push(oracle.clone());
+        }
+        if let Some(config_accounts) = &self.config_accounts {
+            accounts.extend(config_accounts.accounts());
+        }
+        accounts
+    }
+
+    /// Returns the tick arrays currently tracked by the pool, ordered by ascending start tick index.
+    ///
+    /// The set can change with each refresh (see `TickArrayMode::Windowed`) and rediscovery, this is a copy of
+    /// the current one.
+    pub fn tick_arrays(&self) -> Vec<Arc<TickArrayAccount>> {
+        self.tick_arrays.load().to_vec()
+    }
+
+    /// Returns the tick array PDAs that didn't exist on-chain when last probed.
+    pub fn missing_tick_arrays(&self) -> Vec<Pubkey> {
+        self.missing_tick_arrays.load().to_vec()
+    }
+
+    /// Returns whether the tick array starting at `start_tick_index` exists on-chain, as far as the pool knows.
+    ///
+    /// Tracked tick arrays exist. Otherwise we only answer `Some(false)` if the tick array was probed as of the
+    /// latest refresh, and return `None` when we don't know:
+    /// - In `TickArrayMode::Full` the missing tick arrays are only probed at construction and by `rediscover`,
+    ///   so an untracked one counts as missing until the next refresh that doesn't rediscover.
+    /// - In `TickArrayMode::Windowed` the window is re-probed on every refresh, so an untracked one inside it is
+    ///   missing, but beyond it we don't know.
+    ///
+    /// `OrcaLiquidityBuilder` probes the tick arrays we return `None` for over RPC.
+    pub fn tick_array_initialized(&self, start_tick_index: i32) -> AnyResult<Option<bool>> {
+        let tick_array_pubkey = pda::get_tick_array_address(self.whirlpool.pubkey(), start_tick_index)?;
+        if self.tick_arrays.load().iter().any(|ta| ta.pubkey() == &tick_array_pubkey) {
+            return Ok(Some(true));
+        }
+        match self.tick_array_mode {
+            TickArrayMode::Full => {
+                let probed = self.missing_probed_at.load(Ordering::Relaxed) == self.refresh_count.load(Ordering::Relaxed);
+                Ok(probed.then_some(false))
+            }
+            TickArrayMode::Windowed { radius } => {
+                Ok(self.tick_array_window(radius)?.contains(&tick_array_pubkey).then_some(false))
+            }
+        }
+    }
+
+    /// Sets how often `refresh` re-probes the missing tick arrays, in number of refreshes. 
+    /// 
+    /// E.g. an interval of 10 calls `rediscover` on every 10th refresh, and 0 (the default) disables it. 
+    /// 
+    /// Note: In `TickArrayMode::Full`, `tick_array_initialized` only reports a tick array missing after a refresh 
+    /// that rediscovered, so an interval of 1 is needed for it to always know.
+    pub fn set_rediscovery_interval(&self, refreshes: u64) {
+        self.rediscovery_interval.store(refreshes, Ordering::Relaxed);
+    }
+
+    /// Re-probes the tick array PDAs that were missing on-chain, and starts tracking any that an LP has 
+    /// since initialized. Returns the pubkeys of the newly tracked tick arrays.
+    /// 
+    /// The new `TickArrayAccount`s are inserted with an `ArcSwap::rcu`, so readers holding the previous set of 
+    /// tick arrays are unaffected, and a concurrent refresh or rediscovery can't lose the insertion. 
+    /// 
+    /// Note: As with the constructor, we batch into chunks of `max_accounts_per_rpc_call` ourselves, as for small 
+    /// tick spacings there are thousands of missing PDAs. In `TickArrayMode::Windowed` this is a no-op, as 
+    /// the window is fully re-probed on every refresh anyway.
+    pub async fn rediscover(
+        &self,
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+    ) -> AnyResult<Vec<Pubkey>> {
+        let _refresh_guard = self.refresh_lock.lock().await;
+        let mut report = RefreshReport::default();
+        self.rediscover_unlocked(rpc_client, RefreshPolicy::Abort, &mut report).await?;
+        if self.unpublished_changes.load(Ordering::Relaxed) {
+            self.publish_unlocked(report.slot)?;
+        }
+        Ok(report.changed)
+    }
+
+    /// `rediscover` without taking the refresh lock or publishing, for use within `refresh`. The discovered tick 
+    /// arrays are recorded as changed in `report`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(pool = %self.whirlpool.pubkey(), probed = tracing::field::Empty, discovered = tracing::field::Empty),
+    ))]
+    async fn rediscover_unlocked(
+        &self,
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+        policy: RefreshPolicy,
+        report: &mut RefreshReport,
+    ) -> AnyResult<()> {
+        if let TickArrayMode::Windowed { .. } = self.tick_array_mode {
+            return Ok(());
+        }
+
+        let missing_tick_arrays = self.missing_tick_arrays.load_full();
+        trace_record!("probed" = missing_tick_arrays.len());
+        let mut discovered: Vec<Arc<TickArrayAccount>> = Vec::new();
+        for chunk in missing_tick_arrays.chunks(rpc_client.max_accounts_per_rpc_call()) {
+            let request_start = Instant::now();
+            let rpc_response = rpc_client.get_multiple_accounts(chunk).await?;
+            report.record_rpc_call(request_start.elapsed(), rpc_response.slot);
+            let accounts_time = rpc_response.response_time;
+            for (pubkey, account_option) in chunk.iter().zip(rpc_response.result) {
+                if let Some(account) = account_option {
+                    let result = TickArrayAccount::new_initialized_from_bytes(
+                        self.tick_array_storage,
+                        *pubkey,
+                        account.into_bytes(),
+                        accounts_time,
+                    );
+                    match result {
+                        Ok(managed_account) => discovered.push(Arc::new(managed_account)),
+                        Err(e) => report.record(policy, *pubkey, Err(e))?,
+                    }
+                }
+            }
+        }
+        self.missing_probed_at.store(self.refresh_count.load(Ordering::Relaxed), Ordering::Relaxed);
+
+        if discovered.is_empty() {
+            return Ok(());
+        }
+
+        let discovered_pubkeys: HashSet<Pubkey> = discovered.iter().map(|ta| *ta.pubkey()).collect();
+        self.tick_arrays.rcu(|current| {
+            let mut next: Vec<Arc<TickArrayAccount>> = current
+                .iter()
+                .filter(|ta| !discovered_pubkeys.contains(ta.pubkey()))
+                .cloned()
+                .collect();
+            next.extend(discovered.iter().cloned());
+            next.sort_by_key(|ta| ta.start_tick_index());
+            next
+        });
+        self.unpublished_changes.store(true, Ordering::Relaxed);
+        self.missing_tick_arrays.rcu(|current| {
+            current
+                .iter()
+                .filter(|pk| !discovered_pubkeys.contains(pk))
+                .copied()
+                .collect::<Vec<Pubkey>>()
+        });
+
+        trace_record!("discovered" = discovered.len());
+        report.changed.extend(discovered.iter().map(|ta| *ta.pubkey()));
+        Ok(())
+    }
+
+    /// Returns the tick array pubkeys in the window of size `radius` centred on the cached current tick.
+    fn tick_array_window(&self, radius: usize) -> AnyResult<Vec<Pubkey>> {
+        let whirlpool_data = self.whirlpool.get();
+        pda::get_tick_array_window_addresses(
+            self.whirlpool.pubkey(),
+            &whirlpool_data.tick_spacing,
+            whirlpool_data.tick_current_index,
+            radius,
+        )
+    }
+
+    /// Applies freshly fetched window data to the tracked tick arrays, re-centring the window if the 
+    /// whirlpool's current tick has moved, and atomically swaps in the new set.
+    /// 
+    /// Tick arrays that have left the window are dropped, and any that have appeared (either because the 
+    /// window moved or because an LP initialized them) are wrapped in a new `TickArrayAccount`. Both are recorded 
+    /// as changed in `report`. Untracked PDAs that don't exist aren't recorded at all.
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(pool = %self.whirlpool.pubkey(), radius, recentred = tracing::field::Empty),
+    ))]
+    async fn update_tick_array_window(
+        &self,
+        rpc_client: &dyn RpcProvider<AccountType = Account>,
+        tick_arrays: &[Arc<TickArrayAccount>],
+        tick_arrays_data: Vec<(Pubkey, Option<Vec<u8>>)>,
+        update_time: u64,
+        radius: usize,
+        policy: RefreshPolicy,
+        report: &mut RefreshReport,
+    ) -> AnyResult<()> {
+        let mut tracked: HashMap<Pubkey, Arc<TickArrayAccount>> = tick_arrays
+            .iter()
+            .map(|ta| (*ta.pubkey(), ta.clone()))
+            .collect();
+        let mut fetched: HashSet<Pubkey> = HashSet::new();
+
+        for (pubkey, bytes_option) in tick_arrays_data {
+            fetched.insert(pubkey);
+            self.apply_tick_array_data(
+                &mut tracked,
+                pubkey,
+                bytes_option,
+                update_time,
+                policy,
+                report,
+            )?;
+        }
+
+        // The whirlpool has already been updated, so this is the window around the new current tick.
+        let window = self.tick_array_window(radius)?;
+        let unfetched: Vec<Pubkey> = window.iter().filter(|pk| !fetched.contains(pk)).copied().collect();
+        trace_record!("recentred" = !unfetched.is_empty());
+        if !unfetched.is_empty() {
+            let request_start = Instant::now();
+            let rpc_response = rpc_client.get_multiple_accounts(&unfetched).await?;
+            report.record_rpc_call(request_start.elapsed(), rpc_response.slot);
+            let recentre_time = rpc_response.response_time;
+            for (pubkey, account_option) in unfetched.into_iter().zip(rpc_response.result) {
+                let bytes_option = account_option.map(|a| a.into_bytes());
+                self.apply_tick_array_data(
+                    &mut tracked,
+                    pubkey,
+                    bytes_option,
+                    recentre_time,
+                    policy,
+                    report,
+                )?;
+            }
+        }
+
+        // Tick arrays that were tracked but fell out of the window.
+        report.changed.extend(tracked.keys().filter(|pk| !window.contains(pk)).copied());
+
+        // Tick arrays dropped because they were closed, which `health` still has to report until the next refresh.
+        let dropped: Vec<Arc<TickArrayAccount>> = tick_arrays
+            .iter()
+            .filter(|ta| !tracked.contains_key(ta.pubkey()) && ta.lifecycle() == AccountLifecycle::Closed)
+            .cloned()
+            .collect();
+        self.dropped_tick_arrays.store(Arc::new(dropped));
+
+        let new_tick_arrays: Vec<Arc<TickArrayAccount>> =
+            window.iter().filter_map(|pk| tracked.get(pk).cloned()).collect();
+        self.tick_arrays.store(Arc::new(new_tick_arrays));
+        if report.has_changes() {
+            self.unpublished_changes.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Updates the tracked tick array at `pubkey` with freshly fetched data, creating a new `TickArrayAccount` 
+    /// if it wasn't tracked yet, or dropping it (as missing and changed) if it no longer exists on-chain.
+    fn apply_tick_array_data(
+        &self,
+        tracked: &mut HashMap<Pubkey, Arc<TickArrayAccount>>,
+        pubkey: Pubkey,
+        bytes_option: Option<Vec<u8>>,
+        update_time: u64,
+        policy: RefreshPolicy,
+        report: &mut RefreshReport,
+    ) -> AnyResult<()> {
+        match (bytes_option, tracked.get(&pubkey)) {
+            (bytes_option @ Some(_), Some(managed_account)) => {
+                self.apply_update(report, policy, managed_account.as_ref(), bytes_option, update_time)
+            }
+            (Some(bytes), None) => {
+                let result =
+                    TickArrayAccount::new_initialized_from_bytes(self.tick_array_storage, pubkey, bytes, update_time)
+                    .map(|managed_account| {
+                        tracked.insert(pubkey, Arc::new(managed_account));
+                        true
+                    });
+                report.record(policy, pubkey, result)
+            }
+            (None, tracked_account) => {
+                if let Some(managed_account) = tracked_account {
+                    // Holders of the dropped account can still tell it's gone.
+                    managed_account.mark_closed();
+                    tracked.remove(&pubkey);
+                    report.missing.push(pubkey);
+                    // Dropping it changes the tracked set, which needs publishing like any other change.
+                    report.changed.push(pubkey);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// `RefreshReport::apply`, flagging the changes as unpublished if the account changed.
+    fn apply_update(
+        &self,
+        report: &mut RefreshReport,
+        policy: RefreshPolicy,
+        account: &dyn AccountState,
+        bytes: Option<Vec<u8>>,
+        update_time: u64,
+    ) -> AnyResult<()> {
+        let changed = report.changed.len();
+        let result = report.apply(policy, account, bytes, update_time);
+        if report.changed.len() > changed {
+            self.unpublished_changes.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+/// Returns the start tick index of a tick array, whichever layout it's stored in.
+pub(crate) fn tick_array_start_index(tick_array: &TickArray) -> i32 {
+    match tick_array {
+        TickArray::FixedTickArray(fixed) => fixed.start_tick_index,
+        TickArray::DynamicTickArray(dynamic) => dynamic.start_tick_index,
+    }
 }
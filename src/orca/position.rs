@@ -0,0 +1,238 @@
+//! Defines the `OrcaPosition` and `OrcaPositionBundle` structs, managed wrappers around Orca liquidity positions.
+//! 
+//! A position on its own is just a liquidity amount, a tick range and some checkpoints, so everything 
+//! interesting (token amounts, uncollected fees and rewards) is computed against the state of the 
+//! position's `OrcaWhirlpool`, see `OrcaWhirlpoolState`.
+
+use crate::common::{
+    account::AccountData,
+    rpc::RpcProvider,
+    state::{AccountState, ManagedAccount},
+    types::AnyResult,
+};
+use crate::orca::{pda, state::OrcaWhirlpoolState};
+use anyhow::anyhow;
+use orca_whirlpools_client::{Position, PositionBundle};
+use orca_whirlpools_core::{
+    collect_fees_quote, collect_rewards_quote, is_position_in_range, try_get_token_estimates_from_liquidity,
+    CollectFeesQuote, CollectRewardsQuote, TickFacade, POSITION_BUNDLE_SIZE,
+};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+// --- Orca Position --- //
+
+/// A single Orca liquidity position, either standalone (owned through its position mint) or bundled.
+pub struct OrcaPosition {
+    pub position: Arc<ManagedAccount<Position>>,
+}
+
+impl OrcaPosition {
+    /// Asynchronously fetches the position owned through `position_mint` and constructs a new `OrcaPosition`.
+    pub async fn new_initialized_from_rpc<C: RpcProvider + Send + Sync>(
+        position_mint: &Pubkey,
+        rpc_provider: &C,
+    ) -> AnyResult<Self> {
+        let (position_pubkey, _) = pda::get_position_address(position_mint)?;
+        Self::new_initialized_from_address(&position_pubkey, rpc_provider).await
+    }
+
+    /// Asynchronously fetches the position at `position_pubkey` and constructs a new `OrcaPosition`.
+    /// 
+    /// Use this for bundled positions, whose address is derived from the bundle rather than a mint, 
+    /// see `OrcaPositionBundle::position_addresses`.
+    pub async fn new_initialized_from_address<C: RpcProvider + Send + Sync>(
+        position_pubkey: &Pubkey,
+        rpc_provider: &C,
+    ) -> AnyResult<Self> {
+        let position = ManagedAccount::<Position>::new_initialized_from_rpc(*position_pubkey, rpc_provider)
+            .await
+            .map_err(|e| anyhow!("Failed to fetch position account {}: {}", position_pubkey, e))?;
+        Ok(Self {
+            position: Arc::new(position),
+        })
+    }
+
+    /// Returns the pubkey of the position account.
+    pub fn pubkey(&self) -> &Pubkey {
+        self.position.pubkey()
+    }
+
+    /// Returns the pubkey of the whirlpool the position provides liquidity to.
+    pub fn whirlpool(&self) -> Pubkey {
+        self.position.get().whirlpool
+    }
+
+    /// Re-fetches the position account, e.g. after liquidity has been added or fees collected. Returns whether 
+    /// it changed. A closed position is marked so (see `AccountState::lifecycle`), which counts as a change.
+    pub async fn refresh(&self, rpc_client: &dyn RpcProvider<AccountType = Account>) -> AnyResult<bool> {
+        refresh_account(self.position.as_ref(), rpc_client).await
+    }
+
+    /// Returns true if the pool's current tick is inside the position's range, i.e. the position is earning fees.
+    pub fn is_in_range(&self, pool_state: &OrcaWhirlpoolState) -> AnyResult<bool> {
+        let position = self.position.get();
+        self.check_pool(&position, pool_state)?;
+        Ok(is_position_in_range(
+            pool_state.whirlpool.sqrt_price,
+            position.tick_lower_index,
+            position.tick_upper_index,
+        ))
+    }
+
+    /// Returns the amounts of token A and token B the position's liquidity is currently worth, 
+    /// i.e. what decreasing all of its liquidity would return (before slippage and transfer fees).
+    pub fn token_amounts(&self, pool_state: &OrcaWhirlpoolState) -> AnyResult<(u64, u64)> {
+        let position = self.position.get();
+        self.check_pool(&position, pool_state)?;
+        try_get_token_estimates_from_liquidity(
+            position.liquidity,
+            pool_state.whirlpool.sqrt_price,
+            position.tick_lower_index,
+            position.tick_upper_index,
+            false,
+        )
+        .map_err(|e| anyhow!("Failed to compute token amounts for position {}: {}", self.pubkey(), e))
+    }
+
+    /// Returns the fees the position has earned but not yet collected, including those accrued since its 
+    /// last on-chain update.
+    /// 
+    /// Note: The ticks at both ends of the range are needed, so unless the position has no liquidity, their tick
+    /// arrays must be in `pool_state`.
+    pub fn fees_owed(&self, pool_state: &OrcaWhirlpoolState) -> AnyResult<CollectFeesQuote> {
+        let position = self.position.get();
+        self.check_pool(&position, pool_state)?;
+        let (tick_lower, tick_upper) = self.range_ticks(&position, pool_state)?;
+        collect_fees_quote(
+            pool_state.whirlpool_facade(),
+            (**position).clone().into(),
+            tick_lower,
+            tick_upper,
+            None,
+            None,
+        )
+        .map_err(|e| anyhow!("Failed to compute fees owed for position {}: {}", self.pubkey(), e))
+    }
+
+    /// Returns the rewards the position has earned but not yet collected, as of unix timestamp `timestamp` (seconds).
+    /// 
+    /// Note: As with `fees_owed`, the tick arrays at both ends of the range must be in `pool_state`.
+    pub fn rewards_owed(&self, pool_state: &OrcaWhirlpoolState, timestamp: u64) -> AnyResult<CollectRewardsQuote> {
+        let position = self.position.get();
+        self.check_pool(&position, pool_state)?;
+        let (tick_lower, tick_upper) = self.range_ticks(&position, pool_state)?;
+        collect_rewards_quote(
+            pool_state.whirlpool_facade(),
+            (**position).clone().into(),
+            tick_lower,
+            tick_upper,
+            timestamp,
+            None,
+            None,
+            None,
+        )
+        .map_err(|e| anyhow!("Failed to compute rewards owed for position {}: {}", self.pubkey(), e))
+    }
+
+    /// Ensures we aren't valuing the position against some other pool's state.
+    fn check_pool(&self, position: &Position, pool_state: &OrcaWhirlpoolState) -> AnyResult<()> {
+        if position.whirlpool != pool_state.pubkey {
+            return Err(anyhow!(
+                "Position {} belongs to whirlpool {}, not {}",
+                self.pubkey(),
+                position.whirlpool,
+                pool_state.pubkey
+            ));
+        }
+        Ok(())
+    }
+
+    /// Looks up the ticks at the lower and upper ends of the position's range.
+    ///
+    /// Without liquidity nothing accrues, so the ticks don't matter and may not even be initialized.
+    fn range_ticks(&self, position: &Position, pool_state: &OrcaWhirlpoolState) -> AnyResult<(TickFacade, TickFacade)> {
+        if position.liquidity == 0 {
+            return Ok((TickFacade::default(), TickFacade::default()));
+        }
+        let tick = |tick_index: i32| {
+            pool_state.tick(tick_index).ok_or_else(|| {
+                anyhow!(
+                    "Tick array containing tick {} of position {} is not tracked by whirlpool {}",
+                    tick_index,
+                    self.pubkey(),
+                    pool_state.pubkey
+                )
+            })
+        };
+        Ok((tick(position.tick_lower_index)?, tick(position.tick_upper_index)?))
+    }
+}
+
+// --- Orca Position Bundle --- //
+
+/// An Orca position bundle, a single NFT holding up to `POSITION_BUNDLE_SIZE` positions.
+pub struct OrcaPositionBundle {
+    pub position_bundle: Arc<ManagedAccount<PositionBundle>>,
+}
+
+impl OrcaPositionBundle {
+    /// Asynchronously fetches the position bundle owned through `position_bundle_mint` and constructs a new `OrcaPositionBundle`.
+    pub async fn new_initialized_from_rpc<C: RpcProvider + Send + Sync>(
+        position_bundle_mint: &Pubkey,
+        rpc_provider: &C,
+    ) -> AnyResult<Self> {
+        let (position_bundle_pubkey, _) = pda::get_position_bundle_address(position_bundle_mint)?;
+        let position_bundle =
+            ManagedAccount::<PositionBundle>::new_initialized_from_rpc(position_bundle_pubkey, rpc_provider)
+                .await
+                .map_err(|e| anyhow!("Failed to fetch position bundle account {}: {}", position_bundle_pubkey, e))?;
+        Ok(Self {
+            position_bundle: Arc::new(position_bundle),
+        })
+    }
+
+    /// Returns the pubkey of the position bundle account.
+    pub fn pubkey(&self) -> &Pubkey {
+        self.position_bundle.pubkey()
+    }
+
+    /// Returns the indexes of the occupied slots in the bundle.
+    pub fn occupied_indexes(&self) -> Vec<u8> {
+        let bitmap = self.position_bundle.get().position_bitmap;
+        (0..POSITION_BUNDLE_SIZE)
+            .filter(|i| bitmap[i / 8] & (1 << (i % 8)) != 0)
+            .map(|i| i as u8)
+            .collect()
+    }
+
+    /// Returns the pubkeys of the positions in the occupied slots of the bundle, to be loaded with 
+    /// `OrcaPosition::new_initialized_from_address`.
+    pub fn position_addresses(&self) -> AnyResult<Vec<Pubkey>> {
+        let position_bundle_mint = self.position_bundle.get().position_bundle_mint;
+        self.occupied_indexes()
+            .into_iter()
+            .map(|i| pda::get_bundled_position_address(&position_bundle_mint, i).map(|(pubkey, _)| pubkey))
+            .collect()
+    }
+
+    /// Re-fetches the position bundle account, e.g. after a bundled position has been opened or closed. Returns 
+    /// whether it changed, marking the bundle closed if it no longer exists.
+    pub async fn refresh(&self, rpc_client: &dyn RpcProvider<AccountType = Account>) -> AnyResult<bool> {
+        refresh_account(self.position_bundle.as_ref(), rpc_client).await
+    }
+}
+
+/// Re-fetches a single account, returning whether it changed, or marking it closed if it no longer exists.
+async fn refresh_account(
+    account: &dyn AccountState,
+    rpc_client: &dyn RpcProvider<AccountType = Account>,
+) -> AnyResult<bool> {
+    // `get_multiple_accounts` tells a closed account apart from a failed call, unlike `get_account`.
+    let rpc_response = rpc_client.get_multiple_accounts(&[*account.pubkey()]).await?;
+    match rpc_response.result.into_iter().next().flatten() {
+        Some(data) => account.update(data.into_bytes(), rpc_response.response_time),
+        None => Ok(account.mark_closed()),
+    }
+}
@@ -0,0 +1,161 @@
+//! Swap quotes for Orca Whirlpools, computed from the cached account state.
+//!
+//! All the maths is the orca core crate's, we just pick the right tick arrays and pass the oracle along so
+//! that adaptive fee pools quote with the same dynamic fee the program will charge, along with any Token-2022
+//! transfer fees of the mints.
+
+use crate::common::types::AnyResult;
+use crate::orca::{pda, state::OrcaWhirlpoolState};
+use anyhow::anyhow;
+use orca_whirlpools_core::{
+    swap_quote_by_input_token, swap_quote_by_output_token, ExactInSwapQuote, ExactOutSwapQuote, TickArrays, TransferFee,
+    TICK_ARRAY_SIZE,
+};
+
+impl OrcaWhirlpoolState {
+    /// Returns the start tick indexes of the three tick arrays a swap in the given direction runs through,
+    /// in the order the swap instruction expects them.
+    ///
+    /// For b to a swaps we shift the current tick by one tick spacing, as the program does, so a price sat
+    /// on the last tick of an array starts in the next one.
+    pub fn swap_tick_array_start_indexes(&self, a_to_b: bool) -> [i32; 3] {
+        let tick_spacing = self.whirlpool.tick_spacing;
+        let shift = if a_to_b { 0 } else { tick_spacing as i32 };
+        let start_tick_index =
+            pda::get_tick_array_start_index(self.whirlpool.tick_current_index + shift, &tick_spacing);
+        let offset = TICK_ARRAY_SIZE as i32 * tick_spacing as i32;
+        let direction = if a_to_b { -1 } else { 1 };
+        [
+            start_tick_index,
+            start_tick_index + direction * offset,
+            start_tick_index + 2 * direction * offset,
+        ]
+    }
+
+    /// The three tick arrays a swap runs through, in the orca core crate's representation.
+    pub(crate) fn swap_tick_arrays(&self, a_to_b: bool) -> TickArrays {
+        let [first, second, third] = self.swap_tick_array_start_indexes(a_to_b);
+        TickArrays::Three(
+            self.tick_array_facade(first),
+            self.tick_array_facade(second),
+            self.tick_array_facade(third),
+        )
+    }
+
+    /// Quotes a swap of exactly `token_in` of the input token at unix timestamp `timestamp` (seconds).
+    ///
+    /// `epoch` picks the Token-2022 transfer fees, it's irrelevant if neither mint has one.
+    ///
+    /// `specified_token_a` is true if the input token is token A, i.e. the swap is a to b. The slippage
+    /// tolerance is in basis points and only affects `token_min_out`.
+    ///
+    /// Note: The swap is confined to the three tick arrays from `swap_tick_array_start_indexes` (as is the
+    /// swap instruction), so a trade too large to fit errors rather than quoting something untradeable.
+    pub fn swap_quote_by_input_token(
+        &self,
+        token_in: u64,
+        specified_token_a: bool,
+        slippage_tolerance_bps: u16,
+        timestamp: u64,
+        epoch: u64,
+    ) -> AnyResult<ExactInSwapQuote> {
+        let (transfer_fee_a, transfer_fee_b) = self.transfer_fees(epoch)?;
+        self.swap_quote_by_input_token_with_transfer_fees(
+            token_in,
+            specified_token_a,
+            slippage_tolerance_bps,
+            timestamp,
+            transfer_fee_a,
+            transfer_fee_b,
+        )
+    }
+
+    /// As `swap_quote_by_input_token`, but with the transfer fees given explicitly rather than read from the mints.
+    ///
+    /// Useful when a transfer doesn't happen as the quote assumes, e.g. the intermediate token of a two hop swap
+    /// moves between the pools' vaults once, so only one hop should charge its fee.
+    pub fn swap_quote_by_input_token_with_transfer_fees(
+        &self,
+        token_in: u64,
+        specified_token_a: bool,
+        slippage_tolerance_bps: u16,
+        timestamp: u64,
+        transfer_fee_a: Option<TransferFee>,
+        transfer_fee_b: Option<TransferFee>,
+    ) -> AnyResult<ExactInSwapQuote> {
+        self.check_oracle_loaded()?;
+        swap_quote_by_input_token(
+            token_in,
+            specified_token_a,
+            slippage_tolerance_bps,
+            self.whirlpool_facade(),
+            self.oracle_facade(),
+            self.swap_tick_arrays(specified_token_a),
+            timestamp,
+            transfer_fee_a,
+            transfer_fee_b,
+        )
+        .map_err(|e| anyhow!("Failed to quote swap on whirlpool {}: {}", self.pubkey, e))
+    }
+
+    /// Quotes a swap for exactly `token_out` of the output token at unix timestamp `timestamp` (seconds).
+    ///
+    /// `epoch` picks the Token-2022 transfer fees, it's irrelevant if neither mint has one.
+    ///
+    /// `specified_token_a` is true if the output token is token A, i.e. the swap is b to a. The slippage
+    /// tolerance is in basis points and only affects `token_max_in`.
+    pub fn swap_quote_by_output_token(
+        &self,
+        token_out: u64,
+        specified_token_a: bool,
+        slippage_tolerance_bps: u16,
+        timestamp: u64,
+        epoch: u64,
+    ) -> AnyResult<ExactOutSwapQuote> {
+        let (transfer_fee_a, transfer_fee_b) = self.transfer_fees(epoch)?;
+        self.swap_quote_by_output_token_with_transfer_fees(
+            token_out,
+            specified_token_a,
+            slippage_tolerance_bps,
+            timestamp,
+            transfer_fee_a,
+            transfer_fee_b,
+        )
+    }
+
+    /// As `swap_quote_by_output_token`, but with the transfer fees given explicitly rather than read from the mints.
+    pub fn swap_quote_by_output_token_with_transfer_fees(
+        &self,
+        token_out: u64,
+        specified_token_a: bool,
+        slippage_tolerance_bps: u16,
+        timestamp: u64,
+        transfer_fee_a: Option<TransferFee>,
+        transfer_fee_b: Option<TransferFee>,
+    ) -> AnyResult<ExactOutSwapQuote> {
+        self.check_oracle_loaded()?;
+        swap_quote_by_output_token(
+            token_out,
+            specified_token_a,
+            slippage_tolerance_bps,
+            self.whirlpool_facade(),
+            self.oracle_facade(),
+            self.swap_tick_arrays(!specified_token_a),
+            timestamp,
+            transfer_fee_a,
+            transfer_fee_b,
+        )
+        .map_err(|e| anyhow!("Failed to quote swap on whirlpool {}: {}", self.pubkey, e))
+    }
+
+    /// Adaptive fee pools can't be quoted without their oracle, so fail loudly rather than quote the static fee.
+    pub(crate) fn check_oracle_loaded(&self) -> AnyResult<()> {
+        if self.whirlpool_facade().is_initialized_with_adaptive_fee() && self.oracle.is_none() {
+            return Err(anyhow!(
+                "Whirlpool {} has an adaptive fee but its oracle account isn't loaded",
+                self.pubkey
+            ));
+        }
+        Ok(())
+    }
+}
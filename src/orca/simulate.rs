@@ -0,0 +1,445 @@
+//! Offline swap simulation against the cached state of an `OrcaWhirlpool`.
+//!
+//! Quotes only tell us the amounts, here we replay the program's swap loop on cloned accounts and return the
+//! post-trade state: sqrt price, tick, liquidity, fee and reward growth, protocol fees owed, the crossed ticks'
+//! growth checkpoints and the oracle's adaptive fee variables. The result is itself an `OrcaWhirlpoolState`, so
+//! hypothetical trades can be chained, e.g. in a backtest.
+//!
+//! The maths is the orca core crate's (and our port of its `FeeRateManager`), the bookkeeping mirrors the
+//! program's `swap` and `update_after_swap`.
+
+use crate::common::types::AnyResult;
+use crate::orca::{fee::FeeRateManager, pda, pool::OrcaWhirlpool, state::OrcaWhirlpoolState};
+use anyhow::anyhow;
+use orca_whirlpools_client::{AdaptiveFeeVariables, TickArray, Whirlpool};
+use orca_whirlpools_core::{
+    sqrt_price_to_tick_index, tick_index_to_sqrt_price, try_apply_swap_fee, try_get_amount_delta_a,
+    try_get_amount_delta_b, try_get_next_sqrt_price_from_a, try_get_next_sqrt_price_from_b,
+    try_reverse_apply_swap_fee, CoreError, TickArraySequence, TickFacade, AMOUNT_EXCEEDS_MAX_U64, MAX_SQRT_PRICE,
+    MIN_SQRT_PRICE, NUM_REWARDS,
+};
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+/// The protocol fee rate is in basis points of the trade fee.
+const PROTOCOL_FEE_RATE_MUL_VALUE: u128 = 10_000;
+
+/// A tick the simulated swap crossed, with its state either side of the crossing.
+#[derive(Debug, Clone, Copy)]
+pub struct CrossedTick {
+    pub tick_index: i32,
+    pub before: TickFacade,
+    pub after: TickFacade,
+}
+
+/// The outcome of a simulated swap.
+#[derive(Debug, Clone)]
+pub struct SimulatedSwap {
+    /// The amounts of token A and B that moved in or out of the vaults, i.e. before any transfer fees.
+    pub token_a: u64,
+    pub token_b: u64,
+    /// The total trade fee paid in the input token, including the protocol's cut.
+    pub trade_fee: u64,
+    /// The protocol's cut of the trade fee.
+    pub protocol_fee: u64,
+    /// The crossed ticks in the order they were crossed.
+    pub crossed_ticks: Vec<CrossedTick>,
+    /// The whirlpool as it was before the swap, for diffing against `state.whirlpool`.
+    pub whirlpool_before: Arc<Whirlpool>,
+    /// The post-trade state. Untouched accounts share their `Arc`s with the pre-trade state.
+    pub state: OrcaWhirlpoolState,
+}
+
+impl SimulatedSwap {
+    /// Returns the pubkeys of the accounts the swap would write: the whirlpool, the tick arrays holding crossed
+    /// ticks, and the oracle of adaptive fee pools.
+    pub fn changed_accounts(&self) -> AnyResult<Vec<Pubkey>> {
+        let mut changed = vec![self.state.pubkey];
+        let tick_spacing = self.state.whirlpool.tick_spacing;
+        for crossed in &self.crossed_ticks {
+            let start_tick_index = pda::get_tick_array_start_index(crossed.tick_index, &tick_spacing);
+            let tick_array = pda::get_tick_array_address(&self.state.pubkey, start_tick_index)?;
+            if !changed.contains(&tick_array) {
+                changed.push(tick_array);
+            }
+        }
+        if self.state.adaptive_fee_info().is_some() {
+            changed.push(pda::get_oracle_address(&self.state.pubkey)?.0);
+        }
+        Ok(changed)
+    }
+}
+
+impl OrcaWhirlpool {
+    /// Simulates a swap against the pool's current state, see `OrcaWhirlpoolState::simulate_swap`.
+    pub fn simulate_swap(
+        &self,
+        amount: u64,
+        a_to_b: bool,
+        amount_specified_is_input: bool,
+        sqrt_price_limit: u128,
+        timestamp: u64,
+    ) -> AnyResult<SimulatedSwap> {
+        self.state().simulate_swap(amount, a_to_b, amount_specified_is_input, sqrt_price_limit, timestamp)
+    }
+}
+
+impl OrcaWhirlpoolState {
+    /// Simulates a swap at unix timestamp `timestamp` (seconds), as the swap_v2 instruction would execute it.
+    ///
+    /// `amount` is what reaches (exact in) or leaves (exact out) the vault, i.e. net of transfer fees.
+    /// `sqrt_price_limit` of 0 means no limit. As with the instruction, the swap is confined to the three tick
+    /// arrays from `swap_tick_array_start_indexes` and errors if it runs out of them.
+    pub fn simulate_swap(
+        &self,
+        amount: u64,
+        a_to_b: bool,
+        amount_specified_is_input: bool,
+        sqrt_price_limit: u128,
+        timestamp: u64,
+    ) -> AnyResult<SimulatedSwap> {
+        self.check_oracle_loaded()?;
+        let specified_input = amount_specified_is_input;
+        let whirlpool = &self.whirlpool;
+        let err = |e: CoreError| anyhow!("Failed to simulate swap on whirlpool {}: {}", self.pubkey, e);
+
+        let sqrt_price_limit = match sqrt_price_limit {
+            0 if a_to_b => MIN_SQRT_PRICE,
+            0 => MAX_SQRT_PRICE,
+            limit => limit,
+        };
+        if !(MIN_SQRT_PRICE..=MAX_SQRT_PRICE).contains(&sqrt_price_limit) {
+            return Err(anyhow!("Sqrt price limit {} is out of bounds", sqrt_price_limit));
+        }
+        if (a_to_b && sqrt_price_limit >= whirlpool.sqrt_price) || (!a_to_b && sqrt_price_limit <= whirlpool.sqrt_price) {
+            return Err(anyhow!("Sqrt price limit {} is on the wrong side of the current price", sqrt_price_limit));
+        }
+        if amount == 0 {
+            return Err(anyhow!("Can't simulate a swap of 0"));
+        }
+        if timestamp < whirlpool.reward_last_updated_timestamp {
+            return Err(anyhow!(
+                "Timestamp {} is before the whirlpool's last update at {}",
+                timestamp,
+                whirlpool.reward_last_updated_timestamp
+            ));
+        }
+
+        let mut next = (**whirlpool).clone();
+        update_reward_growths(&mut next, timestamp)?;
+        let reward_growths = next.reward_infos.each_ref().map(|reward_info| reward_info.growth_global_x64);
+
+        let tick_sequence = TickArraySequence::new(self.swap_tick_arrays(a_to_b).into(), whirlpool.tick_spacing)
+            .map_err(err)?;
+        let mut tick_arrays = self.tick_arrays.clone();
+        let mut crossed_ticks = Vec::new();
+
+        let adaptive_fee_info = self.adaptive_fee_info();
+        let mut fee_rate_manager = FeeRateManager::new(
+            a_to_b,
+            whirlpool.tick_current_index,
+            timestamp,
+            whirlpool.fee_rate,
+            &adaptive_fee_info,
+        )?;
+
+        let mut amount_remaining = amount;
+        let mut amount_calculated = 0u64;
+        let mut trade_fee = 0u64;
+        let mut protocol_fee = 0u64;
+        while amount_remaining > 0 && sqrt_price_limit != next.sqrt_price {
+            let (next_tick, next_tick_index) = if a_to_b {
+                tick_sequence.prev_initialized_tick(next.tick_current_index)
+            } else {
+                tick_sequence.next_initialized_tick(next.tick_current_index)
+            }
+            .map_err(err)?;
+            let next_tick_sqrt_price = tick_index_to_sqrt_price(next_tick_index);
+            let target_sqrt_price = if a_to_b {
+                next_tick_sqrt_price.max(sqrt_price_limit)
+            } else {
+                next_tick_sqrt_price.min(sqrt_price_limit)
+            };
+
+            loop {
+                fee_rate_manager.update_volatility_accumulator();
+                let total_fee_rate = fee_rate_manager.get_total_fee_rate();
+                let (bounded_sqrt_price_target, adaptive_fee_update_skipped) =
+                    fee_rate_manager.get_bounded_sqrt_price_target(target_sqrt_price, next.liquidity);
+
+                let step = compute_swap_step(
+                    amount_remaining,
+                    total_fee_rate,
+                    next.liquidity,
+                    next.sqrt_price,
+                    bounded_sqrt_price_target,
+                    a_to_b,
+                    specified_input,
+                )
+                .map_err(err)?;
+
+                if specified_input {
+                    amount_remaining = amount_remaining
+                        .checked_sub(step.amount_in + step.fee_amount)
+                        .ok_or_else(|| anyhow!("Swap simulation overflowed"))?;
+                    amount_calculated = amount_calculated
+                        .checked_add(step.amount_out)
+                        .ok_or_else(|| anyhow!("Swap simulation overflowed"))?;
+                } else {
+                    amount_remaining = amount_remaining
+                        .checked_sub(step.amount_out)
+                        .ok_or_else(|| anyhow!("Swap simulation overflowed"))?;
+                    amount_calculated = amount_calculated
+                        .checked_add(step.amount_in + step.fee_amount)
+                        .ok_or_else(|| anyhow!("Swap simulation overflowed"))?;
+                }
+
+                // Fees are charged in the input token, the protocol takes its cut and the rest grows the LPs' share.
+                trade_fee += step.fee_amount;
+                let step_protocol_fee =
+                    (step.fee_amount as u128 * next.protocol_fee_rate as u128 / PROTOCOL_FEE_RATE_MUL_VALUE) as u64;
+                protocol_fee += step_protocol_fee;
+                let lp_fee = step.fee_amount - step_protocol_fee;
+                let fee_growth_delta = ((lp_fee as u128) << 64).checked_div(next.liquidity).unwrap_or(0);
+                if a_to_b {
+                    next.protocol_fee_owed_a = next.protocol_fee_owed_a.wrapping_add(step_protocol_fee);
+                    next.fee_growth_global_a = next.fee_growth_global_a.wrapping_add(fee_growth_delta);
+                } else {
+                    next.protocol_fee_owed_b = next.protocol_fee_owed_b.wrapping_add(step_protocol_fee);
+                    next.fee_growth_global_b = next.fee_growth_global_b.wrapping_add(fee_growth_delta);
+                }
+
+                if step.next_sqrt_price == next_tick_sqrt_price {
+                    if let Some(tick) = next_tick.filter(|tick| tick.initialized) {
+                        let crossed = cross_tick(
+                            &mut tick_arrays,
+                            next_tick_index,
+                            whirlpool.tick_spacing,
+                            *tick,
+                            (next.fee_growth_global_a, next.fee_growth_global_b),
+                            reward_growths,
+                            &next,
+                        );
+                        crossed_ticks.push(crossed);
+                        next.liquidity = next_liquidity(next.liquidity, tick.liquidity_net, a_to_b)
+                            .ok_or_else(|| anyhow!("Liquidity over/underflowed crossing tick {}", next_tick_index))?;
+                    }
+                    next.tick_current_index = if a_to_b { next_tick_index - 1 } else { next_tick_index };
+                } else if step.next_sqrt_price != next.sqrt_price {
+                    next.tick_current_index = sqrt_price_to_tick_index(step.next_sqrt_price);
+                }
+                next.sqrt_price = step.next_sqrt_price;
+
+                if !adaptive_fee_update_skipped {
+                    fee_rate_manager.advance_tick_group();
+                } else {
+                    fee_rate_manager.advance_tick_group_after_skip(next.sqrt_price, next_tick_sqrt_price, next_tick_index);
+                }
+
+                if amount_remaining == 0 || next.sqrt_price == target_sqrt_price {
+                    break;
+                }
+            }
+        }
+
+        fee_rate_manager.update_major_swap_timestamp(timestamp, whirlpool.sqrt_price, next.sqrt_price);
+        let oracle = match (fee_rate_manager.adaptive_fee_info(), &self.oracle) {
+            (Some(adaptive_fee_info), Some(oracle)) => {
+                let mut oracle = (**oracle).clone();
+                let variables = adaptive_fee_info.variables;
+                oracle.adaptive_fee_variables = AdaptiveFeeVariables {
+                    last_reference_update_timestamp: variables.last_reference_update_timestamp,
+                    last_major_swap_timestamp: variables.last_major_swap_timestamp,
+                    volatility_reference: variables.volatility_reference,
+                    tick_group_index_reference: variables.tick_group_index_reference,
+                    volatility_accumulator: variables.volatility_accumulator,
+                    reserved: oracle.adaptive_fee_variables.reserved,
+                };
+                Some(Arc::new(oracle))
+            }
+            (_, oracle) => oracle.clone(),
+        };
+
+        let swapped = amount - amount_remaining;
+        let (token_a, token_b) = if a_to_b == specified_input {
+            (swapped, amount_calculated)
+        } else {
+            (amount_calculated, swapped)
+        };
+        Ok(SimulatedSwap {
+            token_a,
+            token_b,
+            trade_fee,
+            protocol_fee,
+            crossed_ticks,
+            whirlpool_before: whirlpool.clone(),
+            state: OrcaWhirlpoolState {
+                whirlpool: Arc::new(next),
+                tick_arrays,
+                oracle,
+                ..self.clone()
+            },
+        })
+    }
+}
+
+/// Accrues reward emissions since the whirlpool's last update, as the program does at the start of a swap.
+fn update_reward_growths(whirlpool: &mut Whirlpool, timestamp: u64) -> AnyResult<()> {
+    let time_delta = timestamp - whirlpool.reward_last_updated_timestamp;
+    if whirlpool.liquidity > 0 && time_delta > 0 {
+        for reward_info in whirlpool.reward_infos.iter_mut().take(NUM_REWARDS) {
+            if reward_info.mint == Pubkey::default() {
+                continue;
+            }
+            let growth_delta = (time_delta as u128)
+                .checked_mul(reward_info.emissions_per_second_x64)
+                .map(|emissions| emissions / whirlpool.liquidity)
+                .ok_or_else(|| anyhow!("Reward growth overflowed"))?;
+            reward_info.growth_global_x64 = reward_info.growth_global_x64.wrapping_add(growth_delta);
+        }
+    }
+    whirlpool.reward_last_updated_timestamp = timestamp;
+    Ok(())
+}
+
+/// Flips the growth checkpoints of the tick at `tick_index` to the other side of the current price, writing it
+/// into its (cloned) tick array, and returns the crossing.
+fn cross_tick(
+    tick_arrays: &mut [Arc<TickArray>],
+    tick_index: i32,
+    tick_spacing: u16,
+    before: TickFacade,
+    (fee_growth_global_a, fee_growth_global_b): (u128, u128),
+    reward_growths: [u128; NUM_REWARDS],
+    whirlpool: &Whirlpool,
+) -> CrossedTick {
+    let mut after = before;
+    after.fee_growth_outside_a = fee_growth_global_a.wrapping_sub(before.fee_growth_outside_a);
+    after.fee_growth_outside_b = fee_growth_global_b.wrapping_sub(before.fee_growth_outside_b);
+    for (i, reward_info) in whirlpool.reward_infos.iter().enumerate() {
+        if reward_info.mint != Pubkey::default() {
+            after.reward_growths_outside[i] = reward_growths[i].wrapping_sub(before.reward_growths_outside[i]);
+        }
+    }
+
+    let start_tick_index = pda::get_tick_array_start_index(tick_index, &tick_spacing);
+    let offset = ((tick_index - start_tick_index) / tick_spacing as i32) as usize;
+    let tick_array = tick_arrays
+        .iter_mut()
+        .find(|ta| crate::orca::pool::tick_array_start_index(ta) == start_tick_index);
+    // An initialized tick can only come from a tracked tick array, untracked ones are treated as empty.
+    if let Some(tick_array) = tick_array {
+        match Arc::make_mut(tick_array) {
+            TickArray::FixedTickArray(fixed) => {
+                let tick = &mut fixed.ticks[offset];
+                tick.fee_growth_outside_a = after.fee_growth_outside_a;
+                tick.fee_growth_outside_b = after.fee_growth_outside_b;
+                tick.reward_growths_outside = after.reward_growths_outside;
+            }
+            TickArray::DynamicTickArray(dynamic) => {
+                if let orca_whirlpools_client::DynamicTick::Initialized(tick) = &mut dynamic.ticks[offset] {
+                    tick.fee_growth_outside_a = after.fee_growth_outside_a;
+                    tick.fee_growth_outside_b = after.fee_growth_outside_b;
+                    tick.reward_growths_outside = after.reward_growths_outside;
+                }
+            }
+        }
+    }
+    CrossedTick { tick_index, before, after }
+}
+
+/// Applies a crossed tick's net liquidity, which is signed for a left to right (b to a) crossing.
+fn next_liquidity(liquidity: u128, liquidity_net: i128, a_to_b: bool) -> Option<u128> {
+    let liquidity_net = if a_to_b { -liquidity_net } else { liquidity_net };
+    if liquidity_net < 0 {
+        liquidity.checked_sub(liquidity_net.unsigned_abs())
+    } else {
+        liquidity.checked_add(liquidity_net.unsigned_abs())
+    }
+}
+
+// --- Swap step maths, ported from the orca core crate where it's private --- //
+
+struct SwapStep {
+    amount_in: u64,
+    amount_out: u64,
+    next_sqrt_price: u128,
+    fee_amount: u64,
+}
+
+fn compute_swap_step(
+    amount_remaining: u64,
+    fee_rate: u32,
+    liquidity: u128,
+    sqrt_price: u128,
+    target_sqrt_price: u128,
+    a_to_b: bool,
+    specified_input: bool,
+) -> Result<SwapStep, CoreError> {
+    // Overflowing the fixed delta just means the step can't reach the target, any other error is fatal.
+    let initial_amount_fixed_delta = amount_fixed_delta(sqrt_price, target_sqrt_price, liquidity, a_to_b, specified_input);
+    let is_initial_amount_fixed_overflow = initial_amount_fixed_delta == Err(AMOUNT_EXCEEDS_MAX_U64);
+
+    let amount_calculated = if specified_input {
+        try_apply_swap_fee(amount_remaining, fee_rate)?
+    } else {
+        amount_remaining
+    };
+
+    let next_sqrt_price = if !is_initial_amount_fixed_overflow && initial_amount_fixed_delta? <= amount_calculated {
+        target_sqrt_price
+    } else if specified_input == a_to_b {
+        try_get_next_sqrt_price_from_a(sqrt_price, liquidity, amount_calculated, specified_input)?
+    } else {
+        try_get_next_sqrt_price_from_b(sqrt_price, liquidity, amount_calculated, specified_input)?
+    };
+    let is_max_swap = next_sqrt_price == target_sqrt_price;
+
+    let amount_unfixed_delta = if specified_input == a_to_b {
+        try_get_amount_delta_b(sqrt_price, next_sqrt_price, liquidity, !specified_input)?
+    } else {
+        try_get_amount_delta_a(sqrt_price, next_sqrt_price, liquidity, !specified_input)?
+    };
+    let amount_fixed_delta = if !is_max_swap || is_initial_amount_fixed_overflow {
+        amount_fixed_delta(sqrt_price, next_sqrt_price, liquidity, a_to_b, specified_input)?
+    } else {
+        initial_amount_fixed_delta?
+    };
+
+    let (amount_in, mut amount_out) = if specified_input {
+        (amount_fixed_delta, amount_unfixed_delta)
+    } else {
+        (amount_unfixed_delta, amount_fixed_delta)
+    };
+    if !specified_input && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+
+    let fee_amount = if specified_input && !is_max_swap {
+        amount_remaining - amount_in
+    } else {
+        try_reverse_apply_swap_fee(amount_in, fee_rate)? - amount_in
+    };
+
+    Ok(SwapStep {
+        amount_in,
+        amount_out,
+        next_sqrt_price,
+        fee_amount,
+    })
+}
+
+/// The delta of the specified token between two prices.
+fn amount_fixed_delta(
+    sqrt_price: u128,
+    target_sqrt_price: u128,
+    liquidity: u128,
+    a_to_b: bool,
+    specified_input: bool,
+) -> Result<u64, CoreError> {
+    if a_to_b == specified_input {
+        try_get_amount_delta_a(sqrt_price, target_sqrt_price, liquidity, specified_input)
+    } else {
+        try_get_amount_delta_b(sqrt_price, target_sqrt_price, liquidity, specified_input)
+    }
+}
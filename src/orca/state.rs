@@ -0,0 +1,149 @@
+//! Defines `OrcaWhirlpoolState`, a typed, read-only view of the deserialized accounts of an `OrcaWhirlpool`.
+//!
+//! Quoting needs the whirlpool, its tick arrays and its oracle together, so rather than loading each
+//! `ManagedAccount` all over the place we build this view from the pool's published snapshot, which also
+//! guarantees the accounts come from the same refresh (see `Pool::snapshot`).
+
+use crate::common::{snapshot::PoolSnapshot, types::AnyResult};
+use crate::mint::extensions::MintExtensions;
+use crate::orca::{pda, pool::tick_array_start_index};
+use anyhow::anyhow;
+use orca_whirlpools_client::{Oracle, TickArray, Whirlpool};
+use orca_whirlpools_core::{
+    AdaptiveFeeInfo, OracleFacade, TickArrayFacade, TickFacade, TransferFee, WhirlpoolFacade, TICK_ARRAY_SIZE,
+};
+use solana_sdk::pubkey::Pubkey;
+use spl_token::state::Mint;
+use std::sync::Arc;
+
+/// A typed view of the deserialized accounts of an `OrcaWhirlpool`.
+///
+/// Cloning is cheap, everything is behind an `Arc`.
+#[derive(Debug, Clone)]
+pub struct OrcaWhirlpoolState {
+    /// The pubkey of the whirlpool account.
+    pub pubkey: Pubkey,
+    pub whirlpool: Arc<Whirlpool>,
+    /// The tracked tick arrays, ordered by ascending start tick index.
+    pub tick_arrays: Vec<Arc<TickArray>>,
+    pub oracle: Option<Arc<Oracle>>,
+    pub mint_a: Arc<Mint>,
+    pub mint_b: Arc<Mint>,
+    /// The raw mint account data, kept for the Token-2022 extensions `Mint` doesn't carry.
+    pub mint_a_data: Arc<Vec<u8>>,
+    pub mint_b_data: Arc<Vec<u8>>,
+    /// The owners of the mint accounts, i.e. the token programs of tokens A and B.
+    pub token_program_a: Pubkey,
+    pub token_program_b: Pubkey,
+}
+
+impl OrcaWhirlpoolState {
+    /// Builds the view from a snapshot of an `OrcaWhirlpool`'s accounts, see `Pool::snapshot`.
+    ///
+    /// `oracle` is the pubkey of the pool's oracle account, if it has one, and `token_programs` the owners of 
+    /// the mint accounts of tokens A and B, which snapshots don't carry.
+    pub fn from_snapshot(
+        snapshot: &PoolSnapshot,
+        oracle: Option<&Pubkey>,
+        token_programs: (Pubkey, Pubkey),
+    ) -> AnyResult<Self> {
+        let whirlpool: Arc<Whirlpool> = snapshot
+            .get(&snapshot.pubkey)
+            .ok_or_else(|| anyhow!("Snapshot of {} has no whirlpool", snapshot.pubkey))?;
+        let mint = |mint_pubkey: &Pubkey| -> AnyResult<(Arc<Mint>, Arc<Vec<u8>>)> {
+            let account = snapshot
+                .account(mint_pubkey)
+                .ok_or_else(|| anyhow!("Snapshot of {} has no mint {}", snapshot.pubkey, mint_pubkey))?;
+            let mint = account
+                .downcast::<Mint>()
+                .ok_or_else(|| anyhow!("Account {} of the snapshot isn't a mint", mint_pubkey))?;
+            Ok((mint, account.bytes.clone()))
+        };
+        let (mint_a, mint_a_data) = mint(&whirlpool.token_mint_a)?;
+        let (mint_b, mint_b_data) = mint(&whirlpool.token_mint_b)?;
+        Ok(Self {
+            pubkey: snapshot.pubkey,
+            tick_arrays: snapshot.all::<TickArray>(),
+            oracle: oracle.and_then(|oracle| snapshot.get::<Oracle>(oracle)),
+            whirlpool,
+            mint_a,
+            mint_b,
+            mint_a_data,
+            mint_b_data,
+            token_program_a: token_programs.0,
+            token_program_b: token_programs.1,
+        })
+    }
+
+    /// Converts the whirlpool into the orca core crate's representation, used for all the quoting maths.
+    pub fn whirlpool_facade(&self) -> WhirlpoolFacade {
+        (*self.whirlpool).clone().into()
+    }
+
+    /// Returns the oracle in the orca core crate's representation, but only for adaptive fee pools.
+    ///
+    /// The core quoting functions reject an oracle for a static fee pool, so we filter here.
+    pub fn oracle_facade(&self) -> Option<OracleFacade> {
+        if !self.whirlpool_facade().is_initialized_with_adaptive_fee() {
+            return None;
+        }
+        self.oracle.as_ref().map(|oracle| (**oracle).clone().into())
+    }
+
+    /// Returns the adaptive fee constants and variables of the pool, `None` for static fee pools.
+    pub fn adaptive_fee_info(&self) -> Option<AdaptiveFeeInfo> {
+        self.oracle_facade().map(|oracle| oracle.into())
+    }
+
+    /// Parses the Token-2022 extensions of token A's mint.
+    pub fn mint_a_extensions(&self) -> AnyResult<MintExtensions> {
+        MintExtensions::from_account(&self.token_program_a, &self.mint_a_data)
+    }
+
+    /// Parses the Token-2022 extensions of token B's mint.
+    pub fn mint_b_extensions(&self) -> AnyResult<MintExtensions> {
+        MintExtensions::from_account(&self.token_program_b, &self.mint_b_data)
+    }
+
+    /// Returns the Token-2022 transfer fees of tokens A and B in effect at `epoch`, `None` for tokens without one.
+    pub fn transfer_fees(&self, epoch: u64) -> AnyResult<(Option<TransferFee>, Option<TransferFee>)> {
+        Ok((
+            self.mint_a_extensions()?.transfer_fee(epoch),
+            self.mint_b_extensions()?.transfer_fee(epoch),
+        ))
+    }
+
+    /// Returns the tracked tick array starting at `start_tick_index`, if any.
+    pub fn tick_array(&self, start_tick_index: i32) -> Option<&Arc<TickArray>> {
+        self.tick_arrays
+            .iter()
+            .find(|ta| tick_array_start_index(ta) == start_tick_index)
+    }
+
+    /// Returns the tick array starting at `start_tick_index` in the orca core crate's representation.
+    ///
+    /// Untracked tick arrays (uninitialized, or outside the window) are treated as empty, i.e. all ticks
+    /// uninitialized, which is exactly how the program treats an uninitialized tick array in a swap.
+    pub fn tick_array_facade(&self, start_tick_index: i32) -> TickArrayFacade {
+        match self.tick_array(start_tick_index) {
+            Some(tick_array) => (**tick_array).clone().into(),
+            None => TickArrayFacade {
+                start_tick_index,
+                ticks: [TickFacade::default(); TICK_ARRAY_SIZE],
+            },
+        }
+    }
+
+    /// Returns the tick at `tick_index`, `None` if its tick array isn't tracked.
+    ///
+    /// Note: `tick_index` should be a multiple of the tick spacing, otherwise we return the tick below it.
+    pub fn tick(&self, tick_index: i32) -> Option<TickFacade> {
+        let tick_spacing = self.whirlpool.tick_spacing;
+        let start_tick_index = pda::get_tick_array_start_index(tick_index, &tick_spacing);
+        let offset = ((tick_index - start_tick_index) / tick_spacing as i32) as usize;
+        self.tick_array(start_tick_index).map(|tick_array| match &**tick_array {
+            TickArray::FixedTickArray(fixed) => fixed.ticks[offset].clone().into(),
+            TickArray::DynamicTickArray(dynamic) => dynamic.ticks[offset].clone().into(),
+        })
+    }
+}
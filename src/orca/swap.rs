@@ -0,0 +1,330 @@
+//! Builds Orca `swap_v2` instructions from the cached state of an `OrcaWhirlpool`.
+//!
+//! The builder picks the three tick arrays the swap runs through, the oracle and the token programs from the
+//! pool's state, quotes the swap with the same tick arrays, and sets the instruction's slippage threshold from
+//! that quote. So the instruction and the quote returned alongside it always agree, which is why a sqrt price
+//! limit, which the quote can't account for, is rejected.
+
+use crate::common::types::AnyResult;
+use crate::mint::extensions::MintExtensions;
+use crate::orca::{pda, pool::OrcaWhirlpool, state::OrcaWhirlpoolState};
+use anyhow::anyhow;
+use orca_whirlpools_client::{
+    AccountsType, RemainingAccountsInfo, RemainingAccountsSlice, SwapV2, SwapV2InstructionArgs,
+};
+use orca_whirlpools_core::{ExactInSwapQuote, ExactOutSwapQuote};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey,
+    pubkey::Pubkey,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The SPL memo program, which `swap_v2` takes for Token-2022 mints that require transfer memos.
+pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// The quote of a swap, exact in or exact out depending on how the amount was specified.
+#[derive(Debug, Clone)]
+pub enum OrcaSwapQuote {
+    ExactIn(ExactInSwapQuote),
+    ExactOut(ExactOutSwapQuote),
+}
+
+impl OrcaSwapQuote {
+    /// The amount of the input token the swap is expected to take, including transfer fees.
+    pub fn token_in(&self) -> u64 {
+        match self {
+            OrcaSwapQuote::ExactIn(quote) => quote.token_in,
+            OrcaSwapQuote::ExactOut(quote) => quote.token_est_in,
+        }
+    }
+
+    /// The amount of the output token the swap is expected to give, net of transfer fees.
+    pub fn token_out(&self) -> u64 {
+        match self {
+            OrcaSwapQuote::ExactIn(quote) => quote.token_est_out,
+            OrcaSwapQuote::ExactOut(quote) => quote.token_out,
+        }
+    }
+
+    /// The threshold on the unspecified side of the swap, i.e. the `other_amount_threshold` instruction arg.
+    pub fn other_amount_threshold(&self) -> u64 {
+        match self {
+            OrcaSwapQuote::ExactIn(quote) => quote.token_min_out,
+            OrcaSwapQuote::ExactOut(quote) => quote.token_max_in,
+        }
+    }
+}
+
+/// A built swap instruction along with the quote it was built from.
+#[derive(Debug, Clone)]
+pub struct OrcaSwap {
+    pub instruction: Instruction,
+    pub quote: OrcaSwapQuote,
+}
+
+/// Builder for a `swap_v2` instruction on a single whirlpool.
+///
+/// Required: the token authority and both of its token accounts. Everything else has a default, see the setters.
+#[derive(Debug, Clone)]
+pub struct OrcaSwapBuilder {
+    state: OrcaWhirlpoolState,
+    a_to_b: bool,
+    amount: u64,
+    amount_specified_is_input: bool,
+    slippage_tolerance_bps: u16,
+    sqrt_price_limit: u128,
+    timestamp: Option<u64>,
+    epoch: Option<u64>,
+    token_authority: Option<Pubkey>,
+    token_owner_account_a: Option<Pubkey>,
+    token_owner_account_b: Option<Pubkey>,
+    token_program_a: Option<Pubkey>,
+    token_program_b: Option<Pubkey>,
+    transfer_hook_accounts_a: Option<Vec<AccountMeta>>,
+    transfer_hook_accounts_b: Option<Vec<AccountMeta>>,
+}
+
+impl OrcaWhirlpool {
+    /// Starts building a swap of `amount` of the input token against the pool's current state.
+    ///
+    /// `a_to_b` is the swap direction, token A in and token B out if true.
+    pub fn swap_builder(&self, a_to_b: bool, amount: u64) -> OrcaSwapBuilder {
+        OrcaSwapBuilder::new(self.state(), a_to_b, amount)
+    }
+}
+
+impl OrcaSwapBuilder {
+    /// Starts building a swap of `amount` of the input token against `state`.
+    ///
+    /// `a_to_b` is the swap direction, token A in and token B out if true.
+    pub fn new(state: OrcaWhirlpoolState, a_to_b: bool, amount: u64) -> Self {
+        Self {
+            state,
+            a_to_b,
+            amount,
+            amount_specified_is_input: true,
+            slippage_tolerance_bps: 0,
+            sqrt_price_limit: 0,
+            timestamp: None,
+            epoch: None,
+            token_authority: None,
+            token_owner_account_a: None,
+            token_owner_account_b: None,
+            token_program_a: None,
+            token_program_b: None,
+            transfer_hook_accounts_a: None,
+            transfer_hook_accounts_b: None,
+        }
+    }
+
+    /// Treats the amount as the exact output rather than the exact input. Defaults to exact input.
+    pub fn exact_output(mut self, exact_output: bool) -> Self {
+        self.amount_specified_is_input = !exact_output;
+        self
+    }
+
+    /// The slippage tolerance in basis points, which bounds the unspecified side of the swap. Defaults to 0.
+    pub fn slippage_tolerance_bps(mut self, slippage_tolerance_bps: u16) -> Self {
+        self.slippage_tolerance_bps = slippage_tolerance_bps;
+        self
+    }
+
+    /// The sqrt price (Q64.64) the swap stops at. Defaults to 0, which the program reads as no limit.
+    ///
+    /// Note: The quote doesn't account for a limit, so `build` rejects any other value. A swap that may stop at
+    /// the limit should be quoted with `OrcaWhirlpoolState::simulate_swap` instead.
+    pub fn sqrt_price_limit(mut self, sqrt_price_limit: u128) -> Self {
+        self.sqrt_price_limit = sqrt_price_limit;
+        self
+    }
+
+    /// The unix timestamp (seconds) the adaptive fee is quoted at. Defaults to the system clock.
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// The epoch the Token-2022 transfer fees are quoted at. Only required if a mint has a transfer fee.
+    pub fn epoch(mut self, epoch: u64) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// The signer owning the token accounts.
+    pub fn token_authority(mut self, token_authority: Pubkey) -> Self {
+        self.token_authority = Some(token_authority);
+        self
+    }
+
+    /// The token authority's token accounts for tokens A and B.
+    pub fn token_owner_accounts(mut self, token_owner_account_a: Pubkey, token_owner_account_b: Pubkey) -> Self {
+        self.token_owner_account_a = Some(token_owner_account_a);
+        self.token_owner_account_b = Some(token_owner_account_b);
+        self
+    }
+
+    /// Overrides the token programs of tokens A and B, see `MintExtensions::token_program_id` for the default.
+    pub fn token_programs(mut self, token_program_a: Pubkey, token_program_b: Pubkey) -> Self {
+        self.token_program_a = Some(token_program_a);
+        self.token_program_b = Some(token_program_b);
+        self
+    }
+
+    /// The accounts token A's transfer hook needs: its extra accounts followed by the hook program itself.
+    ///
+    /// Required if token A has a transfer hook. We don't resolve these, it needs the hook's extra account
+    /// metas account and possibly arbitrary seeds, see the spl-transfer-hook-interface crate.
+    pub fn transfer_hook_accounts_a(mut self, accounts: Vec<AccountMeta>) -> Self {
+        self.transfer_hook_accounts_a = Some(accounts);
+        self
+    }
+
+    /// The accounts token B's transfer hook needs, see `transfer_hook_accounts_a`.
+    pub fn transfer_hook_accounts_b(mut self, accounts: Vec<AccountMeta>) -> Self {
+        self.transfer_hook_accounts_b = Some(accounts);
+        self
+    }
+
+    /// Quotes the swap and builds the instruction.
+    ///
+    /// Fails if a required account is missing, if a mint needs an epoch or transfer hook accounts that
+    /// weren't given, if a sqrt price limit was set, or if the swap can't be quoted, e.g. it doesn't fit in
+    /// the three tick arrays.
+    pub fn build(self) -> AnyResult<OrcaSwap> {
+        let state = &self.state;
+        if self.sqrt_price_limit != 0 {
+            return Err(anyhow!(
+                "Swap on whirlpool {} has sqrt price limit {}, which its quote can't account for",
+                state.pubkey,
+                self.sqrt_price_limit
+            ));
+        }
+        let token_authority = self
+            .token_authority
+            .ok_or_else(|| anyhow!("Swap on whirlpool {} is missing the token authority", state.pubkey))?;
+        let (token_owner_account_a, token_owner_account_b) = self
+            .token_owner_account_a
+            .zip(self.token_owner_account_b)
+            .ok_or_else(|| anyhow!("Swap on whirlpool {} is missing the token owner accounts", state.pubkey))?;
+
+        let mint_a_extensions = state.mint_a_extensions()?;
+        let mint_b_extensions = state.mint_b_extensions()?;
+        let has_transfer_fee =
+            mint_a_extensions.transfer_fee_config.is_some() || mint_b_extensions.transfer_fee_config.is_some();
+        let epoch = match self.epoch {
+            Some(epoch) => epoch,
+            None if has_transfer_fee => {
+                return Err(anyhow!(
+                    "Whirlpool {} has a Token-2022 transfer fee, the swap needs an epoch to quote it",
+                    state.pubkey
+                ))
+            }
+            None => 0,
+        };
+        let timestamp = match self.timestamp {
+            Some(timestamp) => timestamp,
+            None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        };
+
+        let quote = if self.amount_specified_is_input {
+            OrcaSwapQuote::ExactIn(state.swap_quote_by_input_token(
+                self.amount,
+                self.a_to_b,
+                self.slippage_tolerance_bps,
+                timestamp,
+                epoch,
+            )?)
+        } else {
+            OrcaSwapQuote::ExactOut(state.swap_quote_by_output_token(
+                self.amount,
+                !self.a_to_b,
+                self.slippage_tolerance_bps,
+                timestamp,
+                epoch,
+            )?)
+        };
+
+        let (remaining_accounts_info, remaining_accounts) = transfer_hook_remaining_accounts(
+            &state.pubkey,
+            [
+                (AccountsType::TransferHookA, &mint_a_extensions, self.transfer_hook_accounts_a),
+                (AccountsType::TransferHookB, &mint_b_extensions, self.transfer_hook_accounts_b),
+            ],
+        )?;
+
+        let [tick_array0, tick_array1, tick_array2] = state
+            .swap_tick_array_start_indexes(self.a_to_b)
+            .map(|start_tick_index| pda::get_tick_array_address(&state.pubkey, start_tick_index));
+        // The program always takes the oracle PDA, whether or not the account exists.
+        let (oracle, _) = pda::get_oracle_address(&state.pubkey)?;
+
+        let instruction = SwapV2 {
+            token_program_a: self.token_program_a.unwrap_or_else(|| mint_a_extensions.token_program_id()),
+            token_program_b: self.token_program_b.unwrap_or_else(|| mint_b_extensions.token_program_id()),
+            memo_program: MEMO_PROGRAM_ID,
+            token_authority,
+            whirlpool: state.pubkey,
+            token_mint_a: state.whirlpool.token_mint_a,
+            token_mint_b: state.whirlpool.token_mint_b,
+            token_owner_account_a,
+            token_vault_a: state.whirlpool.token_vault_a,
+            token_owner_account_b,
+            token_vault_b: state.whirlpool.token_vault_b,
+            tick_array0: tick_array0?,
+            tick_array1: tick_array1?,
+            tick_array2: tick_array2?,
+            oracle,
+        }
+        .instruction_with_remaining_accounts(
+            SwapV2InstructionArgs {
+                amount: self.amount,
+                other_amount_threshold: quote.other_amount_threshold(),
+                sqrt_price_limit: self.sqrt_price_limit,
+                amount_specified_is_input: self.amount_specified_is_input,
+                a_to_b: self.a_to_b,
+                remaining_accounts_info,
+            },
+            &remaining_accounts,
+        );
+
+        Ok(OrcaSwap { instruction, quote })
+    }
+}
+
+/// Lays out the transfer hook accounts of each mint as the `*_v2` instructions' remaining accounts, returning the
+/// `RemainingAccountsInfo` arg describing them alongside the accounts themselves.
+///
+/// Fails if a mint has a transfer hook but no accounts were given for it. Accounts given for a mint without a hook
+/// are passed along regardless, the caller presumably knows something we don't.
+pub(crate) fn transfer_hook_remaining_accounts<const N: usize>(
+    whirlpool_pubkey: &Pubkey,
+    mints: [(AccountsType, &MintExtensions, Option<Vec<AccountMeta>>); N],
+) -> AnyResult<(Option<RemainingAccountsInfo>, Vec<AccountMeta>)> {
+    let mut slices = Vec::new();
+    let mut remaining_accounts = Vec::new();
+    for (accounts_type, extensions, accounts) in mints {
+        let accounts = match (accounts, extensions.transfer_hook_program_id) {
+            (Some(accounts), _) => accounts,
+            (None, Some(hook_program_id)) => {
+                return Err(anyhow!(
+                    "A mint of whirlpool {} has transfer hook program {}, but no transfer hook accounts were given",
+                    whirlpool_pubkey,
+                    hook_program_id
+                ))
+            }
+            (None, None) => continue,
+        };
+        if accounts.is_empty() {
+            continue;
+        }
+        slices.push(RemainingAccountsSlice {
+            accounts_type,
+            length: u8::try_from(accounts.len())
+                .map_err(|_| anyhow!("Too many transfer hook accounts: {}", accounts.len()))?,
+        });
+        remaining_accounts.extend(accounts);
+    }
+    let remaining_accounts_info = (!slices.is_empty()).then_some(RemainingAccountsInfo { slices });
+    Ok((remaining_accounts_info, remaining_accounts))
+}
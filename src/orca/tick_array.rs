@@ -0,0 +1,148 @@
+//! Defines `TickArrayAccount`, the account type an `OrcaWhirlpool` tracks its tick arrays with.
+//!
+//! Tick arrays are by far the biggest accounts of a pool, so how they're stored is configurable, see
+//! `TickArrayStorage`. Either way they implement `AccountState`, and downcast to the underlying `ManagedAccount`
+//! or `LazyAccount` through `as_any`.
+
+use crate::common::{
+    snapshot::AccountSnapshot,
+    state::{AccountLifecycle, AccountState, ManagedAccount, UpdateStamp},
+    types::AnyResult,
+    zero_copy::LazyAccount,
+};
+use crate::orca::pool::tick_array_start_index;
+use arc_swap::Guard;
+use orca_whirlpools_client::TickArray;
+use solana_sdk::pubkey::Pubkey;
+use std::any::Any;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// How an `OrcaWhirlpool` stores its tick arrays, fixed at construction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TickArrayStorage {
+    /// A `ManagedAccount<TickArray>` each, deserializing every changed update as it lands.
+    #[default]
+    Eager,
+    /// A `LazyAccount<TickArray>` each, whose updates only store the bytes. The tick arrays are deserialized
+    /// when the pool next publishes (see `OrcaWhirlpool::publish`), so a refresh that aborts part way, or updates
+    /// pushed faster than they're published, don't pay for it.
+    ///
+    /// Note: Updates can't reject bad data, a tick array that fails to deserialize is instead marked
+    /// `AccountLifecycle::Errored` when published, and left out of `OrcaWhirlpool::state` (see `Pool::health`).
+    Lazy,
+}
+
+/// A tick array tracked by an `OrcaWhirlpool`, stored as chosen by `TickArrayStorage`.
+pub struct TickArrayAccount {
+    /// The start tick index never changes for a tick array PDA, so we keep it to order tick arrays without
+    /// deserializing them.
+    start_tick_index: i32,
+    account: StoredTickArray,
+}
+
+enum StoredTickArray {
+    Eager(ManagedAccount<TickArray>),
+    Lazy(LazyAccount<TickArray>),
+}
+
+impl TickArrayAccount {
+    /// Constructs the tick array from its on-chain data.
+    ///
+    /// The data is deserialized once whatever the storage, so this fails if it isn't a tick array.
+    pub fn new_initialized_from_bytes(
+        storage: TickArrayStorage,
+        pubkey: Pubkey,
+        initial_bytes: Vec<u8>,
+        initial_time: u64,
+    ) -> AnyResult<Self> {
+        let (start_tick_index, account) = match storage {
+            TickArrayStorage::Eager => {
+                let account = ManagedAccount::<TickArray>::new_initialized_from_bytes(pubkey, initial_bytes, initial_time)?;
+                (tick_array_start_index(&account.get()), StoredTickArray::Eager(account))
+            }
+            TickArrayStorage::Lazy => {
+                let account = LazyAccount::<TickArray>::new_from_bytes(pubkey, initial_bytes, initial_time);
+                // Also warms the cache, so the first publish doesn't deserialize again.
+                (tick_array_start_index(&*account.get()?), StoredTickArray::Lazy(account))
+            }
+        };
+        Ok(Self {
+            start_tick_index,
+            account,
+        })
+    }
+
+    /// Returns the start tick index of the tick array.
+    pub fn start_tick_index(&self) -> i32 {
+        self.start_tick_index
+    }
+
+    /// Returns how the tick array is stored.
+    pub fn storage(&self) -> TickArrayStorage {
+        match &self.account {
+            StoredTickArray::Eager(_) => TickArrayStorage::Eager,
+            StoredTickArray::Lazy(_) => TickArrayStorage::Lazy,
+        }
+    }
+
+    /// Returns the deserialized tick array, which for `TickArrayStorage::Lazy` deserializes it if it was updated
+    /// since, see `LazyAccount::get`.
+    pub fn get(&self) -> AnyResult<Arc<TickArray>> {
+        match &self.account {
+            StoredTickArray::Eager(account) => Ok(account.get().clone()),
+            StoredTickArray::Lazy(account) => account.get(),
+        }
+    }
+
+    fn inner(&self) -> &dyn AccountState {
+        match &self.account {
+            StoredTickArray::Eager(account) => account,
+            StoredTickArray::Lazy(account) => account,
+        }
+    }
+}
+
+/// Delegates to the underlying account.
+impl AccountState for TickArrayAccount {
+    fn update(&self, new_bytes: Vec<u8>, update_time: u64) -> AnyResult<bool> {
+        self.inner().update(new_bytes, update_time)
+    }
+
+    fn pubkey(&self) -> &Pubkey {
+        self.inner().pubkey()
+    }
+
+    fn bytes(&self) -> Guard<Arc<Vec<u8>>> {
+        self.inner().bytes()
+    }
+
+    /// Downcasts to the underlying `ManagedAccount<TickArray>` or `LazyAccount<TickArray>`.
+    fn as_any(&self) -> &dyn Any {
+        self.inner().as_any()
+    }
+
+    fn watch(&self) -> watch::Receiver<UpdateStamp> {
+        self.inner().watch()
+    }
+
+    fn snapshot(&self) -> AccountSnapshot {
+        self.inner().snapshot()
+    }
+
+    fn lifecycle(&self) -> AccountLifecycle {
+        self.inner().lifecycle()
+    }
+
+    fn mark_closed(&self) -> bool {
+        self.inner().mark_closed()
+    }
+
+    fn update_slot(&self) -> u64 {
+        self.inner().update_slot()
+    }
+
+    fn last_update_time(&self) -> u64 {
+        self.inner().last_update_time()
+    }
+}
@@ -0,0 +1,414 @@
+//! Builds Orca `two_hop_swap_v2` instructions, routing a swap through two `OrcaWhirlpool`s that share a mint.
+//!
+//! As with `OrcaSwapBuilder`, the accounts and the slippage threshold come from the pools' cached state, and the
+//! chained quote is returned alongside the instruction. Sqrt price limits are rejected for the same reason.
+
+use crate::common::types::AnyResult;
+use crate::mint::extensions::MintExtensions;
+use crate::orca::{
+    pda,
+    pool::OrcaWhirlpool,
+    state::OrcaWhirlpoolState,
+    swap::{transfer_hook_remaining_accounts, OrcaSwapQuote, MEMO_PROGRAM_ID},
+};
+use anyhow::anyhow;
+use orca_whirlpools_client::{AccountsType, TwoHopSwapV2, TwoHopSwapV2InstructionArgs};
+use orca_whirlpools_core::TransferFee;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The chained quote of a two hop swap, one quote per hop.
+///
+/// The intermediate token's transfer fee is charged once, on the first hop, as the program moves it straight
+/// from the first pool's vault to the second's.
+#[derive(Debug, Clone)]
+pub struct OrcaTwoHopSwapQuote {
+    pub hop_one: OrcaSwapQuote,
+    pub hop_two: OrcaSwapQuote,
+}
+
+impl OrcaTwoHopSwapQuote {
+    /// The amount of the input token the swap is expected to take.
+    pub fn token_in(&self) -> u64 {
+        self.hop_one.token_in()
+    }
+
+    /// The amount of the output token the swap is expected to give.
+    pub fn token_out(&self) -> u64 {
+        self.hop_two.token_out()
+    }
+
+    /// The threshold on the unspecified end of the route: the output's minimum for exact in swaps, the input's
+    /// maximum for exact out swaps. Slippage is only applied to that end.
+    pub fn other_amount_threshold(&self) -> u64 {
+        match &self.hop_one {
+            OrcaSwapQuote::ExactIn(_) => self.hop_two.other_amount_threshold(),
+            OrcaSwapQuote::ExactOut(_) => self.hop_one.other_amount_threshold(),
+        }
+    }
+}
+
+/// A built two hop swap instruction along with the quote it was built from.
+#[derive(Debug, Clone)]
+pub struct OrcaTwoHopSwap {
+    pub instruction: Instruction,
+    pub quote: OrcaTwoHopSwapQuote,
+}
+
+/// Builder for a `two_hop_swap_v2` instruction, swapping the input token for the intermediate token on the first
+/// whirlpool and the intermediate token for the output token on the second.
+///
+/// Required: the token authority and its input and output token accounts. Everything else has a default.
+#[derive(Debug, Clone)]
+pub struct OrcaTwoHopSwapBuilder {
+    state_one: OrcaWhirlpoolState,
+    state_two: OrcaWhirlpoolState,
+    input_mint: Pubkey,
+    amount: u64,
+    amount_specified_is_input: bool,
+    slippage_tolerance_bps: u16,
+    sqrt_price_limit_one: u128,
+    sqrt_price_limit_two: u128,
+    timestamp: Option<u64>,
+    epoch: Option<u64>,
+    token_authority: Option<Pubkey>,
+    token_owner_account_input: Option<Pubkey>,
+    token_owner_account_output: Option<Pubkey>,
+    token_programs: Option<[Pubkey; 3]>,
+    transfer_hook_accounts: [Option<Vec<AccountMeta>>; 3],
+}
+
+/// The route of a two hop swap, derived from the pools and the input mint.
+struct TwoHopRoute {
+    a_to_b_one: bool,
+    a_to_b_two: bool,
+    intermediate_mint: Pubkey,
+    output_mint: Pubkey,
+}
+
+impl OrcaWhirlpool {
+    /// Starts building a swap of `amount` of `input_mint` through this pool and then `next`, against both
+    /// pools' current state.
+    pub fn two_hop_swap_builder(&self, next: &OrcaWhirlpool, input_mint: Pubkey, amount: u64) -> OrcaTwoHopSwapBuilder {
+        OrcaTwoHopSwapBuilder::new(self.state(), next.state(), input_mint, amount)
+    }
+}
+
+impl OrcaTwoHopSwapBuilder {
+    /// Starts building a swap of `amount` of `input_mint` through `state_one` and then `state_two`.
+    ///
+    /// The pools are validated in `build`: `input_mint` must be one of the first pool's mints, and the first
+    /// pool's other mint (the intermediate) must be one of the second pool's.
+    pub fn new(state_one: OrcaWhirlpoolState, state_two: OrcaWhirlpoolState, input_mint: Pubkey, amount: u64) -> Self {
+        Self {
+            state_one,
+            state_two,
+            input_mint,
+            amount,
+            amount_specified_is_input: true,
+            slippage_tolerance_bps: 0,
+            sqrt_price_limit_one: 0,
+            sqrt_price_limit_two: 0,
+            timestamp: None,
+            epoch: None,
+            token_authority: None,
+            token_owner_account_input: None,
+            token_owner_account_output: None,
+            token_programs: None,
+            transfer_hook_accounts: [None, None, None],
+        }
+    }
+
+    /// Treats the amount as the exact output rather than the exact input. Defaults to exact input.
+    pub fn exact_output(mut self, exact_output: bool) -> Self {
+        self.amount_specified_is_input = !exact_output;
+        self
+    }
+
+    /// The slippage tolerance in basis points, which bounds the unspecified end of the route. Defaults to 0.
+    pub fn slippage_tolerance_bps(mut self, slippage_tolerance_bps: u16) -> Self {
+        self.slippage_tolerance_bps = slippage_tolerance_bps;
+        self
+    }
+
+    /// The sqrt prices (Q64.64) each hop stops at. Defaults to 0, which the program reads as no limit.
+    ///
+    /// Note: As with `OrcaSwapBuilder::sqrt_price_limit`, the chained quote doesn't account for limits, so `build`
+    /// rejects any other value.
+    pub fn sqrt_price_limits(mut self, sqrt_price_limit_one: u128, sqrt_price_limit_two: u128) -> Self {
+        self.sqrt_price_limit_one = sqrt_price_limit_one;
+        self.sqrt_price_limit_two = sqrt_price_limit_two;
+        self
+    }
+
+    /// The unix timestamp (seconds) the adaptive fees are quoted at. Defaults to the system clock.
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// The epoch the Token-2022 transfer fees are quoted at. Only required if a mint has a transfer fee.
+    pub fn epoch(mut self, epoch: u64) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// The signer owning the token accounts.
+    pub fn token_authority(mut self, token_authority: Pubkey) -> Self {
+        self.token_authority = Some(token_authority);
+        self
+    }
+
+    /// The token authority's token accounts for the input and output tokens.
+    ///
+    /// Note: No intermediate token account is needed, the intermediate token moves between the pools' vaults.
+    pub fn token_owner_accounts(mut self, token_owner_account_input: Pubkey, token_owner_account_output: Pubkey) -> Self {
+        self.token_owner_account_input = Some(token_owner_account_input);
+        self.token_owner_account_output = Some(token_owner_account_output);
+        self
+    }
+
+    /// Overrides the token programs of the input, intermediate and output tokens, see
+    /// `MintExtensions::token_program_id` for the default.
+    pub fn token_programs(mut self, input: Pubkey, intermediate: Pubkey, output: Pubkey) -> Self {
+        self.token_programs = Some([input, intermediate, output]);
+        self
+    }
+
+    /// The accounts the input, intermediate and output tokens' transfer hooks need, see
+    /// `OrcaSwapBuilder::transfer_hook_accounts_a`. Each is required if that token has a transfer hook.
+    pub fn transfer_hook_accounts(
+        mut self,
+        input: Option<Vec<AccountMeta>>,
+        intermediate: Option<Vec<AccountMeta>>,
+        output: Option<Vec<AccountMeta>>,
+    ) -> Self {
+        self.transfer_hook_accounts = [input, intermediate, output];
+        self
+    }
+
+    /// Validates the shared mint, derives each hop's direction and returns the route.
+    fn route(&self) -> AnyResult<TwoHopRoute> {
+        let (one, two) = (&self.state_one, &self.state_two);
+        if one.pubkey == two.pubkey {
+            return Err(anyhow!("Two hop swap needs two different whirlpools, got {} twice", one.pubkey));
+        }
+        let (a_to_b_one, intermediate_mint) = if self.input_mint == one.whirlpool.token_mint_a {
+            (true, one.whirlpool.token_mint_b)
+        } else if self.input_mint == one.whirlpool.token_mint_b {
+            (false, one.whirlpool.token_mint_a)
+        } else {
+            return Err(anyhow!("Input mint {} isn't a mint of whirlpool {}", self.input_mint, one.pubkey));
+        };
+        let (a_to_b_two, output_mint) = if intermediate_mint == two.whirlpool.token_mint_a {
+            (true, two.whirlpool.token_mint_b)
+        } else if intermediate_mint == two.whirlpool.token_mint_b {
+            (false, two.whirlpool.token_mint_a)
+        } else {
+            return Err(anyhow!(
+                "Whirlpools {} and {} don't share the intermediate mint {}",
+                one.pubkey,
+                two.pubkey,
+                intermediate_mint
+            ));
+        };
+        Ok(TwoHopRoute { a_to_b_one, a_to_b_two, intermediate_mint, output_mint })
+    }
+
+    /// Validates the route, quotes both hops and builds the instruction.
+    ///
+    /// Fails if the pools don't share a mint as described in `new`, if a required account is missing, if a mint
+    /// needs an epoch or transfer hook accounts that weren't given, if a sqrt price limit was set, or if either hop
+    /// can't be quoted.
+    pub fn build(self) -> AnyResult<OrcaTwoHopSwap> {
+        let route = self.route()?;
+        let (one, two) = (&self.state_one, &self.state_two);
+        if self.sqrt_price_limit_one != 0 || self.sqrt_price_limit_two != 0 {
+            return Err(anyhow!(
+                "Two hop swap via whirlpool {} has sqrt price limits ({}, {}), which its quote can't account for",
+                one.pubkey,
+                self.sqrt_price_limit_one,
+                self.sqrt_price_limit_two
+            ));
+        }
+        let token_authority = self
+            .token_authority
+            .ok_or_else(|| anyhow!("Two hop swap via whirlpool {} is missing the token authority", one.pubkey))?;
+        let (token_owner_account_input, token_owner_account_output) = self
+            .token_owner_account_input
+            .zip(self.token_owner_account_output)
+            .ok_or_else(|| anyhow!("Two hop swap via whirlpool {} is missing the token owner accounts", one.pubkey))?;
+
+        let (one_a, one_b) = (one.mint_a_extensions()?, one.mint_b_extensions()?);
+        let (two_a, two_b) = (two.mint_a_extensions()?, two.mint_b_extensions()?);
+        let (input, intermediate) = if route.a_to_b_one { (one_a, one_b) } else { (one_b, one_a) };
+        let output = if route.a_to_b_two { two_b } else { two_a };
+
+        let has_transfer_fee = [&input, &intermediate, &output]
+            .iter()
+            .any(|extensions| extensions.transfer_fee_config.is_some());
+        let epoch = match self.epoch {
+            Some(epoch) => epoch,
+            None if has_transfer_fee => {
+                return Err(anyhow!(
+                    "Two hop swap via whirlpool {} has a Token-2022 transfer fee, it needs an epoch to quote it",
+                    one.pubkey
+                ))
+            }
+            None => 0,
+        };
+        let timestamp = match self.timestamp {
+            Some(timestamp) => timestamp,
+            None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        };
+
+        let quote = self.quote(&route, [&input, &intermediate, &output], timestamp, epoch)?;
+
+        let [input_hook, intermediate_hook, output_hook] = self.transfer_hook_accounts;
+        let (remaining_accounts_info, remaining_accounts) = transfer_hook_remaining_accounts(
+            &one.pubkey,
+            [
+                (AccountsType::TransferHookInput, &input, input_hook),
+                (AccountsType::TransferHookIntermediate, &intermediate, intermediate_hook),
+                (AccountsType::TransferHookOutput, &output, output_hook),
+            ],
+        )?;
+        let [token_program_input, token_program_intermediate, token_program_output] = self
+            .token_programs
+            .unwrap_or_else(|| [&input, &intermediate, &output].map(MintExtensions::token_program_id));
+
+        let tick_arrays = |state: &OrcaWhirlpoolState, a_to_b: bool| -> AnyResult<[Pubkey; 3]> {
+            let [first, second, third] = state
+                .swap_tick_array_start_indexes(a_to_b)
+                .map(|start_tick_index| pda::get_tick_array_address(&state.pubkey, start_tick_index));
+            Ok([first?, second?, third?])
+        };
+        let [tick_array_one0, tick_array_one1, tick_array_one2] = tick_arrays(one, route.a_to_b_one)?;
+        let [tick_array_two0, tick_array_two1, tick_array_two2] = tick_arrays(two, route.a_to_b_two)?;
+        let vaults = |state: &OrcaWhirlpoolState, a_to_b: bool| {
+            let (vault_a, vault_b) = (state.whirlpool.token_vault_a, state.whirlpool.token_vault_b);
+            if a_to_b { (vault_a, vault_b) } else { (vault_b, vault_a) }
+        };
+        let (token_vault_one_input, token_vault_one_intermediate) = vaults(one, route.a_to_b_one);
+        let (token_vault_two_intermediate, token_vault_two_output) = vaults(two, route.a_to_b_two);
+
+        let instruction = TwoHopSwapV2 {
+            whirlpool_one: one.pubkey,
+            whirlpool_two: two.pubkey,
+            token_mint_input: self.input_mint,
+            token_mint_intermediate: route.intermediate_mint,
+            token_mint_output: route.output_mint,
+            token_program_input,
+            token_program_intermediate,
+            token_program_output,
+            token_owner_account_input,
+            token_vault_one_input,
+            token_vault_one_intermediate,
+            token_vault_two_intermediate,
+            token_vault_two_output,
+            token_owner_account_output,
+            token_authority,
+            tick_array_one0,
+            tick_array_one1,
+            tick_array_one2,
+            tick_array_two0,
+            tick_array_two1,
+            tick_array_two2,
+            oracle_one: pda::get_oracle_address(&one.pubkey)?.0,
+            oracle_two: pda::get_oracle_address(&two.pubkey)?.0,
+            memo_program: MEMO_PROGRAM_ID,
+        }
+        .instruction_with_remaining_accounts(
+            TwoHopSwapV2InstructionArgs {
+                amount: self.amount,
+                other_amount_threshold: quote.other_amount_threshold(),
+                amount_specified_is_input: self.amount_specified_is_input,
+                a_to_b_one: route.a_to_b_one,
+                a_to_b_two: route.a_to_b_two,
+                sqrt_price_limit_one: self.sqrt_price_limit_one,
+                sqrt_price_limit_two: self.sqrt_price_limit_two,
+                remaining_accounts_info,
+            },
+            &remaining_accounts,
+        );
+
+        Ok(OrcaTwoHopSwap { instruction, quote })
+    }
+
+    /// Chains the quotes of the two hops, from the specified end of the route to the other.
+    ///
+    /// Slippage is only applied to the final quote, the intermediate amount is quoted exactly.
+    fn quote(
+        &self,
+        route: &TwoHopRoute,
+        [input, intermediate, output]: [&MintExtensions; 3],
+        timestamp: u64,
+        epoch: u64,
+    ) -> AnyResult<OrcaTwoHopSwapQuote> {
+        let input_fee = input.transfer_fee(epoch);
+        let intermediate_fee = intermediate.transfer_fee(epoch);
+        let output_fee = output.transfer_fee(epoch);
+        let (fee_a_one, fee_b_one) = hop_transfer_fees(route.a_to_b_one, input_fee, intermediate_fee);
+        let (fee_a_two, fee_b_two) = hop_transfer_fees(route.a_to_b_two, None, output_fee);
+
+        if self.amount_specified_is_input {
+            let hop_one = self.state_one.swap_quote_by_input_token_with_transfer_fees(
+                self.amount,
+                route.a_to_b_one,
+                0,
+                timestamp,
+                fee_a_one,
+                fee_b_one,
+            )?;
+            let hop_two = self.state_two.swap_quote_by_input_token_with_transfer_fees(
+                hop_one.token_est_out,
+                route.a_to_b_two,
+                self.slippage_tolerance_bps,
+                timestamp,
+                fee_a_two,
+                fee_b_two,
+            )?;
+            Ok(OrcaTwoHopSwapQuote {
+                hop_one: OrcaSwapQuote::ExactIn(hop_one),
+                hop_two: OrcaSwapQuote::ExactIn(hop_two),
+            })
+        } else {
+            let hop_two = self.state_two.swap_quote_by_output_token_with_transfer_fees(
+                self.amount,
+                !route.a_to_b_two,
+                0,
+                timestamp,
+                fee_a_two,
+                fee_b_two,
+            )?;
+            let hop_one = self.state_one.swap_quote_by_output_token_with_transfer_fees(
+                hop_two.token_est_in,
+                !route.a_to_b_one,
+                self.slippage_tolerance_bps,
+                timestamp,
+                fee_a_one,
+                fee_b_one,
+            )?;
+            Ok(OrcaTwoHopSwapQuote {
+                hop_one: OrcaSwapQuote::ExactOut(hop_one),
+                hop_two: OrcaSwapQuote::ExactOut(hop_two),
+            })
+        }
+    }
+}
+
+/// Maps a hop's input and output transfer fees onto its pool's tokens A and B.
+fn hop_transfer_fees(
+    a_to_b: bool,
+    input_fee: Option<TransferFee>,
+    output_fee: Option<TransferFee>,
+) -> (Option<TransferFee>, Option<TransferFee>) {
+    if a_to_b {
+        (input_fee, output_fee)
+    } else {
+        (output_fee, input_fee)
+    }
+}
@@ -0,0 +1,145 @@
+//! Error classification and failover across providers.
+
+use async_trait::async_trait;
+use solana_dex_tools::common::{
+    combinators::{is_retriable, Failover, Hedged, RpcTimeout},
+    rpc::{RpcProvider, RpcResponse},
+    types::AnyResult,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Fails every call with a timeout while `failing`, and counts its calls.
+#[derive(Default)]
+struct MockProvider {
+    failing: AtomicBool,
+    calls: AtomicUsize,
+}
+
+impl MockProvider {
+    fn failing() -> Self {
+        Self {
+            failing: AtomicBool::new(true),
+            ..Self::default()
+        }
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl RpcProvider for MockProvider {
+    type AccountType = Account;
+
+    async fn get_account(&self, _pubkey: &Pubkey) -> AnyResult<RpcResponse<Account>> {
+        Err(anyhow::anyhow!("AccountNotFound"))
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> AnyResult<RpcResponse<Vec<Option<Account>>>> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if self.failing.load(Ordering::Relaxed) {
+            return Err(RpcTimeout {
+                method: "get_multiple_accounts",
+                timeout: Duration::from_secs(1),
+            }
+            .into());
+        }
+        Ok(RpcResponse {
+            result: vec![None; pubkeys.len()],
+            response_time: 0,
+            slot: None,
+        })
+    }
+
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        100
+    }
+}
+
+/// Unwraps the error of `result`, as `RpcResponse` isn't `Debug`.
+fn error<T>(result: AnyResult<T>) -> anyhow::Error {
+    match result {
+        Ok(_) => panic!("Expected an error"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn unknown_errors_are_not_retriable() {
+    let timeout = RpcTimeout {
+        method: "get_slot",
+        timeout: Duration::from_secs(1),
+    };
+    assert!(is_retriable(&timeout.into()));
+    assert!(!is_retriable(&anyhow::anyhow!("AccountNotFound: pubkey={}", Pubkey::new_unique())));
+    assert!(!is_retriable(&anyhow::anyhow!("Something we've never seen")));
+}
+
+#[tokio::test]
+async fn exhausted_failover_keeps_the_last_error() {
+    let failover = Failover::new(vec![MockProvider::failing(), MockProvider::failing()]).unwrap();
+    let failure = error(failover.get_multiple_accounts(&[Pubkey::new_unique()]).await);
+    assert!(failure.to_string().starts_with("All RPC providers failed"));
+    assert!(failure.is::<RpcTimeout>());
+    assert!(is_retriable(&failure));
+    assert!(failover.providers().iter().all(|provider| provider.calls() == 1));
+
+    // Non-retriable errors are returned straight away.
+    let failure = error(failover.get_account(&Pubkey::new_unique()).await);
+    assert_eq!(failure.to_string(), "AccountNotFound");
+}
+
+#[tokio::test]
+async fn failover_probes_demoted_provider_after_cooldown() {
+    let cooldown = Duration::from_millis(50);
+    let failover = Failover::new(vec![MockProvider::failing(), MockProvider::default()])
+        .unwrap()
+        .recovery_cooldown(cooldown);
+    let [preferred, backup] = [&failover.providers()[0], &failover.providers()[1]];
+    let pubkeys = [Pubkey::new_unique()];
+
+    // One transient failure demotes the preferred provider.
+    failover.get_multiple_accounts(&pubkeys).await.unwrap();
+    assert_eq!((preferred.calls(), backup.calls()), (1, 1));
+    preferred.failing.store(false, Ordering::Relaxed);
+    failover.get_multiple_accounts(&pubkeys).await.unwrap();
+    assert_eq!((preferred.calls(), backup.calls()), (1, 2));
+
+    // After the cooldown it's probed, and restored by succeeding.
+    tokio::time::sleep(cooldown).await;
+    failover.get_multiple_accounts(&pubkeys).await.unwrap();
+    assert_eq!((preferred.calls(), backup.calls()), (2, 2));
+    // Back to being ranked on latency alongside the backup.
+    assert_eq!(failover.health()[0].consecutive_failures, 0);
+}
+
+#[tokio::test]
+async fn failed_probe_demotes_for_another_cooldown() {
+    let cooldown = Duration::from_millis(50);
+    let failover = Failover::new(vec![MockProvider::failing(), MockProvider::default()])
+        .unwrap()
+        .recovery_cooldown(cooldown);
+    let [preferred, backup] = [&failover.providers()[0], &failover.providers()[1]];
+    let pubkeys = [Pubkey::new_unique()];
+
+    failover.get_multiple_accounts(&pubkeys).await.unwrap();
+    tokio::time::sleep(cooldown).await;
+    failover.get_multiple_accounts(&pubkeys).await.unwrap();
+    assert_eq!((preferred.calls(), backup.calls()), (2, 2));
+    assert_eq!(failover.health()[0].consecutive_failures, 2);
+
+    // Not probed again until the next cooldown is up.
+    failover.get_multiple_accounts(&pubkeys).await.unwrap();
+    assert_eq!((preferred.calls(), backup.calls()), (2, 3));
+}
+
+#[tokio::test]
+async fn hedged_failure_keeps_the_last_error() {
+    let hedged = Hedged::new(MockProvider::failing(), MockProvider::failing(), Duration::ZERO);
+    let failure = error(hedged.get_multiple_accounts(&[Pubkey::new_unique()]).await);
+    assert!(failure.to_string().starts_with("Both hedged RPC calls failed"));
+    assert!(is_retriable(&failure));
+}
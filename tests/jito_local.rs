@@ -0,0 +1,126 @@
+//! Bundle flows through `JitoClient` against a `LocalBlockEngine`.
+#![cfg(feature = "jito")]
+
+use solana_dex_tools::common::transaction::{TransactionBuilder, TransactionSigner};
+use solana_dex_tools::jito::{
+    bundle::{Bundle, BundleBuilder},
+    client::{InflightStatus, JitoClient},
+    local::LocalBlockEngine,
+    tip_instruction, JITO_TIP_ACCOUNTS, MIN_TIP_LAMPORTS,
+};
+use solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Keypair};
+use std::time::Duration;
+
+/// A bundle of a single transaction tipping `lamports` to `tip_account`, signed by `payer`.
+fn tip_bundle(payer: &Keypair, tip_account: Pubkey, lamports: u64) -> Bundle {
+    let transaction = TransactionBuilder::new(payer.pubkey())
+        .instruction(tip_instruction(&payer.pubkey(), &tip_account, lamports))
+        .build(Hash::new_unique(), &[payer as &dyn TransactionSigner])
+        .unwrap();
+    Bundle::new(vec![transaction.transaction]).unwrap()
+}
+
+#[tokio::test]
+async fn send_records_pending_bundle_until_landed() {
+    let client = JitoClient::new(LocalBlockEngine::new());
+    let payer = Keypair::new();
+    let bundle = BundleBuilder::new()
+        .transaction(TransactionBuilder::new(payer.pubkey()))
+        .tip(payer.pubkey(), 10_000)
+        .build(Hash::new_unique(), &[&payer])
+        .unwrap();
+
+    let bundle_id = client.send_bundle(&bundle).await.unwrap();
+    assert_eq!(bundle_id, bundle.id());
+    let received = client.transport().received();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].tip_lamports, 10_000);
+
+    let ids = [bundle_id.clone()];
+    let inflight = client.get_inflight_bundle_statuses(&ids).await.unwrap();
+    assert_eq!(inflight[0].status, InflightStatus::Pending);
+    assert_eq!(client.get_bundle_statuses(&ids).await.unwrap(), vec![None]);
+
+    let slot = client.transport().land(&bundle_id).unwrap();
+    assert_eq!(slot, 1);
+    let inflight = client.get_inflight_bundle_statuses(&ids).await.unwrap();
+    assert_eq!(inflight[0].status, InflightStatus::Landed);
+    assert_eq!(inflight[0].landed_slot, Some(slot));
+    let status = client.get_bundle_statuses(&ids).await.unwrap().pop().flatten().unwrap();
+    assert_eq!(status.slot, slot);
+    assert!(status.is_ok());
+    assert_eq!(status.transactions, vec![bundle.transactions[0].signatures[0].to_string()]);
+
+    // A landed bundle is final.
+    assert!(client.transport().fail(&bundle_id).is_err());
+    assert!(client.send_bundle(&bundle).await.is_err(), "duplicate bundles are rejected");
+}
+
+#[tokio::test]
+async fn failed_and_unknown_bundle_statuses() {
+    let client = JitoClient::new(LocalBlockEngine::new());
+    let payer = Keypair::new();
+    let bundle = tip_bundle(&payer, JITO_TIP_ACCOUNTS[0], MIN_TIP_LAMPORTS);
+    let bundle_id = client.send_bundle(&bundle).await.unwrap();
+    client.transport().fail(&bundle_id).unwrap();
+
+    let ids = [bundle_id, "unknown".to_string()];
+    let statuses = client.get_inflight_bundle_statuses(&ids).await.unwrap();
+    assert_eq!(statuses[0].status, InflightStatus::Failed);
+    assert_eq!(statuses[1].status, InflightStatus::Invalid);
+    assert_eq!(client.get_bundle_statuses(&ids).await.unwrap(), vec![None, None]);
+    assert_eq!(client.transport().slot(), 0);
+}
+
+#[tokio::test]
+async fn auto_land_lands_each_bundle_in_its_own_slot() {
+    let client = JitoClient::new(LocalBlockEngine::new().auto_land(true));
+    let payer = Keypair::new();
+    for (i, tip_account) in JITO_TIP_ACCOUNTS.iter().take(2).enumerate() {
+        let bundle_id = client
+            .send_bundle(&tip_bundle(&payer, *tip_account, MIN_TIP_LAMPORTS))
+            .await
+            .unwrap();
+        let status = client
+            .wait_for_bundle(&bundle_id, Duration::from_millis(100), Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(status.status, InflightStatus::Landed);
+        assert_eq!(status.landed_slot, Some(i as u64 + 1));
+    }
+    assert!(client.transport().land_pending().is_empty());
+}
+
+#[tokio::test]
+async fn rejects_bundles_without_enough_tip() {
+    let client = JitoClient::new(LocalBlockEngine::new());
+    let payer = Keypair::new();
+
+    let below_minimum = tip_bundle(&payer, JITO_TIP_ACCOUNTS[0], MIN_TIP_LAMPORTS - 1);
+    assert!(client.send_bundle(&below_minimum).await.is_err());
+    let not_a_tip_account = tip_bundle(&payer, Pubkey::new_unique(), MIN_TIP_LAMPORTS);
+    assert!(client.send_bundle(&not_a_tip_account).await.is_err());
+
+    let mut unsigned = tip_bundle(&payer, JITO_TIP_ACCOUNTS[0], MIN_TIP_LAMPORTS);
+    unsigned.transactions[0].signatures[0] = Default::default();
+    assert!(client.send_bundle(&unsigned).await.is_err());
+    assert!(client.transport().received().is_empty());
+}
+
+#[tokio::test]
+async fn tip_accounts_are_served_and_honoured() {
+    let tip_account = Pubkey::new_unique();
+    let client = JitoClient::new(LocalBlockEngine::new().tip_accounts(vec![tip_account]));
+    assert_eq!(client.get_tip_accounts().await.unwrap(), vec![tip_account]);
+
+    let payer = Keypair::new();
+    assert!(client
+        .send_bundle(&tip_bundle(&payer, JITO_TIP_ACCOUNTS[0], MIN_TIP_LAMPORTS))
+        .await
+        .is_err());
+    let bundle_id = client
+        .send_bundle(&tip_bundle(&payer, tip_account, MIN_TIP_LAMPORTS))
+        .await
+        .unwrap();
+    assert_eq!(client.transport().land_pending(), vec![bundle_id]);
+}
@@ -0,0 +1,16 @@
+//! PDA derivations checked against known addresses.
+
+use solana_dex_tools::orca::pda;
+use solana_sdk::pubkey;
+
+#[test]
+fn bundled_position_address_is_seeded_by_bundle_mint() {
+    // The test vector of orca_whirlpools_client's `get_bundled_position_address`.
+    let position_bundle_mint = pubkey!("6sf6fSK6tTubFA2LMCeTzt4c6DeNVyA6WpDDgtWs7a5p");
+    let (address, _) = pda::get_bundled_position_address(&position_bundle_mint, 0).unwrap();
+    assert_eq!(address, pubkey!("9Zj8oWYVQdBCtqMn9Z3YyGo8o7hVXLEUZ5x5no5ykVm6"));
+
+    // Every index seeds a distinct position.
+    let (other, _) = pda::get_bundled_position_address(&position_bundle_mint, 1).unwrap();
+    assert_ne!(address, other);
+}
@@ -0,0 +1,352 @@
+//! The ported fee manager and swap simulation, checked against the orca core crate's quotes on fixture pools, and
+//! the swap builders' quotes.
+
+use orca_whirlpools_client::{
+    AdaptiveFeeConstants, AdaptiveFeeVariables, FixedTickArray, Oracle, Tick, TickArray, Whirlpool,
+    WhirlpoolRewardInfo,
+};
+use orca_whirlpools_core::{
+    swap_quote_by_input_token, swap_quote_by_output_token, tick_index_to_sqrt_price, TickArrays,
+    TICK_ARRAY_SIZE,
+};
+use solana_dex_tools::orca::{
+    fee::{compute_adaptive_fee_rate, FeeRateManager},
+    state::OrcaWhirlpoolState,
+    swap::OrcaSwapBuilder,
+    two_hop::OrcaTwoHopSwapBuilder,
+};
+use solana_dex_tools::mint::extensions::TOKEN_PROGRAM_ID;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::{solana_program::program_pack::Pack, state::Mint};
+use std::sync::Arc;
+
+const TICK_SPACING: u16 = 64;
+const TIMESTAMP: u64 = 1_700_000_000;
+const LIQUIDITY: u128 = 1_000_000_000;
+
+/// Initialized ticks as (tick index, liquidity net). At tick 0 the pool has `LIQUIDITY`: 600M in [-1280, 1280]
+/// and 400M in a range beyond the fixture tick arrays. Another 200M sits in [-3200, -640], below the price.
+const TICKS: [(i32, i128); 4] = [
+    (-3200, 200_000_000),
+    (-1280, 600_000_000),
+    (-640, -200_000_000),
+    (1280, -600_000_000),
+];
+
+fn tick_array(start_tick_index: i32) -> Arc<TickArray> {
+    let mut ticks: [Tick; TICK_ARRAY_SIZE] = std::array::from_fn(|_| Tick {
+        initialized: false,
+        liquidity_net: 0,
+        liquidity_gross: 0,
+        fee_growth_outside_a: 0,
+        fee_growth_outside_b: 0,
+        reward_growths_outside: [0; 3],
+    });
+    for (tick_index, liquidity_net) in TICKS {
+        let offset = (tick_index - start_tick_index) / TICK_SPACING as i32;
+        if tick_index.rem_euclid(TICK_SPACING as i32) == 0 && (0..TICK_ARRAY_SIZE as i32).contains(&offset) {
+            let tick = &mut ticks[offset as usize];
+            tick.initialized = true;
+            tick.liquidity_net = liquidity_net;
+            tick.liquidity_gross = liquidity_net.unsigned_abs();
+            // Arbitrary checkpoints, so crossing them is visible.
+            tick.fee_growth_outside_a = 7 << 64;
+            tick.fee_growth_outside_b = 3 << 64;
+        }
+    }
+    Arc::new(TickArray::FixedTickArray(FixedTickArray {
+        discriminator: [0; 8],
+        start_tick_index,
+        ticks,
+        whirlpool: Pubkey::default(),
+    }))
+}
+
+fn reward_info() -> WhirlpoolRewardInfo {
+    WhirlpoolRewardInfo {
+        mint: Pubkey::default(),
+        vault: Pubkey::default(),
+        authority: Pubkey::default(),
+        emissions_per_second_x64: 0,
+        growth_global_x64: 0,
+    }
+}
+
+/// A pool at tick 0 tracking the five tick arrays around it, with an adaptive fee if `adaptive_fee_variables` are
+/// given.
+fn state(adaptive_fee_variables: Option<AdaptiveFeeVariables>) -> OrcaWhirlpoolState {
+    let pubkey = Pubkey::new_unique();
+    // The fee tier index differs from the tick spacing for adaptive fee pools.
+    let fee_tier_index = if adaptive_fee_variables.is_some() { 1024 + TICK_SPACING } else { TICK_SPACING };
+    let whirlpool = Whirlpool {
+        discriminator: [0; 8],
+        whirlpools_config: Pubkey::default(),
+        whirlpool_bump: [0],
+        tick_spacing: TICK_SPACING,
+        fee_tier_index_seed: fee_tier_index.to_le_bytes(),
+        fee_rate: 3000,
+        protocol_fee_rate: 1300,
+        liquidity: LIQUIDITY,
+        sqrt_price: tick_index_to_sqrt_price(0),
+        tick_current_index: 0,
+        protocol_fee_owed_a: 0,
+        protocol_fee_owed_b: 0,
+        token_mint_a: Pubkey::new_unique(),
+        token_vault_a: Pubkey::default(),
+        fee_growth_global_a: 0,
+        token_mint_b: Pubkey::new_unique(),
+        token_vault_b: Pubkey::default(),
+        fee_growth_global_b: 0,
+        reward_last_updated_timestamp: TIMESTAMP - 60,
+        reward_infos: [reward_info(), reward_info(), reward_info()],
+    };
+    let oracle = adaptive_fee_variables.map(|adaptive_fee_variables| {
+        Arc::new(Oracle {
+            discriminator: [0; 8],
+            whirlpool: pubkey,
+            trade_enable_timestamp: 0,
+            adaptive_fee_constants: AdaptiveFeeConstants {
+                filter_period: 30,
+                decay_period: 600,
+                reduction_factor: 500,
+                adaptive_fee_control_factor: 5_000,
+                max_volatility_accumulator: 88 * 3 * 10_000,
+                tick_group_size: TICK_SPACING,
+                major_swap_threshold_ticks: TICK_SPACING,
+                reserved: [0; 16],
+            },
+            adaptive_fee_variables,
+            reserved: [0; 128],
+        })
+    });
+    let mint = Mint {
+        decimals: 6,
+        is_initialized: true,
+        ..Mint::default()
+    };
+    let mut mint_data = vec![0; Mint::LEN];
+    mint.pack_into_slice(&mut mint_data);
+    let tick_array_size = TICK_ARRAY_SIZE as i32 * TICK_SPACING as i32;
+    OrcaWhirlpoolState {
+        pubkey,
+        whirlpool: Arc::new(whirlpool),
+        tick_arrays: (-2..=2).map(|i| tick_array(i * tick_array_size)).collect(),
+        oracle,
+        mint_a: Arc::new(mint),
+        mint_b: Arc::new(mint),
+        mint_a_data: Arc::new(mint_data.clone()),
+        mint_b_data: Arc::new(mint_data),
+        token_program_a: TOKEN_PROGRAM_ID,
+        token_program_b: TOKEN_PROGRAM_ID,
+    }
+}
+
+/// An adaptive fee pool with volatility left over from a trade `elapsed` seconds ago.
+fn adaptive_state(elapsed: u64) -> OrcaWhirlpoolState {
+    state(Some(AdaptiveFeeVariables {
+        last_reference_update_timestamp: TIMESTAMP - elapsed,
+        last_major_swap_timestamp: TIMESTAMP - elapsed,
+        volatility_reference: 20_000,
+        tick_group_index_reference: 2,
+        volatility_accumulator: 50_000,
+        reserved: [0; 16],
+    }))
+}
+
+fn tick_arrays(state: &OrcaWhirlpoolState, a_to_b: bool) -> TickArrays {
+    let [first, second, third] = state.swap_tick_array_start_indexes(a_to_b);
+    TickArrays::Three(
+        state.tick_array_facade(first),
+        state.tick_array_facade(second),
+        state.tick_array_facade(third),
+    )
+}
+
+/// Checks exact in and exact out simulations of `amount` both ways against the core crate's quotes.
+fn assert_simulation_matches_quotes(state: &OrcaWhirlpoolState, amount: u64) {
+    for a_to_b in [true, false] {
+        let quote = swap_quote_by_input_token(
+            amount,
+            a_to_b,
+            0,
+            state.whirlpool_facade(),
+            state.oracle_facade(),
+            tick_arrays(state, a_to_b),
+            TIMESTAMP,
+            None,
+            None,
+        )
+        .unwrap();
+        let simulated = state.simulate_swap(amount, a_to_b, true, 0, TIMESTAMP).unwrap();
+        let (token_in, token_out) = if a_to_b {
+            (simulated.token_a, simulated.token_b)
+        } else {
+            (simulated.token_b, simulated.token_a)
+        };
+        assert_eq!((token_in, token_out), (quote.token_in, quote.token_est_out), "exact in, a_to_b {}", a_to_b);
+        assert_eq!(simulated.trade_fee, quote.trade_fee, "exact in, a_to_b {}", a_to_b);
+
+        // The output token is token A when swapping b to a.
+        let quote = swap_quote_by_output_token(
+            amount,
+            !a_to_b,
+            0,
+            state.whirlpool_facade(),
+            state.oracle_facade(),
+            tick_arrays(state, a_to_b),
+            TIMESTAMP,
+            None,
+            None,
+        )
+        .unwrap();
+        let simulated = state.simulate_swap(amount, a_to_b, false, 0, TIMESTAMP).unwrap();
+        let (token_in, token_out) = if a_to_b {
+            (simulated.token_a, simulated.token_b)
+        } else {
+            (simulated.token_b, simulated.token_a)
+        };
+        assert_eq!((token_in, token_out), (quote.token_est_in, quote.token_out), "exact out, a_to_b {}", a_to_b);
+        assert_eq!(simulated.trade_fee, quote.trade_fee, "exact out, a_to_b {}", a_to_b);
+    }
+}
+
+#[test]
+fn static_fee_simulation_matches_quotes() {
+    let state = state(None);
+    // Within the current range, then across two initialized ticks either way.
+    assert_simulation_matches_quotes(&state, 1_000_000);
+    assert_simulation_matches_quotes(&state, 100_000_000);
+}
+
+#[test]
+fn adaptive_fee_simulation_matches_quotes() {
+    // Volatility references kept (within the filter period), decayed, and reset (past the decay period).
+    for elapsed in [10, 100, 1_000] {
+        let state = adaptive_state(elapsed);
+        assert_simulation_matches_quotes(&state, 1_000_000);
+        assert_simulation_matches_quotes(&state, 100_000_000);
+    }
+}
+
+#[test]
+fn chained_simulations_match_quotes() {
+    for state in [state(None), adaptive_state(100)] {
+        let simulated = state.simulate_swap(100_000_000, true, true, 0, TIMESTAMP).unwrap();
+        // The post-trade state, including the oracle's updated variables, quotes as the program would see it.
+        assert_simulation_matches_quotes(&simulated.state, 1_000_000);
+        assert_simulation_matches_quotes(&simulated.state, 20_000_000);
+    }
+}
+
+#[test]
+fn simulation_crosses_ticks_and_accrues_fees() {
+    let state = state(None);
+    let simulated = state.simulate_swap(100_000_000, true, true, 0, TIMESTAMP).unwrap();
+
+    let crossed: Vec<i32> = simulated.crossed_ticks.iter().map(|crossed| crossed.tick_index).collect();
+    assert_eq!(crossed, vec![-640, -1280]);
+    let next = &simulated.state.whirlpool;
+    // Entering [-3200, -640] adds 200M, leaving [-1280, 1280] removes 600M.
+    assert_eq!(next.liquidity, LIQUIDITY + 200_000_000 - 600_000_000);
+    assert!(next.tick_current_index < -1280 && next.tick_current_index >= -3200);
+    assert_eq!(next.protocol_fee_owed_a, simulated.protocol_fee);
+    // The protocol's cut is floored step by step, of which there are three.
+    assert!((simulated.trade_fee * 1300 / 10_000).abs_diff(simulated.protocol_fee) < 3);
+    assert!(next.fee_growth_global_a > 0 && next.fee_growth_global_b == 0);
+    assert_eq!(next.reward_last_updated_timestamp, TIMESTAMP);
+
+    for crossed in &simulated.crossed_ticks {
+        let fee_growth_outside_a = simulated.state.tick(crossed.tick_index).unwrap().fee_growth_outside_a;
+        assert_eq!(crossed.after.fee_growth_outside_a, fee_growth_outside_a);
+        // Flipped against the global growths at the crossing: token A's has grown by then, token B's never does.
+        let growth_at_crossing = fee_growth_outside_a.wrapping_add(7 << 64);
+        assert!(growth_at_crossing > 0 && growth_at_crossing < next.fee_growth_global_a);
+        assert_eq!(crossed.after.fee_growth_outside_b, 0u128.wrapping_sub(3 << 64));
+        // The pre-trade state is untouched.
+        assert_eq!(state.tick(crossed.tick_index).unwrap().fee_growth_outside_a, 7 << 64);
+    }
+    // The whirlpool and both tick arrays holding the crossed ticks.
+    assert_eq!(simulated.changed_accounts().unwrap().len(), 2);
+}
+
+#[test]
+fn current_fee_rate_matches_quotes() {
+    assert_eq!(state(None).current_fee_rate(TIMESTAMP).unwrap(), 3000);
+    for elapsed in [10, 100, 1_000] {
+        let state = adaptive_state(elapsed);
+        // A swap too small to leave the current tick group pays the first step's rate throughout. Swapping b to
+        // a, as a to b would start on the boundary of tick group 0 and step straight into the next.
+        let quote = swap_quote_by_input_token(
+            1_000,
+            false,
+            0,
+            state.whirlpool_facade(),
+            state.oracle_facade(),
+            tick_arrays(&state, false),
+            TIMESTAMP,
+            None,
+            None,
+        )
+        .unwrap();
+        let fee_rate = state.current_fee_rate(TIMESTAMP).unwrap();
+        assert_eq!((fee_rate, fee_rate), (quote.trade_fee_rate_min, quote.trade_fee_rate_max), "elapsed {}", elapsed);
+        // Past the decay period the references reset, leaving only the static fee.
+        assert_eq!(fee_rate > 3000, elapsed < 600, "elapsed {}", elapsed);
+    }
+}
+
+#[test]
+fn fee_rate_manager_static_and_adaptive() {
+    let mut static_fee = FeeRateManager::new(true, 100, TIMESTAMP, 3000, &None).unwrap();
+    static_fee.update_volatility_accumulator();
+    assert_eq!(static_fee.get_total_fee_rate(), 3000);
+    assert_eq!(static_fee.get_bounded_sqrt_price_target(1 << 64, LIQUIDITY), (1 << 64, false));
+    assert!(static_fee.adaptive_fee_info().is_none());
+
+    let adaptive_fee_info = adaptive_state(100).adaptive_fee_info();
+    let mut adaptive_fee = FeeRateManager::new(true, 0, TIMESTAMP, 3000, &adaptive_fee_info).unwrap();
+    adaptive_fee.update_volatility_accumulator();
+    let info = adaptive_fee.adaptive_fee_info().unwrap();
+    let expected = 3000 + compute_adaptive_fee_rate(&info.constants, &info.variables);
+    assert_eq!(adaptive_fee.get_total_fee_rate(), expected);
+    // Stepping a to b from tick 0 is bounded to the lower edge of tick group 0.
+    assert_eq!(
+        adaptive_fee.get_bounded_sqrt_price_target(tick_index_to_sqrt_price(-1000), LIQUIDITY),
+        (tick_index_to_sqrt_price(0), false)
+    );
+    // One tick group further from the reference pays more.
+    adaptive_fee.advance_tick_group();
+    adaptive_fee.update_volatility_accumulator();
+    assert!(adaptive_fee.get_total_fee_rate() > expected);
+}
+
+#[test]
+fn swap_builder_rejects_sqrt_price_limit() {
+    let builder = OrcaSwapBuilder::new(state(None), false, 1_000_000)
+        .token_authority(Pubkey::new_unique())
+        .token_owner_accounts(Pubkey::new_unique(), Pubkey::new_unique())
+        .timestamp(TIMESTAMP);
+    let swap = builder.clone().build().unwrap();
+    assert_eq!(swap.quote.token_in(), 1_000_000);
+
+    // The quote would ignore the limit, so the instruction would disagree with it.
+    let limited = builder.sqrt_price_limit(tick_index_to_sqrt_price(640));
+    assert!(limited.build().is_err());
+}
+
+#[test]
+fn two_hop_swap_builder_rejects_sqrt_price_limits() {
+    let one = state(None);
+    let mut two = state(None);
+    Arc::make_mut(&mut two.whirlpool).token_mint_a = one.whirlpool.token_mint_b;
+    let builder = OrcaTwoHopSwapBuilder::new(one.clone(), two, one.whirlpool.token_mint_a, 1_000_000)
+        .token_authority(Pubkey::new_unique())
+        .token_owner_accounts(Pubkey::new_unique(), Pubkey::new_unique())
+        .timestamp(TIMESTAMP);
+    let swap = builder.clone().build().unwrap();
+    assert_eq!(swap.quote.token_in(), 1_000_000);
+
+    for limits in [(tick_index_to_sqrt_price(-640), 0), (0, tick_index_to_sqrt_price(-640))] {
+        assert!(builder.clone().sqrt_price_limits(limits.0, limits.1).build().is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//! `TokenBucket` refill, and the coalescing of account fetches by `RateLimited`.
+
+use async_trait::async_trait;
+use solana_dex_tools::common::{
+    combinators::{is_retriable, RpcTimeout},
+    rate_limit::{RateLimitConfig, RateLimited, SharedError, TokenBucket},
+    rpc::{RpcProvider, RpcResponse},
+    types::AnyResult,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Serves accounts whose data is their pubkey, except `missing`, and records the pubkeys of every call.
+#[derive(Default)]
+struct MockProvider {
+    missing: HashSet<Pubkey>,
+    max_accounts_per_rpc_call: usize,
+    fail: bool,
+    calls: Mutex<Vec<Vec<Pubkey>>>,
+}
+
+impl MockProvider {
+    fn new(max_accounts_per_rpc_call: usize) -> Self {
+        Self {
+            max_accounts_per_rpc_call,
+            ..Self::default()
+        }
+    }
+
+    fn calls(&self) -> Vec<Vec<Pubkey>> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn account(&self, pubkey: &Pubkey) -> Option<Account> {
+        (!self.missing.contains(pubkey)).then(|| Account {
+            data: pubkey.to_bytes().to_vec(),
+            ..Account::default()
+        })
+    }
+}
+
+#[async_trait]
+impl RpcProvider for MockProvider {
+    type AccountType = Account;
+
+    async fn get_account(&self, pubkey: &Pubkey) -> AnyResult<RpcResponse<Account>> {
+        let response = self.get_multiple_accounts(std::slice::from_ref(pubkey)).await?;
+        Ok(RpcResponse {
+            result: response.result[0].clone().ok_or_else(|| anyhow::anyhow!("AccountNotFound"))?,
+            response_time: response.response_time,
+            slot: response.slot,
+        })
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> AnyResult<RpcResponse<Vec<Option<Account>>>> {
+        let call = {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(pubkeys.to_vec());
+            calls.len() as u64
+        };
+        if self.fail {
+            return Err(RpcTimeout {
+                method: "getMultipleAccounts",
+                timeout: Duration::from_secs(1),
+            }
+            .into());
+        }
+        Ok(RpcResponse {
+            result: pubkeys.iter().map(|pubkey| self.account(pubkey)).collect(),
+            response_time: call,
+            slot: Some(100 + call),
+        })
+    }
+
+    fn max_accounts_per_rpc_call(&self) -> usize {
+        self.max_accounts_per_rpc_call
+    }
+}
+
+fn coalescing(inner: MockProvider) -> RateLimited<MockProvider> {
+    let config = RateLimitConfig {
+        credits_per_second: 1_000.0,
+        burst: 1_000.0,
+        coalesce_window: Some(Duration::from_millis(5)),
+        ..RateLimitConfig::default()
+    };
+    RateLimited::new(inner, config).unwrap()
+}
+
+/// Unwraps the error of `result`, as `RpcResponse` isn't `Debug`.
+fn error<T>(result: AnyResult<T>) -> anyhow::Error {
+    match result {
+        Ok(_) => panic!("Expected an error"),
+        Err(e) => e,
+    }
+}
+
+fn data(account: &Option<Account>) -> Option<Pubkey> {
+    account.as_ref().map(|account| Pubkey::try_from(account.data.as_slice()).unwrap())
+}
+
+fn some(pubkeys: &[Pubkey]) -> Vec<Option<Pubkey>> {
+    pubkeys.iter().copied().map(Some).collect()
+}
+
+#[test]
+fn token_bucket_rejects_invalid_settings() {
+    assert!(TokenBucket::new(0.0, 1.0).is_err());
+    assert!(TokenBucket::new(1.0, -1.0).is_err());
+    assert!(TokenBucket::new(f64::NAN, 1.0).is_err());
+    assert!(TokenBucket::new(1.0, f64::NAN).is_err());
+}
+
+#[test]
+fn token_bucket_refills_up_to_capacity() {
+    let bucket = TokenBucket::new(10.0, 100.0).unwrap();
+    // Starts full, and costs above the capacity are capped at it.
+    assert!(bucket.try_acquire(25.0).is_ok());
+
+    // Empty, so 5 credits are 50ms away, less whatever refilled since.
+    let wait = bucket.try_acquire(5.0).unwrap_err();
+    assert!(wait <= Duration::from_millis(50) && wait > Duration::from_millis(30), "{:?}", wait);
+    std::thread::sleep(wait);
+    assert!(bucket.try_acquire(5.0).is_ok());
+
+    // Refilling stops at the capacity.
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(bucket.try_acquire(10.0).is_ok());
+    assert!(bucket.try_acquire(5.0).is_err());
+}
+
+#[tokio::test]
+async fn token_bucket_acquire_waits_for_refill() {
+    let bucket = TokenBucket::new(2.0, 100.0).unwrap();
+    let start = Instant::now();
+    bucket.acquire(2.0).await;
+    assert!(start.elapsed() < Duration::from_millis(10));
+    bucket.acquire(2.0).await;
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
+
+#[tokio::test]
+async fn concurrent_fetches_are_coalesced_and_fanned_out() {
+    let [a, b, c] = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let missing = Pubkey::new_unique();
+    let provider = coalescing(MockProvider {
+        missing: HashSet::from([missing]),
+        ..MockProvider::new(100)
+    });
+
+    let (first_pubkeys, second_pubkeys) = ([a, b], [c, missing, b]);
+    let (single, first, second, not_found) = tokio::join!(
+        provider.get_account(&a),
+        provider.get_multiple_accounts(&first_pubkeys),
+        provider.get_multiple_accounts(&second_pubkeys),
+        provider.get_account(&missing),
+    );
+    // One upstream call over the deduplicated pubkeys, in the order they were first asked for.
+    assert_eq!(provider.inner().calls(), vec![vec![a, b, c, missing]]);
+
+    assert_eq!(data(&Some(single.unwrap().result)), Some(a));
+    let first = first.unwrap();
+    assert_eq!(first.result.iter().map(data).collect::<Vec<_>>(), vec![Some(a), Some(b)]);
+    assert_eq!((first.response_time, first.slot), (1, Some(101)));
+    let second = second.unwrap();
+    assert_eq!(second.result.iter().map(data).collect::<Vec<_>>(), vec![Some(c), None, Some(b)]);
+    assert!(error(not_found).to_string().contains("AccountNotFound"));
+
+    // The next fetch starts a new batch.
+    provider.get_account(&c).await.unwrap();
+    assert_eq!(provider.inner().calls().len(), 2);
+}
+
+#[tokio::test]
+async fn coalesced_batches_are_split_by_the_inner_limit() {
+    let pubkeys: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+    let provider = coalescing(MockProvider::new(2));
+
+    let (first, second) = tokio::join!(
+        provider.get_multiple_accounts(&pubkeys[..3]),
+        provider.get_multiple_accounts(&pubkeys[2..]),
+    );
+    let calls = provider.inner().calls();
+    assert_eq!(calls, vec![pubkeys[..2].to_vec(), pubkeys[2..4].to_vec(), pubkeys[4..].to_vec()]);
+
+    let first = first.unwrap();
+    assert_eq!(first.result.iter().map(data).collect::<Vec<_>>(), some(&pubkeys[..3]));
+    // The latest of the chunks' slots.
+    assert_eq!(first.slot, Some(103));
+    let second = second.unwrap();
+    assert_eq!(second.result.iter().map(data).collect::<Vec<_>>(), some(&pubkeys[2..]));
+}
+
+#[tokio::test]
+async fn coalesced_errors_are_shared_with_their_original() {
+    let provider = coalescing(MockProvider {
+        fail: true,
+        ..MockProvider::new(100)
+    });
+    let [a, b] = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let pubkeys = [b];
+    let (first, second) = tokio::join!(provider.get_account(&a), provider.get_multiple_accounts(&pubkeys));
+    assert_eq!(provider.inner().calls().len(), 1);
+
+    for error in [error(first), error(second)] {
+        assert!(error.is::<SharedError>());
+        assert!(SharedError::original(&error).is::<RpcTimeout>());
+        assert_eq!(error.to_string(), SharedError::original(&error).to_string());
+        assert!(is_retriable(&error));
+    }
+}
+
+#[tokio::test]
+async fn dropped_caller_does_not_fail_its_batch() {
+    let [a, b] = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let provider = Arc::new(coalescing(MockProvider::new(100)));
+
+    let dropped = tokio::spawn({
+        let provider = provider.clone();
+        async move { provider.get_account(&a).await }
+    });
+    // Let it start the batch, then drop it.
+    tokio::task::yield_now().await;
+    dropped.abort();
+
+    let response = provider.get_multiple_accounts(&[a, b]).await.unwrap();
+    assert_eq!(response.result.iter().map(data).collect::<Vec<_>>(), vec![Some(a), Some(b)]);
+    assert_eq!(provider.inner().calls(), vec![vec![a, b]]);
+    assert!(dropped.await.is_err_and(|e| e.is_cancelled()));
+}
+
+#[tokio::test]
+async fn fetches_pass_straight_through_without_coalescing() {
+    let config = RateLimitConfig {
+        coalesce_window: None,
+        ..RateLimitConfig::default()
+    };
+    let provider = RateLimited::new(MockProvider::new(100), config).unwrap();
+    let [a, b] = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+    let pubkeys = [a, b];
+    let (first, second) = tokio::join!(provider.get_account(&a), provider.get_multiple_accounts(&pubkeys));
+    assert!(first.is_ok() && second.is_ok());
+    assert_eq!(provider.inner().calls().len(), 2);
+    // Both calls spent a credit from the bucket of 10.
+    assert!(provider.bucket().try_acquire(8.0).is_ok());
+    assert!(provider.bucket().try_acquire(1.0).is_err());
+}
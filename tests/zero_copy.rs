@@ -0,0 +1,70 @@
+//! The lifecycle of `ZeroCopyAccount` and `LazyAccount` as updates come and go.
+
+use solana_dex_tools::common::{
+    deserialize::Deserializable,
+    state::{AccountLifecycle, AccountState},
+    types::AnyResult,
+    zero_copy::{LazyAccount, ZeroCopyAccount},
+};
+use solana_sdk::pubkey::Pubkey;
+
+/// Deserializes from a single byte, which must be non-zero.
+struct NonZero(u8);
+
+impl Deserializable for NonZero {
+    fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+        match bytes {
+            [byte] if *byte != 0 => Ok(NonZero(*byte)),
+            _ => Err(anyhow::anyhow!("Expected a single non-zero byte, got {:?}", bytes)),
+        }
+    }
+}
+
+#[test]
+fn lazy_account_stays_errored_on_unchanged_bad_bytes() {
+    let account = LazyAccount::<NonZero>::new_from_bytes(Pubkey::new_unique(), vec![1], 0);
+    assert_eq!(account.get().unwrap().0, 1);
+
+    // Updates can't reject bad bytes, the `get()` after them does.
+    assert!(account.update(vec![0], 1).unwrap());
+    assert_eq!(account.lifecycle(), AccountLifecycle::Live);
+    assert!(account.get().is_err());
+    assert_eq!(account.lifecycle(), AccountLifecycle::Errored);
+
+    // Polling the same bad bytes again doesn't revive it.
+    assert!(!account.update(vec![0], 2).unwrap());
+    assert_eq!(account.lifecycle(), AccountLifecycle::Errored);
+    assert_eq!(account.last_update_time(), 2);
+
+    assert!(account.update(vec![2], 3).unwrap());
+    assert_eq!(account.lifecycle(), AccountLifecycle::Live);
+    assert_eq!(account.get().unwrap().0, 2);
+}
+
+#[test]
+fn unchanged_bytes_revive_closed_accounts() {
+    let lazy = LazyAccount::<NonZero>::new_from_bytes(Pubkey::new_unique(), vec![1], 0);
+    assert!(lazy.mark_closed());
+    assert!(!lazy.update(vec![1], 1).unwrap());
+    assert_eq!(lazy.lifecycle(), AccountLifecycle::Live);
+
+    let zero_copy = ZeroCopyAccount::<[u8; 4]>::new_initialized_from_bytes(Pubkey::new_unique(), vec![1; 4], 0, 0)
+        .unwrap();
+    assert!(zero_copy.mark_closed());
+    assert!(!zero_copy.update(vec![1; 4], 1).unwrap());
+    assert_eq!(zero_copy.lifecycle(), AccountLifecycle::Live);
+}
+
+#[test]
+fn zero_copy_account_recovers_on_unchanged_good_bytes() {
+    let account = ZeroCopyAccount::<[u8; 4]>::new_initialized_from_bytes(Pubkey::new_unique(), vec![1; 4], 0, 0)
+        .unwrap();
+    // Too short, so rejected without being stored.
+    assert!(account.update(vec![2; 2], 1).is_err());
+    assert_eq!(account.lifecycle(), AccountLifecycle::Errored);
+    assert_eq!(*account.get(), [1; 4]);
+
+    // The stored bytes are good, so seeing them again means the account is fine.
+    assert!(!account.update(vec![1; 4], 2).unwrap());
+    assert_eq!(account.lifecycle(), AccountLifecycle::Live);
+}